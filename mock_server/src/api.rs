@@ -0,0 +1,388 @@
+// The http endpoints, answering like the real server: {"Ok": ..} or {"Err": ".."} with a 200,
+// 401 when the bearer token is missing or expired
+use crate::{unix_now, Account, Challenge, MockState, Shared};
+use axum::extract::{Form, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Json, Response};
+use modules::server_interaction::structs::{Page, UserProfile};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::str::FromStr;
+
+const CHALLENGE_LIFETIME: u64 = 300;
+const CHALLENGE_STATEMENT: &str = "Sign in to Chatuza with your wallet.";
+
+#[derive(Deserialize)]
+pub(crate) struct CreateTokenAccountForm {
+    wallet_address: String,
+    token_mint_address: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct FundWalletForm {
+    wallet_address: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SignUpForm {
+    username: String,
+    display_name: String,
+    wallet_pubkey: String,
+    avatar_url: Option<String>,
+    password: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SignInForm {
+    username: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ChallengeForm {
+    wallet_pubkey: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct WalletSignInForm {
+    wallet_pubkey: String,
+    message: String,
+    signature: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct RefreshForm {
+    refresh_token: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SearchQuery {
+    q: String,
+    page: u32,
+    per_page: u32,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AddContactForm {
+    username: String,
+}
+
+// Wallet
+
+pub(crate) async fn create_token_account(
+    State(state): State<Shared>,
+    Form(form): Form<CreateTokenAccountForm>,
+) -> Response {
+    if Pubkey::from_str(&form.wallet_address).is_err()
+        || Pubkey::from_str(&form.token_mint_address).is_err()
+    {
+        return refused("invalid wallet or mint address");
+    }
+    let mut state = state.lock().unwrap();
+    state
+        .token_accounts
+        .push((form.wallet_address, form.token_mint_address));
+    ok(json!({
+        "signatures": [
+            Signature::new_unique().to_string(),
+            Signature::new_unique().to_string()
+        ]
+    }))
+}
+
+pub(crate) async fn fund_wallet(
+    State(state): State<Shared>,
+    Form(form): Form<FundWalletForm>,
+) -> Response {
+    if Pubkey::from_str(&form.wallet_address).is_err() {
+        return refused("invalid wallet address");
+    }
+    state
+        .lock()
+        .unwrap()
+        .funded_wallets
+        .push(form.wallet_address);
+    ok(Signature::new_unique().to_string())
+}
+
+// Accounts
+
+pub(crate) async fn sign_up(State(state): State<Shared>, Form(form): Form<SignUpForm>) -> Response {
+    let mut state = state.lock().unwrap();
+    if form.username.trim().is_empty() || form.password.is_empty() {
+        return refused("username and password are required");
+    }
+    if state.accounts.contains_key(&form.username) {
+        return refused("the username is taken");
+    }
+    let profile = UserProfile {
+        username: form.username,
+        display_name: form.display_name,
+        wallet_pubkey: form.wallet_pubkey,
+        avatar_url: form.avatar_url.filter(|url| !url.is_empty()),
+    };
+    state.accounts.insert(
+        profile.username.clone(),
+        Account {
+            profile: profile.clone(),
+            password: form.password,
+        },
+    );
+    ok(state.start_session(profile))
+}
+
+pub(crate) async fn sign_in(State(state): State<Shared>, Form(form): Form<SignInForm>) -> Response {
+    let mut state = state.lock().unwrap();
+    let profile = match state.accounts.get(&form.username) {
+        Some(account) if account.password == form.password => account.profile.clone(),
+        _ => return refused("wrong username or password"),
+    };
+    ok(state.start_session(profile))
+}
+
+pub(crate) async fn challenge(
+    State(state): State<Shared>,
+    headers: HeaderMap,
+    Form(form): Form<ChallengeForm>,
+) -> Response {
+    let domain = match headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+    {
+        Some(host) => host.to_string(),
+        None => return refused("the request has no host"),
+    };
+    let nonce = random_token();
+    let issued_at = unix_now();
+    let expires_at = issued_at + CHALLENGE_LIFETIME;
+    state.lock().unwrap().challenges.insert(
+        nonce.clone(),
+        Challenge {
+            wallet_pubkey: form.wallet_pubkey,
+            expires_at,
+        },
+    );
+    ok(json!({
+        "domain": domain,
+        "statement": CHALLENGE_STATEMENT,
+        "nonce": nonce,
+        "issued_at": issued_at,
+        "expires_at": expires_at,
+    }))
+}
+
+pub(crate) async fn wallet_sign_in(
+    State(state): State<Shared>,
+    Form(form): Form<WalletSignInForm>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    let nonce = form
+        .message
+        .lines()
+        .find_map(|line| line.strip_prefix("Nonce: "))
+        .unwrap_or_default();
+    // a challenge is good for one attempt
+    let challenge = match state.challenges.remove(nonce) {
+        Some(challenge) if challenge.expires_at > unix_now() => challenge,
+        _ => return refused("the sign in challenge is unknown or expired"),
+    };
+    if challenge.wallet_pubkey != form.wallet_pubkey || !form.message.contains(&form.wallet_pubkey)
+    {
+        return refused("the sign in challenge was issued for another wallet");
+    }
+    let signed = match (
+        Pubkey::from_str(&form.wallet_pubkey),
+        Signature::from_str(&form.signature),
+    ) {
+        (Ok(pubkey), Ok(signature)) => signature.verify(pubkey.as_ref(), form.message.as_bytes()),
+        _ => false,
+    };
+    if !signed {
+        return refused("the signature doesn't match the wallet");
+    }
+    let profile = match state
+        .accounts
+        .values()
+        .find(|account| account.profile.wallet_pubkey == form.wallet_pubkey)
+    {
+        Some(account) => account.profile.clone(),
+        None => return refused("no account uses this wallet"),
+    };
+    ok(state.start_session(profile))
+}
+
+pub(crate) async fn refresh(
+    State(state): State<Shared>,
+    Form(form): Form<RefreshForm>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    // refresh tokens rotate, the used one is gone
+    let profile = match state.refresh_tokens.remove(&form.refresh_token) {
+        Some(username) => match state.accounts.get(&username) {
+            Some(account) => account.profile.clone(),
+            None => return unauthorized(),
+        },
+        None => return unauthorized(),
+    };
+    ok(state.start_session(profile))
+}
+
+pub(crate) async fn sign_out(State(state): State<Shared>, headers: HeaderMap) -> Response {
+    let mut state = state.lock().unwrap();
+    let token = match bearer_token(&headers) {
+        Some(token) => token,
+        None => return unauthorized(),
+    };
+    let username = match state.access_tokens.remove(&token) {
+        Some((username, _)) => username,
+        None => return unauthorized(),
+    };
+    state.refresh_tokens.retain(|_, owner| *owner != username);
+    ok(())
+}
+
+pub(crate) async fn profile(State(state): State<Shared>, headers: HeaderMap) -> Response {
+    let state = state.lock().unwrap();
+    match state.authorize(&headers) {
+        Some(username) => ok(state.accounts[&username].profile.clone()),
+        None => unauthorized(),
+    }
+}
+
+// Contacts
+
+pub(crate) async fn search_users(
+    State(state): State<Shared>,
+    headers: HeaderMap,
+    Query(query): Query<SearchQuery>,
+) -> Response {
+    let state = state.lock().unwrap();
+    if state.authorize(&headers).is_none() {
+        return unauthorized();
+    }
+    let needle = query.q.to_lowercase();
+    let matches: Vec<UserProfile> = state
+        .accounts
+        .values()
+        .map(|account| &account.profile)
+        .filter(|profile| {
+            profile.username.to_lowercase().contains(&needle)
+                || profile.display_name.to_lowercase().contains(&needle)
+                || profile.wallet_pubkey.to_lowercase().contains(&needle)
+        })
+        .cloned()
+        .collect();
+    let per_page = query.per_page.max(1);
+    ok(Page {
+        total: matches.len() as u64,
+        items: matches
+            .into_iter()
+            .skip((query.page * per_page) as usize)
+            .take(per_page as usize)
+            .collect(),
+        page: query.page,
+        per_page,
+    })
+}
+
+pub(crate) async fn add_contact(
+    State(state): State<Shared>,
+    headers: HeaderMap,
+    Form(form): Form<AddContactForm>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    let username = match state.authorize(&headers) {
+        Some(username) => username,
+        None => return unauthorized(),
+    };
+    let contact = match state.accounts.get(&form.username) {
+        Some(account) => account.profile.clone(),
+        None => return refused("no user with this username"),
+    };
+    if contact.username == username {
+        return refused("you can't add yourself as a contact");
+    }
+    let contacts = state.contacts.entry(username).or_default();
+    if !contacts.contains(&contact.username) {
+        contacts.push(contact.username.clone());
+    }
+    ok(contact)
+}
+
+pub(crate) async fn list_contacts(State(state): State<Shared>, headers: HeaderMap) -> Response {
+    let state = state.lock().unwrap();
+    let username = match state.authorize(&headers) {
+        Some(username) => username,
+        None => return unauthorized(),
+    };
+    let contacts: Vec<UserProfile> = state
+        .contacts
+        .get(&username)
+        .map(|contacts| {
+            contacts
+                .iter()
+                .filter_map(|contact| state.accounts.get(contact))
+                .map(|account| account.profile.clone())
+                .collect()
+        })
+        .unwrap_or_default();
+    ok(contacts)
+}
+
+impl MockState {
+    // issues a new token pair for `profile`, the body of every successful sign in
+    fn start_session(&mut self, profile: UserProfile) -> serde_json::Value {
+        let access_token = random_token();
+        let refresh_token = random_token();
+        self.access_tokens.insert(
+            access_token.clone(),
+            (profile.username.clone(), unix_now() + self.token_lifetime),
+        );
+        self.refresh_tokens
+            .insert(refresh_token.clone(), profile.username.clone());
+        json!({
+            "user": profile,
+            "access_token": access_token,
+            "refresh_token": refresh_token,
+            "expires_in": self.token_lifetime,
+        })
+    }
+
+    // the user of a valid bearer token
+    pub(crate) fn authorize(&self, headers: &HeaderMap) -> Option<String> {
+        let token = bearer_token(headers)?;
+        match self.access_tokens.get(&token) {
+            Some((username, expires_at)) if *expires_at > unix_now() => Some(username.clone()),
+            _ => None,
+        }
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::to_string)
+}
+
+fn random_token() -> String {
+    let bytes: [u8; 24] = rand::thread_rng().gen();
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub(crate) fn ok<T: Serialize>(value: T) -> Response {
+    Json(json!({ "Ok": value })).into_response()
+}
+
+pub(crate) fn refused(message: &str) -> Response {
+    Json(json!({ "Err": message })).into_response()
+}
+
+pub(crate) fn unauthorized() -> Response {
+    (StatusCode::UNAUTHORIZED, "invalid or expired token").into_response()
+}
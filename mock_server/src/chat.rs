@@ -0,0 +1,365 @@
+// The chat websocket: the client frames of modules::p2p_connection answered like the real server.
+// Messages are stored for the resume of later connections, receipts and presence go to whoever
+// is connected at the time
+use crate::api::unauthorized;
+use crate::{unix_now, Connection, MockState, Outgoing, Shared};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::Response;
+use futures_util::{SinkExt, StreamExt};
+use modules::p2p_connection::structs::{
+    ChatMessage, ClientFrame, MessageStatus, PresenceStatus, PresenceUpdate, RetentionUpdate,
+    ServerFrame,
+};
+use tokio::sync::mpsc;
+
+pub(crate) async fn connect(
+    ws: WebSocketUpgrade,
+    State(state): State<Shared>,
+    headers: HeaderMap,
+) -> Response {
+    let username = match state.lock().unwrap().authorize(&headers) {
+        Some(username) => username,
+        None => return unauthorized(),
+    };
+    ws.on_upgrade(move |socket| serve(socket, state, username))
+}
+
+async fn serve(socket: WebSocket, state: Shared, username: String) {
+    let (mut write, mut read) = socket.split();
+    let (frames_tx, mut frames_rx) = mpsc::unbounded_channel();
+    let id = state.lock().unwrap().open_connection(&username, frames_tx);
+
+    let writer = tokio::spawn(async move {
+        while let Some(outgoing) = frames_rx.recv().await {
+            let message = match outgoing {
+                Outgoing::Frame(frame) => match serde_json::to_string(&frame) {
+                    Ok(text) => Message::Text(text),
+                    Err(_) => continue,
+                },
+                Outgoing::Close => {
+                    let _ = write.send(Message::Close(None)).await;
+                    return;
+                }
+            };
+            if write.send(message).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    while let Some(Ok(message)) = read.next().await {
+        match message {
+            Message::Text(text) => {
+                let mut state = state.lock().unwrap();
+                match serde_json::from_str::<ClientFrame>(&text) {
+                    Ok(frame) => state.handle_frame(id, &username, frame),
+                    Err(e) => state.send_to(
+                        id,
+                        ServerFrame::Error {
+                            message: format!("unknown frame \n {}", e),
+                        },
+                    ),
+                }
+            }
+            Message::Close(_) => break,
+            _ => {}
+        }
+    }
+
+    state.lock().unwrap().close_connection(id, &username);
+    writer.abort();
+}
+
+// the usernames of a "direct:a:b" conversation
+pub(crate) fn participants(conversation_id: &str) -> Vec<String> {
+    match conversation_id.strip_prefix("direct:") {
+        Some(pair) => pair.split(':').map(str::to_string).collect(),
+        None => vec![],
+    }
+}
+
+impl MockState {
+    fn open_connection(&mut self, username: &str, frames: mpsc::UnboundedSender<Outgoing>) -> u64 {
+        self.next_connection += 1;
+        let id = self.next_connection;
+        self.connections.insert(
+            id,
+            Connection {
+                username: username.to_string(),
+                frames,
+                watched: vec![],
+            },
+        );
+        id
+    }
+
+    // the user goes offline with its last connection
+    fn close_connection(&mut self, id: u64, username: &str) {
+        self.connections.remove(&id);
+        let still_connected = self
+            .connections
+            .values()
+            .any(|connection| connection.username == username);
+        if !still_connected {
+            self.set_presence(username, PresenceStatus::Offline);
+        }
+    }
+
+    fn handle_frame(&mut self, id: u64, username: &str, frame: ClientFrame) {
+        match frame {
+            ClientFrame::Resume { after } => {
+                let after = after.unwrap_or(0);
+                let missed: Vec<ChatMessage> = self
+                    .messages
+                    .iter()
+                    .filter(|message| message.id > after)
+                    .filter(|message| {
+                        participants(&message.conversation_id).contains(&username.to_string())
+                    })
+                    .cloned()
+                    .collect();
+                for message in missed {
+                    self.send_to(id, ServerFrame::Message(message));
+                }
+                // the timers may have changed while the client was away
+                let timers: Vec<RetentionUpdate> = self
+                    .retention
+                    .values()
+                    .filter(|update| {
+                        participants(&update.conversation_id).contains(&username.to_string())
+                    })
+                    .cloned()
+                    .collect();
+                for update in timers {
+                    self.send_to(id, ServerFrame::Retention(update));
+                }
+            }
+            ClientFrame::Ack { id: message_id } => {
+                let message = self
+                    .messages
+                    .iter_mut()
+                    .find(|message| message.id == message_id && message.sender != username);
+                if let Some(message) = message {
+                    if message.status < MessageStatus::Delivered {
+                        message.status = MessageStatus::Delivered;
+                        let (sender, conversation_id) =
+                            (message.sender.clone(), message.conversation_id.clone());
+                        self.send_to_user(
+                            &sender,
+                            ServerFrame::Receipt {
+                                conversation_id,
+                                up_to: message_id,
+                                status: MessageStatus::Delivered,
+                            },
+                            None,
+                        );
+                    }
+                }
+            }
+            ClientFrame::Send {
+                client_id,
+                conversation_id,
+                body,
+            } => {
+                let key = (username.to_string(), client_id.clone());
+                if let Some(message_id) = self.accepted.get(&key).copied() {
+                    // sent again after a lost acceptance, the message is stored already
+                    let sent_at = self.messages[(message_id - 1) as usize].sent_at;
+                    self.send_to(
+                        id,
+                        ServerFrame::Accepted {
+                            client_id,
+                            id: message_id,
+                            sent_at,
+                        },
+                    );
+                    return;
+                }
+                let reason = if !participants(&conversation_id).contains(&username.to_string()) {
+                    Some("you aren't part of this conversation")
+                } else if body.trim().is_empty() {
+                    Some("the message is empty")
+                } else {
+                    None
+                };
+                match reason {
+                    Some(reason) => self.send_to(
+                        id,
+                        ServerFrame::Rejected {
+                            client_id,
+                            reason: reason.to_string(),
+                        },
+                    ),
+                    None => {
+                        self.store_message(
+                            &conversation_id,
+                            username,
+                            &body,
+                            Some(client_id),
+                            Some(id),
+                        );
+                    }
+                }
+            }
+            ClientFrame::Read {
+                conversation_id,
+                up_to,
+            } => {
+                let mut senders = vec![];
+                for message in self.messages.iter_mut() {
+                    if message.conversation_id == conversation_id
+                        && message.sender != username
+                        && message.id <= up_to
+                        && message.status < MessageStatus::Read
+                    {
+                        message.status = MessageStatus::Read;
+                        if !senders.contains(&message.sender) {
+                            senders.push(message.sender.clone());
+                        }
+                    }
+                }
+                for sender in senders {
+                    self.send_to_user(
+                        &sender,
+                        ServerFrame::Receipt {
+                            conversation_id: conversation_id.clone(),
+                            up_to,
+                            status: MessageStatus::Read,
+                        },
+                        None,
+                    );
+                }
+            }
+            ClientFrame::Presence { status } => self.set_presence(username, status),
+            ClientFrame::Subscribe { usernames } => {
+                let updates: Vec<PresenceUpdate> = usernames
+                    .iter()
+                    .map(|watched| match self.presence.get(watched) {
+                        Some(update) => update.clone(),
+                        None => PresenceUpdate {
+                            username: watched.clone(),
+                            status: PresenceStatus::Offline,
+                            last_seen: None,
+                        },
+                    })
+                    .collect();
+                if let Some(connection) = self.connections.get_mut(&id) {
+                    connection.watched = usernames;
+                }
+                for update in updates {
+                    self.send_to(id, ServerFrame::Presence(update));
+                }
+            }
+            ClientFrame::Retention {
+                conversation_id,
+                retention_secs,
+            } => {
+                let members = participants(&conversation_id);
+                if !members.contains(&username.to_string()) {
+                    self.send_to(
+                        id,
+                        ServerFrame::Error {
+                            message: "you aren't part of this conversation".to_string(),
+                        },
+                    );
+                    return;
+                }
+                let update = RetentionUpdate {
+                    conversation_id: conversation_id.clone(),
+                    retention_secs,
+                    set_by: username.to_string(),
+                    set_at: unix_now(),
+                };
+                self.retention.insert(conversation_id, update.clone());
+                for member in members {
+                    self.send_to_user(&member, ServerFrame::Retention(update.clone()), None);
+                }
+            }
+            ClientFrame::Typing { conversation_id } => {
+                for participant in participants(&conversation_id) {
+                    if participant != username {
+                        self.send_to_user(
+                            &participant,
+                            ServerFrame::Typing {
+                                conversation_id: conversation_id.clone(),
+                                username: username.to_string(),
+                            },
+                            None,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // stores a new message and hands it to the participants, `from` is the connection that
+    // sent it, which gets the acceptance instead of the message
+    pub(crate) fn store_message(
+        &mut self,
+        conversation_id: &str,
+        sender: &str,
+        body: &str,
+        client_id: Option<String>,
+        from: Option<u64>,
+    ) -> ChatMessage {
+        let message = ChatMessage {
+            id: self.messages.len() as u64 + 1,
+            conversation_id: conversation_id.to_string(),
+            sender: sender.to_string(),
+            body: body.to_string(),
+            sent_at: unix_now(),
+            client_id: client_id.clone(),
+            status: MessageStatus::Stored,
+        };
+        self.messages.push(message.clone());
+        if let (Some(client_id), Some(from)) = (client_id, from) {
+            self.accepted
+                .insert((sender.to_string(), client_id.clone()), message.id);
+            self.send_to(
+                from,
+                ServerFrame::Accepted {
+                    client_id,
+                    id: message.id,
+                    sent_at: message.sent_at,
+                },
+            );
+        }
+        for participant in participants(conversation_id) {
+            self.send_to_user(&participant, ServerFrame::Message(message.clone()), from);
+        }
+        message
+    }
+
+    fn set_presence(&mut self, username: &str, status: PresenceStatus) {
+        let update = PresenceUpdate {
+            username: username.to_string(),
+            status,
+            last_seen: Some(unix_now()),
+        };
+        self.presence.insert(username.to_string(), update.clone());
+        for connection in self.connections.values() {
+            if connection.watched.iter().any(|watched| watched == username) {
+                let _ = connection
+                    .frames
+                    .send(Outgoing::Frame(ServerFrame::Presence(update.clone())));
+            }
+        }
+    }
+
+    pub(crate) fn send_to(&self, id: u64, frame: ServerFrame) {
+        if let Some(connection) = self.connections.get(&id) {
+            let _ = connection.frames.send(Outgoing::Frame(frame));
+        }
+    }
+
+    // to every connection of `username` but `except`
+    fn send_to_user(&self, username: &str, frame: ServerFrame, except: Option<u64>) {
+        for (id, connection) in self.connections.iter() {
+            if connection.username == username && Some(*id) != except {
+                let _ = connection.frames.send(Outgoing::Frame(frame.clone()));
+            }
+        }
+    }
+}
@@ -0,0 +1,385 @@
+// Stand-in for the Chatuza server, for development and tests.
+// It serves the same http api and chat websocket as the real server out of memory, and can be
+// scripted per path: canned replies, injected failures and delays, plus a log of the requests.
+mod api;
+mod chat;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{header, Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use modules::p2p_connection::structs::{ChatMessage, PresenceUpdate, RetentionUpdate, ServerFrame};
+use modules::server_interaction::structs::UserProfile;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc::UnboundedSender, oneshot};
+
+// how long issued access tokens are valid, the real server uses the same
+const DEFAULT_TOKEN_LIFETIME: u64 = 3600;
+
+pub(crate) type Shared = Arc<Mutex<MockState>>;
+
+// A running mock server, stopped when dropped.
+// It runs on its own thread and runtime, so blocking and async tests can both use it
+pub struct MockServer {
+    address: SocketAddr,
+    state: Shared,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+// what the next request to a path gets instead of the normal answer
+#[derive(Debug, Clone)]
+pub struct Reply {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Reply {
+    // a successful answer, wrapped like the server does: {"Ok": value}
+    pub fn ok<T: Serialize>(value: T) -> Reply {
+        Reply {
+            status: 200,
+            body: json!({ "Ok": value }).to_string(),
+        }
+    }
+
+    // a refusal the client shows as is: {"Err": message}
+    pub fn err(message: &str) -> Reply {
+        Reply {
+            status: 200,
+            body: json!({ "Err": message }).to_string(),
+        }
+    }
+
+    pub fn status(status: u16, body: &str) -> Reply {
+        Reply {
+            status,
+            body: body.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for Reply {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        (
+            status,
+            [(header::CONTENT_TYPE, "application/json")],
+            self.body,
+        )
+            .into_response()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub body: String,
+    pub authorization: Option<String>,
+}
+
+pub(crate) struct Account {
+    pub profile: UserProfile,
+    pub password: String,
+}
+
+pub(crate) struct Challenge {
+    pub wallet_pubkey: String,
+    pub expires_at: u64,
+}
+
+// a websocket client, frames pushed here are written to it
+pub(crate) struct Connection {
+    pub username: String,
+    pub frames: UnboundedSender<Outgoing>,
+    pub watched: Vec<String>,
+}
+
+pub(crate) enum Outgoing {
+    Frame(ServerFrame),
+    Close,
+}
+
+#[derive(Default)]
+pub(crate) struct MockState {
+    pub accounts: BTreeMap<String, Account>,
+    // access token -> (username, expires at)
+    pub access_tokens: HashMap<String, (String, u64)>,
+    // refresh token -> username
+    pub refresh_tokens: HashMap<String, String>,
+    pub token_lifetime: u64,
+    // nonce -> challenge
+    pub challenges: HashMap<String, Challenge>,
+    pub contacts: HashMap<String, Vec<String>>,
+    pub funded_wallets: Vec<String>,
+    // (wallet, mint)
+    pub token_accounts: Vec<(String, String)>,
+    pub messages: Vec<ChatMessage>,
+    // (sender, client id) -> message id, a message sent twice is stored once
+    pub accepted: HashMap<(String, String), u64>,
+    pub connections: HashMap<u64, Connection>,
+    pub next_connection: u64,
+    pub presence: HashMap<String, PresenceUpdate>,
+    // conversation id -> its disappearing messages timer
+    pub retention: HashMap<String, RetentionUpdate>,
+    scripts: HashMap<String, VecDeque<Reply>>,
+    delays: HashMap<String, Duration>,
+    requests: Vec<RecordedRequest>,
+}
+
+impl MockServer {
+    // EH
+    // on a free local port
+    pub fn start() -> Result<MockServer, String> {
+        Self::bind("127.0.0.1:0")
+    }
+
+    // EH
+    pub fn bind(address: &str) -> Result<MockServer, String> {
+        let listener = match TcpListener::bind(address) {
+            Ok(listener) => listener,
+            Err(e) => return Err(format!("couldn't listen on {} \n {}", address, e)),
+        };
+        let address = match listener.local_addr() {
+            Ok(address) => address,
+            Err(e) => return Err(format!("couldn't read the listening address \n {}", e)),
+        };
+        let runtime = match tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+        {
+            Ok(runtime) => runtime,
+            Err(e) => return Err(format!("couldn't start the mock server runtime \n {}", e)),
+        };
+        let server = {
+            let _guard = runtime.enter();
+            match axum::Server::from_tcp(listener) {
+                Ok(server) => server,
+                Err(e) => return Err(format!("couldn't start the mock server \n {}", e)),
+            }
+        };
+
+        let state: Shared = Arc::new(Mutex::new(MockState {
+            token_lifetime: DEFAULT_TOKEN_LIFETIME,
+            ..Default::default()
+        }));
+        let app = router(state.clone());
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let thread = std::thread::spawn(move || {
+            runtime.block_on(async move {
+                let serving = server
+                    .serve(app.into_make_service())
+                    .with_graceful_shutdown(async move {
+                        let _ = shutdown_rx.await;
+                    });
+                if let Err(e) = serving.await {
+                    eprintln!("the mock server stopped due to \n {}", e);
+                }
+            });
+            // the websocket tasks still running are dropped, not waited for
+            runtime.shutdown_background();
+        });
+
+        Ok(MockServer {
+            address,
+            state,
+            shutdown: Some(shutdown_tx),
+            thread: Some(thread),
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    // blocks until the server stops, for the binary
+    pub fn wait(mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    // Scripting
+
+    // the next request to `path` gets `reply`, replies queued for a path are used in order
+    pub fn script(&self, path: &str, reply: Reply) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .scripts
+            .entry(path.to_string())
+            .or_default()
+            .push_back(reply);
+    }
+
+    // the next `times` requests to `path` fail with `status`
+    pub fn fail_next(&self, path: &str, status: u16, times: usize) {
+        for _ in 0..times {
+            self.script(path, Reply::status(status, "injected failure"));
+        }
+    }
+
+    // every request to `path` waits this long before being answered
+    pub fn delay(&self, path: &str, delay: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.delays.insert(path.to_string(), delay);
+    }
+
+    // the requests received for `path`, oldest first
+    pub fn requests(&self, path: &str) -> Vec<RecordedRequest> {
+        let state = self.state.lock().unwrap();
+        state
+            .requests
+            .iter()
+            .filter(|request| request.path == path)
+            .cloned()
+            .collect()
+    }
+
+    // Data
+
+    pub fn add_user(&self, profile: UserProfile, password: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.accounts.insert(
+            profile.username.clone(),
+            Account {
+                profile,
+                password: password.to_string(),
+            },
+        );
+    }
+
+    // lifetime of the tokens issued from now on, short ones make the client refresh
+    pub fn set_token_lifetime(&self, seconds: u64) {
+        self.state.lock().unwrap().token_lifetime = seconds;
+    }
+
+    // every access token stops working, as if the server restarted with a new key
+    pub fn revoke_tokens(&self) {
+        self.state.lock().unwrap().access_tokens.clear();
+    }
+
+    pub fn funded_wallets(&self) -> Vec<String> {
+        self.state.lock().unwrap().funded_wallets.clone()
+    }
+
+    // (wallet, mint) of the token accounts created
+    pub fn token_accounts(&self) -> Vec<(String, String)> {
+        self.state.lock().unwrap().token_accounts.clone()
+    }
+
+    pub fn messages(&self) -> Vec<ChatMessage> {
+        self.state.lock().unwrap().messages.clone()
+    }
+
+    // EH
+    // a message written by `sender` from another device, delivered to the connected participants
+    pub fn send_message(
+        &self,
+        conversation_id: &str,
+        sender: &str,
+        body: &str,
+    ) -> Result<ChatMessage, String> {
+        let mut state = self.state.lock().unwrap();
+        if !chat::participants(conversation_id).contains(&sender.to_string()) {
+            return Err(format!("{} isn't part of {}", sender, conversation_id));
+        }
+        Ok(state.store_message(conversation_id, sender, body, None, None))
+    }
+
+    // closes every websocket, the clients have to reconnect and resume
+    pub fn drop_connections(&self) {
+        let mut state = self.state.lock().unwrap();
+        for connection in state.connections.values() {
+            let _ = connection.frames.send(Outgoing::Close);
+        }
+        state.connections.clear();
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn router(state: Shared) -> Router {
+    Router::new()
+        .route("/api/create-token-account", post(api::create_token_account))
+        .route("/api/fund-wallet", post(api::fund_wallet))
+        .route("/api/auth/sign-up", post(api::sign_up))
+        .route("/api/auth/sign-in", post(api::sign_in))
+        .route("/api/auth/challenge", post(api::challenge))
+        .route("/api/auth/wallet-sign-in", post(api::wallet_sign_in))
+        .route("/api/auth/refresh", post(api::refresh))
+        .route("/api/auth/sign-out", post(api::sign_out))
+        .route("/api/users/me", get(api::profile))
+        .route("/api/users/search", get(api::search_users))
+        .route(
+            "/api/contacts",
+            get(api::list_contacts).post(api::add_contact),
+        )
+        .route("/api/ws", get(chat::connect))
+        .layer(middleware::from_fn_with_state(state.clone(), intercept))
+        .with_state(state)
+}
+
+// records every request, then answers with the scripted reply of its path if there is one
+async fn intercept(
+    State(state): State<Shared>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+    let path = parts.uri.path().to_string();
+    let (delay, scripted) = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(RecordedRequest {
+            method: parts.method.to_string(),
+            path: path.clone(),
+            query: parts.uri.query().map(str::to_string),
+            body: String::from_utf8_lossy(&body).to_string(),
+            authorization: parts
+                .headers
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+        });
+        let scripted = state
+            .scripts
+            .get_mut(&path)
+            .and_then(|replies| replies.pop_front());
+        (state.delays.get(&path).copied(), scripted)
+    };
+    if let Some(delay) = delay {
+        tokio::time::sleep(delay).await;
+    }
+    match scripted {
+        Some(reply) => reply.into_response(),
+        None => next.run(Request::from_parts(parts, Body::from(body))).await,
+    }
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,37 @@
+// Runs the mock server where the app looks for the real one by default,
+// with a few users to search, add and chat with. CHATUZA_MOCK_ADDR changes the address
+use mock_server::MockServer;
+use modules::server_interaction::structs::UserProfile;
+use solana_sdk::signer::{keypair::Keypair, Signer};
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8000";
+const ADDRESS_ENV: &str = "CHATUZA_MOCK_ADDR";
+const DEMO_PASSWORD: &str = "password";
+
+fn main() {
+    let address = std::env::var(ADDRESS_ENV).unwrap_or_else(|_| DEFAULT_ADDRESS.to_string());
+    let server = match MockServer::bind(&address) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    for (username, display_name) in [("alice", "Alice"), ("bob", "Bob"), ("carol", "Carol")] {
+        server.add_user(
+            UserProfile {
+                username: username.to_string(),
+                display_name: display_name.to_string(),
+                wallet_pubkey: Keypair::new().pubkey().to_string(),
+                avatar_url: None,
+            },
+            DEMO_PASSWORD,
+        );
+    }
+    println!(
+        "mock chatuza server listening on {}, demo users alice, bob and carol use the password \"{}\"",
+        server.url(),
+        DEMO_PASSWORD
+    );
+    server.wait();
+}
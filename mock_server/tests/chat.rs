@@ -0,0 +1,262 @@
+mod common;
+
+use common::{signed_in, temporary_database};
+use mock_server::MockServer;
+use modules::p2p_connection::structs::{ConnectionEvent, MessageStatus};
+use modules::p2p_connection::{direct_conversation_id, resume_point, ChatConnection};
+use modules::retention;
+use modules::sqlite;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time::timeout;
+
+const EVENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// the first event `matches` accepts, the others are skipped
+async fn wait_for(
+    events: &mut UnboundedReceiver<ConnectionEvent>,
+    matches: impl Fn(&ConnectionEvent) -> bool,
+) -> ConnectionEvent {
+    timeout(EVENT_TIMEOUT, async {
+        loop {
+            let event = events.recv().await.expect("the connection stopped");
+            if matches(&event) {
+                return event;
+            }
+        }
+    })
+    .await
+    .expect("the event didn't come")
+}
+
+#[tokio::test]
+async fn messages_are_delivered_and_receipted() {
+    let server = MockServer::start().unwrap();
+    let alice_client = signed_in(&server, "alice").await;
+    let bob_client = signed_in(&server, "bob").await;
+    let (alice, mut alice_events) =
+        ChatConnection::connect(alice_client, temporary_database(), None);
+    let (bob, mut bob_events) = ChatConnection::connect(bob_client, temporary_database(), None);
+    wait_for(&mut bob_events, |event| {
+        matches!(event, ConnectionEvent::Connected)
+    })
+    .await;
+
+    let conversation = direct_conversation_id("alice", "bob");
+    let entry = alice.send_message(&conversation, "hello bob").unwrap();
+    let accepted = wait_for(
+        &mut alice_events,
+        |event| matches!(event, ConnectionEvent::Message(message) if message.client_id.is_some()),
+    )
+    .await;
+    let received = wait_for(&mut bob_events, |event| {
+        matches!(event, ConnectionEvent::Message(_))
+    })
+    .await;
+    let (ConnectionEvent::Message(accepted), ConnectionEvent::Message(received)) =
+        (accepted, received)
+    else {
+        unreachable!()
+    };
+    assert_eq!(accepted.client_id, Some(entry.client_id));
+    assert_eq!(received.id, accepted.id);
+    assert_eq!(received.body, "hello bob");
+
+    // bob's client acknowledged it
+    wait_for(&mut alice_events, |event| {
+        matches!(
+            event,
+            ConnectionEvent::Receipt {
+                status: MessageStatus::Delivered,
+                ..
+            }
+        )
+    })
+    .await;
+    bob.mark_read(&conversation, received.id).unwrap();
+    wait_for(&mut alice_events, |event| {
+        matches!(
+            event,
+            ConnectionEvent::Receipt {
+                status: MessageStatus::Read,
+                ..
+            }
+        )
+    })
+    .await;
+    assert_eq!(server.messages()[0].status, MessageStatus::Read);
+}
+
+#[tokio::test]
+async fn messages_written_while_offline_are_resumed() {
+    let server = MockServer::start().unwrap();
+    let alice_client = signed_in(&server, "alice").await;
+    signed_in(&server, "bob").await;
+    let conversation = direct_conversation_id("alice", "bob");
+    let first = server.send_message(&conversation, "bob", "first").unwrap();
+
+    let (_alice, mut events) = ChatConnection::connect(alice_client, temporary_database(), None);
+    let replayed = wait_for(&mut events, |event| {
+        matches!(event, ConnectionEvent::Message(_))
+    })
+    .await;
+    assert!(matches!(replayed, ConnectionEvent::Message(message) if message.id == first.id));
+
+    // only what came after the last acknowledged message is replayed on reconnection
+    server.drop_connections();
+    wait_for(&mut events, |event| {
+        matches!(event, ConnectionEvent::Disconnected { .. })
+    })
+    .await;
+    let second = server.send_message(&conversation, "bob", "second").unwrap();
+    let replayed = wait_for(&mut events, |event| {
+        matches!(event, ConnectionEvent::Message(_))
+    })
+    .await;
+    assert!(matches!(replayed, ConnectionEvent::Message(message) if message.id == second.id));
+}
+
+#[tokio::test]
+async fn retention_timer_reaches_both_sides_and_expires_messages() {
+    let server = MockServer::start().unwrap();
+    let alice_client = signed_in(&server, "alice").await;
+    let bob_client = signed_in(&server, "bob").await;
+    let bob_database = temporary_database();
+    let (alice, mut alice_events) =
+        ChatConnection::connect(alice_client, temporary_database(), None);
+    let (_bob, mut bob_events) = ChatConnection::connect(bob_client, bob_database.clone(), None);
+    wait_for(&mut bob_events, |event| {
+        matches!(event, ConnectionEvent::Connected)
+    })
+    .await;
+
+    let conversation = direct_conversation_id("alice", "bob");
+    alice
+        .send_message(&conversation, "gone in an hour")
+        .unwrap();
+    let received = wait_for(&mut bob_events, |event| {
+        matches!(event, ConnectionEvent::Message(_))
+    })
+    .await;
+    let ConnectionEvent::Message(received) = received else {
+        unreachable!()
+    };
+
+    alice.set_retention(&conversation, Some(3600)).unwrap();
+    for events in [&mut alice_events, &mut bob_events] {
+        let update = wait_for(events, |event| {
+            matches!(event, ConnectionEvent::Retention(_))
+        })
+        .await;
+        assert!(matches!(
+            update,
+            ConnectionEvent::Retention(update)
+                if update.retention_secs == Some(3600) && update.set_by == "alice"
+        ));
+    }
+
+    // bob's copy goes once the hour is over, the search index with it
+    let mut connection = bob_database.connection().unwrap();
+    let stored = sqlite::get_retention(&connection, &conversation)
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored.retention_secs, Some(3600));
+    assert!(retention::purge(&mut connection, received.sent_at + 3599)
+        .unwrap()
+        .expired
+        .is_empty());
+    let expired = retention::purge(&mut connection, received.sent_at + 3600)
+        .unwrap()
+        .expired;
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].ids, vec![received.id]);
+    assert!(sqlite::get_message(&connection, received.id)
+        .unwrap()
+        .is_none());
+    assert!(sqlite::search_messages(&connection, "hour", None, 10)
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn messages_that_cant_be_stored_are_fetched_again() {
+    let server = MockServer::start().unwrap();
+    let alice_client = signed_in(&server, "alice").await;
+    signed_in(&server, "bob").await;
+    let database = temporary_database();
+    database
+        .connection()
+        .unwrap()
+        .execute_batch(
+            "CREATE TRIGGER refuse_messages BEFORE INSERT ON messages
+             BEGIN SELECT RAISE(ABORT, 'disk full'); END;",
+        )
+        .unwrap();
+    let conversation = direct_conversation_id("alice", "bob");
+    let sent = server
+        .send_message(&conversation, "bob", "keep me")
+        .unwrap();
+
+    let (_alice, mut events) = ChatConnection::connect(alice_client, database.clone(), None);
+    wait_for(&mut events, |event| {
+        matches!(event, ConnectionEvent::LocalError { .. })
+    })
+    .await;
+    assert_eq!(server.messages()[0].status, MessageStatus::Stored);
+
+    database
+        .connection()
+        .unwrap()
+        .execute_batch("DROP TRIGGER refuse_messages;")
+        .unwrap();
+    let replayed = wait_for(&mut events, |event| {
+        matches!(event, ConnectionEvent::Message(_))
+    })
+    .await;
+    assert!(matches!(replayed, ConnectionEvent::Message(message) if message.id == sent.id));
+    let connection = database.connection().unwrap();
+    assert!(sqlite::get_message(&connection, sent.id).unwrap().is_some());
+}
+
+#[tokio::test]
+async fn a_restart_resumes_after_what_was_erased() {
+    let server = MockServer::start().unwrap();
+    let alice_client = signed_in(&server, "alice").await;
+    signed_in(&server, "bob").await;
+    let database = temporary_database();
+    let conversation = direct_conversation_id("alice", "bob");
+    let first = server.send_message(&conversation, "bob", "erased").unwrap();
+
+    let (alice, mut events) = ChatConnection::connect(alice_client.clone(), database.clone(), None);
+    wait_for(&mut events, |event| {
+        matches!(event, ConnectionEvent::Message(_))
+    })
+    .await;
+    timeout(EVENT_TIMEOUT, async {
+        while server.messages()[0].status != MessageStatus::Delivered {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("the message wasn't acknowledged");
+    alice.shutdown();
+    // the timer erased it before the app was closed
+    let mut connection = database.connection().unwrap();
+    sqlite::delete_messages(&mut connection, &[first.id]).unwrap();
+    assert_eq!(resume_point(&connection).unwrap(), Some(first.id));
+
+    let second = server.send_message(&conversation, "bob", "new").unwrap();
+    let (_alice, mut events) = ChatConnection::connect(
+        alice_client,
+        database.clone(),
+        resume_point(&connection).unwrap(),
+    );
+    let replayed = wait_for(&mut events, |event| {
+        matches!(event, ConnectionEvent::Message(_))
+    })
+    .await;
+    assert!(matches!(replayed, ConnectionEvent::Message(message) if message.id == second.id));
+    assert!(sqlite::get_message(&connection, first.id)
+        .unwrap()
+        .is_none());
+}
@@ -0,0 +1,36 @@
+// helpers shared by the tests talking to the mock server, each test file uses some of them
+#![allow(dead_code)]
+use mock_server::MockServer;
+use modules::p2p_connection::new_client_id;
+use modules::server_interaction::structs::{SignInRequest, UserProfile};
+use modules::server_interaction::ChatuzaServerClient;
+use modules::sqlite::{self, Database};
+use solana_sdk::signer::{keypair::Keypair, Signer};
+
+pub fn profile(username: &str) -> UserProfile {
+    UserProfile {
+        username: username.to_string(),
+        display_name: username.to_uppercase(),
+        wallet_pubkey: Keypair::new().pubkey().to_string(),
+        avatar_url: None,
+    }
+}
+
+// a client with a session of `username`, added to the server first
+pub async fn signed_in(server: &MockServer, username: &str) -> ChatuzaServerClient {
+    server.add_user(profile(username), "password");
+    let client = ChatuzaServerClient::new(&server.url()).unwrap();
+    client
+        .sign_in(&SignInRequest {
+            username: username.to_string(),
+            password: "password".to_string(),
+        })
+        .await
+        .unwrap();
+    client
+}
+
+pub fn temporary_database() -> Database {
+    let path = std::env::temp_dir().join(format!("chatuza-test-{}.db", new_client_id()));
+    sqlite::open_database_at(path.to_str().unwrap()).unwrap()
+}
@@ -0,0 +1,276 @@
+mod common;
+
+use common::{profile, signed_in};
+use mock_server::{MockServer, Reply};
+use modules::server_interaction::structs::{SignInRequest, SignUpRequest};
+use modules::server_interaction::ChatuzaServerClient;
+use solana_sdk::signer::{keypair::Keypair, Signer};
+
+#[tokio::test]
+async fn sign_up_starts_a_session_until_sign_out() {
+    let server = MockServer::start().unwrap();
+    let client = ChatuzaServerClient::new(&server.url()).unwrap();
+    let user = client
+        .sign_up(&SignUpRequest {
+            username: "alice".to_string(),
+            display_name: "Alice".to_string(),
+            wallet_pubkey: Keypair::new().pubkey().to_string(),
+            avatar_url: None,
+            password: "password".to_string(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(client.current_user(), Some(user.clone()));
+    assert_eq!(client.fetch_profile().await.unwrap(), user);
+
+    client.sign_out().await.unwrap();
+    assert_eq!(client.current_user(), None);
+    let sign_out = &server.requests("/api/auth/sign-out")[0];
+    assert!(sign_out
+        .authorization
+        .as_deref()
+        .unwrap()
+        .starts_with("Bearer "));
+}
+
+#[tokio::test]
+async fn sign_in_refusals_are_shown_as_is() {
+    let server = MockServer::start().unwrap();
+    server.add_user(profile("alice"), "password");
+    let client = ChatuzaServerClient::new(&server.url()).unwrap();
+    let error = client
+        .sign_in(&SignInRequest {
+            username: "alice".to_string(),
+            password: "wrong".to_string(),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(error, "wrong username or password");
+    assert_eq!(client.current_user(), None);
+}
+
+#[test]
+fn passwords_are_left_out_of_debug_output() {
+    let sign_up = SignUpRequest {
+        username: "alice".to_string(),
+        display_name: "Alice".to_string(),
+        wallet_pubkey: Keypair::new().pubkey().to_string(),
+        avatar_url: None,
+        password: "hunter2".to_string(),
+    };
+    let sign_in = SignInRequest {
+        username: "alice".to_string(),
+        password: "hunter2".to_string(),
+    };
+    for output in [format!("{:?}", sign_up), format!("{:?}", sign_in)] {
+        assert!(output.contains("alice"));
+        assert!(!output.contains("hunter2"));
+    }
+}
+
+#[tokio::test]
+async fn wallet_sign_in_answers_the_server_challenge() {
+    let server = MockServer::start().unwrap();
+    let keypair = Keypair::new();
+    let mut alice = profile("alice");
+    alice.wallet_pubkey = keypair.pubkey().to_string();
+    server.add_user(alice.clone(), "password");
+
+    let client = ChatuzaServerClient::new(&server.url()).unwrap();
+    assert_eq!(client.sign_in_with_wallet(&keypair).await.unwrap(), alice);
+
+    // another wallet can't sign for it
+    let challenge = client
+        .request_sign_in_challenge(&alice.wallet_pubkey)
+        .await
+        .unwrap();
+    let message = client
+        .wallet_sign_in_message(&challenge, &alice.wallet_pubkey)
+        .unwrap();
+    let forged = Keypair::new().sign_message(message.as_bytes());
+    let error = client
+        .complete_wallet_sign_in(&alice.wallet_pubkey, &message, &forged.to_string())
+        .await
+        .unwrap_err();
+    assert_eq!(error, "the signature doesn't match the wallet");
+}
+
+#[tokio::test]
+async fn single_use_requests_are_not_replayed() {
+    let server = MockServer::start().unwrap();
+    let keypair = Keypair::new();
+    let mut alice = profile("alice");
+    alice.wallet_pubkey = keypair.pubkey().to_string();
+    server.add_user(alice, "password");
+    let client = ChatuzaServerClient::new(&server.url()).unwrap();
+
+    // the server may have used the nonce before the gateway failed
+    server.fail_next("/api/auth/wallet-sign-in", 503, 1);
+    let error = client.sign_in_with_wallet(&keypair).await.unwrap_err();
+    assert!(error.contains("503"), "{}", error);
+    assert_eq!(server.requests("/api/auth/wallet-sign-in").len(), 1);
+    client.sign_in_with_wallet(&keypair).await.unwrap();
+}
+
+#[tokio::test]
+async fn unavailable_server_is_retried() {
+    let server = MockServer::start().unwrap();
+    server.add_user(profile("alice"), "password");
+    let request = SignInRequest {
+        username: "alice".to_string(),
+        password: "password".to_string(),
+    };
+
+    server.fail_next("/api/auth/sign-in", 503, 2);
+    let client = ChatuzaServerClient::new(&server.url()).unwrap();
+    client.sign_in(&request).await.unwrap();
+    assert_eq!(server.requests("/api/auth/sign-in").len(), 3);
+
+    server.fail_next("/api/auth/sign-in", 503, 2);
+    let impatient = ChatuzaServerClient::new(&server.url())
+        .unwrap()
+        .with_max_retries(1);
+    let error = impatient.sign_in(&request).await.unwrap_err();
+    assert!(error.contains("503"), "{}", error);
+}
+
+#[tokio::test]
+async fn errors_other_than_unavailable_are_not_retried() {
+    let server = MockServer::start().unwrap();
+    let client = signed_in(&server, "alice").await;
+    server.fail_next("/api/contacts", 500, 1);
+    let error = client.list_contacts().await.unwrap_err();
+    assert!(error.contains("500"), "{}", error);
+    assert_eq!(server.requests("/api/contacts").len(), 1);
+}
+
+#[tokio::test]
+async fn unauthorized_answer_ends_the_session() {
+    let server = MockServer::start().unwrap();
+    let client = signed_in(&server, "alice").await;
+    server.revoke_tokens();
+    let error = client.fetch_profile().await.unwrap_err();
+    assert_eq!(error, "your session expired, sign in again");
+    assert_eq!(client.current_user(), None);
+}
+
+#[tokio::test]
+async fn expiring_access_token_is_refreshed_first() {
+    let server = MockServer::start().unwrap();
+    // shorter than the refresh margin of the client, every call refreshes
+    server.set_token_lifetime(30);
+    let client = signed_in(&server, "alice").await;
+    client.fetch_profile().await.unwrap();
+    client.fetch_profile().await.unwrap();
+    assert_eq!(server.requests("/api/auth/refresh").len(), 2);
+
+    // a refused refresh signs out
+    server.script(
+        "/api/auth/refresh",
+        Reply::status(401, "invalid refresh token"),
+    );
+    let error = client.fetch_profile().await.unwrap_err();
+    assert!(error.starts_with("your session expired"), "{}", error);
+    assert_eq!(client.current_user(), None);
+}
+
+#[tokio::test]
+async fn unavailable_refresh_keeps_the_session() {
+    let server = MockServer::start().unwrap();
+    server.set_token_lifetime(30);
+    let client = signed_in(&server, "alice").await;
+
+    server.fail_next("/api/auth/refresh", 503, 1);
+    let error = client.fetch_profile().await.unwrap_err();
+    assert!(error.contains("503"), "{}", error);
+    assert_eq!(server.requests("/api/auth/refresh").len(), 1);
+    assert_eq!(client.current_user().unwrap().username, "alice");
+    // the refresh token wasn't used, it still works once the server is back
+    client.fetch_profile().await.unwrap();
+    assert_eq!(server.requests("/api/auth/refresh").len(), 2);
+}
+
+#[tokio::test]
+async fn search_pages_are_cached() {
+    let server = MockServer::start().unwrap();
+    for username in ["bob", "bobby", "carol"] {
+        server.add_user(profile(username), "password");
+    }
+    let client = signed_in(&server, "alice").await;
+
+    let page = client.search_users("BOB", 0).await.unwrap();
+    let usernames: Vec<&str> = page
+        .items
+        .iter()
+        .map(|user| user.username.as_str())
+        .collect();
+    assert_eq!(usernames, ["bob", "bobby"]);
+    assert!(!page.has_more());
+    client.search_users("bob", 0).await.unwrap();
+    assert_eq!(server.requests("/api/users/search").len(), 1);
+
+    // too short to be sent
+    assert!(client.search_users("b", 0).await.unwrap().items.is_empty());
+    assert_eq!(server.requests("/api/users/search").len(), 1);
+}
+
+#[tokio::test]
+async fn search_cache_goes_with_the_session() {
+    let server = MockServer::start().unwrap();
+    server.add_user(profile("bob"), "password");
+    let client = signed_in(&server, "alice").await;
+    client.search_users("bob", 0).await.unwrap();
+
+    client.sign_out().await.unwrap();
+    server.add_user(profile("carol"), "password");
+    client
+        .sign_in(&SignInRequest {
+            username: "carol".to_string(),
+            password: "password".to_string(),
+        })
+        .await
+        .unwrap();
+    client.search_users("bob", 0).await.unwrap();
+    assert_eq!(server.requests("/api/users/search").len(), 2);
+
+    // signing in as someone else without signing out first
+    server.add_user(profile("dave"), "password");
+    client
+        .sign_in(&SignInRequest {
+            username: "dave".to_string(),
+            password: "password".to_string(),
+        })
+        .await
+        .unwrap();
+    client.search_users("bob", 0).await.unwrap();
+    assert_eq!(server.requests("/api/users/search").len(), 3);
+}
+
+#[tokio::test]
+async fn contacts_are_added_and_listed() {
+    let server = MockServer::start().unwrap();
+    server.add_user(profile("bob"), "password");
+    let client = signed_in(&server, "alice").await;
+
+    let bob = client.add_contact("bob").await.unwrap();
+    assert_eq!(client.list_contacts().await.unwrap(), vec![bob]);
+    let error = client.add_contact("nobody").await.unwrap_err();
+    assert_eq!(error, "no user with this username");
+}
+
+#[tokio::test]
+async fn scripted_replies_replace_the_normal_answer_once() {
+    let server = MockServer::start().unwrap();
+    let client = signed_in(&server, "alice").await;
+    server.script("/api/contacts", Reply::ok(vec![profile("mallory")]));
+    assert_eq!(client.list_contacts().await.unwrap()[0].username, "mallory");
+    assert!(client.list_contacts().await.unwrap().is_empty());
+
+    server.script("/api/contacts", Reply::status(200, "not json"));
+    let error = client.list_contacts().await.unwrap_err();
+    assert!(
+        error.starts_with("failed to parse the response"),
+        "{}",
+        error
+    );
+}
@@ -0,0 +1,92 @@
+// the wallet calls block on their own runtime, the tests are plain threads
+use mock_server::MockServer;
+use modules::server_interaction::ChatuzaServerClient;
+use modules::solana_wallet::{create_token_account, fund_account};
+use solana_sdk::pubkey::Pubkey;
+use std::time::{Duration, Instant};
+
+#[test]
+fn fund_account_asks_the_backend_faucet() {
+    let server = MockServer::start().unwrap();
+    let client = ChatuzaServerClient::new(&server.url()).unwrap();
+    let wallet = Pubkey::new_unique().to_string();
+
+    let signature = fund_account(&client, wallet.clone()).unwrap();
+    assert!(!signature.is_empty());
+    assert_eq!(server.funded_wallets(), vec![wallet.clone()]);
+    let request = &server.requests("/api/fund-wallet")[0];
+    assert_eq!(request.body, format!("wallet_address={}", wallet));
+}
+
+#[test]
+fn create_token_account_returns_both_signatures() {
+    let server = MockServer::start().unwrap();
+    let client = ChatuzaServerClient::new(&server.url()).unwrap();
+    let (wallet, mint) = (
+        Pubkey::new_unique().to_string(),
+        Pubkey::new_unique().to_string(),
+    );
+    let signatures = create_token_account(
+        &client,
+        wallet.clone(),
+        mint.clone(),
+        spl_token_program(),
+        "blockhash".to_string(),
+    )
+    .unwrap();
+    assert_eq!(signatures.len(), 2);
+    assert_eq!(server.token_accounts(), vec![(wallet, mint)]);
+}
+
+#[test]
+fn wallet_calls_report_server_failures() {
+    let server = MockServer::start().unwrap();
+    let client = ChatuzaServerClient::new(&server.url()).unwrap();
+
+    server.fail_next("/api/fund-wallet", 500, 1);
+    let error = fund_account(&client, Pubkey::new_unique().to_string()).unwrap_err();
+    assert!(error.contains("500"), "{}", error);
+
+    let error = fund_account(&client, "not a wallet".to_string()).unwrap_err();
+    assert_eq!(error, "invalid wallet address");
+    assert!(server.funded_wallets().is_empty());
+}
+
+#[test]
+fn unavailable_faucet_is_not_asked_twice() {
+    let server = MockServer::start().unwrap();
+    let client = ChatuzaServerClient::new(&server.url()).unwrap();
+
+    // the gateway may have timed out after the wallet was funded
+    server.fail_next("/api/fund-wallet", 504, 1);
+    let error = fund_account(&client, Pubkey::new_unique().to_string()).unwrap_err();
+    assert!(error.contains("504"), "{}", error);
+    assert_eq!(server.requests("/api/fund-wallet").len(), 1);
+
+    server.fail_next("/api/create-token-account", 503, 1);
+    let error = create_token_account(
+        &client,
+        Pubkey::new_unique().to_string(),
+        Pubkey::new_unique().to_string(),
+        spl_token_program(),
+        "blockhash".to_string(),
+    )
+    .unwrap_err();
+    assert!(error.contains("503"), "{}", error);
+    assert_eq!(server.requests("/api/create-token-account").len(), 1);
+}
+
+#[test]
+fn slow_faucet_is_waited_for() {
+    let server = MockServer::start().unwrap();
+    let client = ChatuzaServerClient::new(&server.url()).unwrap();
+    server.delay("/api/fund-wallet", Duration::from_millis(300));
+
+    let started = Instant::now();
+    fund_account(&client, Pubkey::new_unique().to_string()).unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(300));
+}
+
+fn spl_token_program() -> String {
+    "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string()
+}
@@ -0,0 +1,440 @@
+// Export of the local history to files kept outside the app.
+// JSON keeps every field and is what the import reads back, HTML and text are for reading.
+// The attachment files go in a zip next to the export, the JSON records where each one is in it
+use crate::p2p_connection::direct_peer;
+use crate::p2p_connection::structs::ChatMessage;
+use crate::profiles::data_path;
+use crate::server_interaction::structs::UserProfile;
+use crate::solana_wallet::unix_now;
+use crate::sqlite;
+use chrono::{Local, TimeZone};
+use rusqlite::Connection;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use structs::{
+    ExportFormat, ExportReport, ExportedAttachment, ExportedConversation, ExportedMessage,
+    HistoryExport,
+};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+// first field of a JSON export, tells it apart from other JSON files
+pub const EXPORT_KIND: &str = "chatuza-export";
+// bumped when the JSON layout changes, the import reads this version and the older ones
+pub const EXPORT_VERSION: u32 = 1;
+
+const HTML_STYLE: &str = "body{background-color:#abd9e9;font-family:Arial,sans-serif;margin:0;padding:20px}
+main{max-width:700px;margin:0 auto 20px;background-color:#eff3f7;border-radius:5px}
+header{padding:20px 30px}
+header h1{font-size:15px;margin:0 0 5px}
+header h2{font-size:13px;color:#7e818a;font-weight:normal;margin:0}
+ul{list-style-type:none;padding:0 0 10px;margin:0;border-top:2px solid #fff}
+li{padding:10px 30px}
+.me{text-align:right}
+.entete{margin-bottom:5px}
+.entete h2,.entete h3{display:inline-block;font-size:13px;font-weight:normal;margin:0 5px}
+.entete h3{color:#bbb}
+.message{padding:20px;color:#fff;line-height:25px;max-width:90%;display:inline-block;text-align:left;border-radius:5px;white-space:pre-wrap}
+.you .message{background-color:#58b666}
+.me .message{background-color:#6fbced}
+.attachment{font-size:12px;color:#7e818a;margin-top:5px}";
+
+// EH
+// writes the conversation, or all of them, to `path` and the attachments to a zip next to it
+// when asked. Attachment files missing from the disk are listed in the report
+pub fn export_history(
+    connection: &Connection,
+    me: &UserProfile,
+    conversation_id: Option<&str>,
+    format: ExportFormat,
+    path: &str,
+    with_attachments: bool,
+) -> Result<ExportReport, String> {
+    let mut export = read_history(connection, me, conversation_id)?;
+    let mut report = ExportReport {
+        path: path.to_string(),
+        attachments_path: None,
+        conversations: export.conversations.len(),
+        messages: 0,
+        attachments: 0,
+        missing_attachments: vec![],
+    };
+    for conversation in &export.conversations {
+        report.messages += conversation.messages.len();
+    }
+    if with_attachments {
+        bundle_attachments(&mut export, path, &mut report)?;
+    }
+
+    let content = match format {
+        ExportFormat::Json => match serde_json::to_string_pretty(&export) {
+            Ok(json) => json,
+            Err(e) => return Err(format!("couldn't export the history \n {}", e)),
+        },
+        ExportFormat::Html => html_transcript(&export, me),
+        ExportFormat::Text => text_transcript(&export, me),
+    };
+    match fs::write(path, content) {
+        Ok(_) => Ok(report),
+        Err(e) => Err(format!("couldn't write the export to {} \n {}", path, e)),
+    }
+}
+
+// chatuza-bob-20240314-1012.html in the profile directory, chatuza-history-... for all the conversations
+pub fn default_export_path(contact: Option<&str>, format: ExportFormat) -> String {
+    let extension = match format {
+        ExportFormat::Json => "json",
+        ExportFormat::Html => "html",
+        ExportFormat::Text => "txt",
+    };
+    let stamp = match Local.timestamp_opt(unix_now() as i64, 0).single() {
+        Some(time) => time.format("%Y%m%d-%H%M%S").to_string(),
+        None => unix_now().to_string(),
+    };
+    data_path(&format!(
+        "chatuza-{}-{}.{}",
+        contact.unwrap_or("history"),
+        stamp,
+        extension
+    ))
+}
+
+// the zip with the attachments of the export at `path`, chat.json -> chat.attachments.zip
+pub fn attachments_archive_path(path: &str) -> String {
+    Path::new(path)
+        .with_extension("attachments.zip")
+        .to_string_lossy()
+        .to_string()
+}
+
+fn read_history(
+    connection: &Connection,
+    me: &UserProfile,
+    conversation_id: Option<&str>,
+) -> Result<HistoryExport, String> {
+    let mut conversations = sqlite::list_conversations(connection, &me.username)?;
+    if let Some(conversation_id) = conversation_id {
+        conversations.retain(|conversation| conversation.id == conversation_id);
+        if conversations.is_empty() {
+            return Err(format!(
+                "there's no conversation {} to export",
+                conversation_id
+            ));
+        }
+    }
+    // oldest conversation first, like the messages
+    conversations.sort_by_key(|conversation| conversation.created_at);
+
+    let mut exported = vec![];
+    for conversation in conversations {
+        let mut messages = vec![];
+        for message in sqlite::list_messages(connection, &conversation.id)? {
+            let attachments = sqlite::list_attachments(connection, message.id)?
+                .into_iter()
+                .map(|attachment| ExportedAttachment {
+                    file_name: attachment.file_name,
+                    mime_type: attachment.mime_type,
+                    size: attachment.size,
+                    source_path: attachment.path,
+                    archive_path: None,
+                })
+                .collect();
+            messages.push(ExportedMessage {
+                message,
+                attachments,
+            });
+        }
+        exported.push(ExportedConversation {
+            id: conversation.id,
+            created_at: conversation.created_at,
+            messages,
+        });
+    }
+    Ok(HistoryExport {
+        kind: EXPORT_KIND.to_string(),
+        version: EXPORT_VERSION,
+        exported_at: unix_now(),
+        account: me.clone(),
+        contacts: sqlite::list_address_book(connection)?,
+        conversations: exported,
+    })
+}
+
+// every attachment goes in the zip as <message id>/<file name>
+fn bundle_attachments(
+    export: &mut HistoryExport,
+    path: &str,
+    report: &mut ExportReport,
+) -> Result<(), String> {
+    let attachments = export
+        .conversations
+        .iter_mut()
+        .flat_map(|conversation| conversation.messages.iter_mut())
+        .flat_map(|exported| {
+            let message_id = exported.message.id;
+            exported
+                .attachments
+                .iter_mut()
+                .map(move |attachment| (message_id, attachment))
+        });
+
+    let archive_path = attachments_archive_path(path);
+    let mut archive: Option<ZipWriter<File>> = None;
+    for (message_id, attachment) in attachments {
+        let mut source = match File::open(&attachment.source_path) {
+            Ok(source) => source,
+            Err(_) => {
+                report
+                    .missing_attachments
+                    .push(attachment.source_path.clone());
+                continue;
+            }
+        };
+        // created with the first attachment found, no archive when they're all missing
+        let writer = match archive {
+            Some(ref mut writer) => writer,
+            None => match File::create(&archive_path) {
+                Ok(file) => archive.insert(ZipWriter::new(file)),
+                Err(e) => {
+                    return Err(format!(
+                        "couldn't create the attachments archive {} \n {}",
+                        archive_path, e
+                    ))
+                }
+            },
+        };
+        let name = format!("{}/{}", message_id, attachment.file_name);
+        if let Err(e) = writer.start_file(name.as_str(), SimpleFileOptions::default()) {
+            return Err(format!("couldn't add {} to the archive \n {}", name, e));
+        }
+        if let Err(e) = io::copy(&mut source, writer) {
+            return Err(format!("couldn't add {} to the archive \n {}", name, e));
+        }
+        attachment.archive_path = Some(name);
+        report.attachments += 1;
+    }
+
+    if let Some(archive) = archive {
+        if let Err(e) = archive.finish() {
+            return Err(format!(
+                "couldn't write the attachments archive {} \n {}",
+                archive_path, e
+            ));
+        }
+        report.attachments_path = Some(archive_path);
+    }
+    Ok(())
+}
+
+// Transcripts
+
+// a page that opens anywhere, styled like the chat view
+fn html_transcript(export: &HistoryExport, me: &UserProfile) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Chatuza history of {}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n",
+        escape_html(&me.display_name),
+        HTML_STYLE
+    );
+    for conversation in &export.conversations {
+        html.push_str(&format!(
+            "<main>\n<header>\n<h1>{}</h1>\n<h2>{} message{}, exported {}</h2>\n</header>\n<ul>\n",
+            escape_html(&conversation_title(export, me, &conversation.id)),
+            conversation.messages.len(),
+            if conversation.messages.len() == 1 {
+                ""
+            } else {
+                "s"
+            },
+            format_time(export.exported_at)
+        ));
+        for exported in &conversation.messages {
+            let message = &exported.message;
+            let (class, name) = (
+                if message.sender == me.username {
+                    "me"
+                } else {
+                    "you"
+                },
+                escape_html(&sender_name(export, me, message)),
+            );
+            html.push_str(&format!(
+                "<li class=\"{}\">\n<div class=\"entete\"><h2>{}</h2><h3>{}</h3></div>\n<div class=\"message\">{}</div>\n",
+                class,
+                name,
+                format_time(message.sent_at),
+                escape_html(&message.body)
+            ));
+            for attachment in &exported.attachments {
+                html.push_str(&format!(
+                    "<div class=\"attachment\">{}</div>\n",
+                    escape_html(&attachment_line(attachment))
+                ));
+            }
+            html.push_str("</li>\n");
+        }
+        html.push_str("</ul>\n</main>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn text_transcript(export: &HistoryExport, me: &UserProfile) -> String {
+    let mut text = String::new();
+    for conversation in &export.conversations {
+        let title = conversation_title(export, me, &conversation.id);
+        text.push_str(&format!(
+            "{}\n{}\n\n",
+            title,
+            "=".repeat(title.chars().count())
+        ));
+        for exported in &conversation.messages {
+            let message = &exported.message;
+            // the lines after the first are indented under the name
+            let body = message.body.replace('\n', "\n    ");
+            text.push_str(&format!(
+                "[{}] {}: {}\n",
+                format_time(message.sent_at),
+                sender_name(export, me, message),
+                body
+            ));
+            for attachment in &exported.attachments {
+                text.push_str(&format!("    {}\n", attachment_line(attachment)));
+            }
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn conversation_title(export: &HistoryExport, me: &UserProfile, conversation_id: &str) -> String {
+    match direct_peer(conversation_id, &me.username) {
+        Some(peer) => format!("Chat with {} (@{})", contact_name(export, &peer), peer),
+        None => conversation_id.to_string(),
+    }
+}
+
+fn sender_name(export: &HistoryExport, me: &UserProfile, message: &ChatMessage) -> String {
+    if message.sender == me.username {
+        me.display_name.clone()
+    } else {
+        contact_name(export, &message.sender)
+    }
+}
+
+// the nickname the user gave, the name of the profile, or the username of a former contact
+fn contact_name(export: &HistoryExport, username: &str) -> String {
+    match export
+        .contacts
+        .iter()
+        .find(|entry| entry.profile.username == username)
+    {
+        Some(entry) => entry.name().to_string(),
+        None => username.to_string(),
+    }
+}
+
+fn attachment_line(attachment: &ExportedAttachment) -> String {
+    let location = match &attachment.archive_path {
+        Some(archive_path) => format!(", {} in the attachments archive", archive_path),
+        None => String::new(),
+    };
+    format!(
+        "attachment: {} ({} KB{})",
+        attachment.file_name,
+        attachment.size.div_ceil(1024),
+        location
+    )
+}
+
+// "2024-03-14 10:12" in the local time zone
+fn format_time(unix_secs: u64) -> String {
+    match Local.timestamp_opt(unix_secs as i64, 0).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+        None => unix_secs.to_string(),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub mod structs {
+    use crate::p2p_connection::structs::ChatMessage;
+    use crate::server_interaction::structs::UserProfile;
+    use crate::sqlite::structs::AddressBookEntry;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    pub enum ExportFormat {
+        // every field, can be imported back
+        Json,
+        // a page styled like the chat view
+        Html,
+        Text,
+    }
+
+    // content of a JSON export
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct HistoryExport {
+        pub kind: String, // EXPORT_KIND
+        pub version: u32,
+        pub exported_at: u64, // unix seconds
+        // the user the history belongs to
+        pub account: UserProfile,
+        pub contacts: Vec<AddressBookEntry>,
+        pub conversations: Vec<ExportedConversation>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct ExportedConversation {
+        pub id: String,
+        pub created_at: u64,
+        // oldest first
+        pub messages: Vec<ExportedMessage>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct ExportedMessage {
+        #[serde(flatten)]
+        pub message: ChatMessage,
+        #[serde(default)]
+        pub attachments: Vec<ExportedAttachment>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct ExportedAttachment {
+        pub file_name: String,
+        pub mime_type: String,
+        pub size: u64, // bytes
+        // where the file was on the exporting device
+        pub source_path: String,
+        // where it is in the attachments archive, None when it wasn't bundled
+        pub archive_path: Option<String>,
+    }
+
+    // what an export wrote
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct ExportReport {
+        pub path: String,
+        pub attachments_path: Option<String>,
+        pub conversations: usize,
+        pub messages: usize,
+        // attachments written to the archive
+        pub attachments: usize,
+        // attachment files that weren't on the disk anymore
+        pub missing_attachments: Vec<String>,
+    }
+}
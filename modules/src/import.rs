@@ -0,0 +1,373 @@
+// Import of a JSON export into the local history, the one of all the conversations being the
+// full backup. Messages are matched by their server id and what's already here is never
+// overwritten. Contacts aren't created, the server keeps the list: the ones still in it get back
+// the nicknames, notes and addresses they had. A dry run takes the same steps in a transaction
+// that is rolled back, and leaves the attachment files where they are. The attachment files are
+// staged next to the attachments and only moved in once the rows are committed, under a name
+// no other file has
+use crate::export::structs::{ExportedAttachment, HistoryExport};
+use crate::export::{attachments_archive_path, EXPORT_KIND, EXPORT_VERSION};
+use crate::p2p_connection::new_client_id;
+use crate::p2p_connection::structs::ChatMessage;
+use crate::profiles::data_path;
+use crate::sqlite::structs::{AddressBookEntry, Attachment};
+use crate::sqlite::{self, ATTACHMENTS_DIR, FORMER_PROFILE_ADDRESS_LABEL, PROFILE_ADDRESS_LABEL};
+use rusqlite::Connection;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use structs::ImportReport;
+use zip::ZipArchive;
+
+// EH
+// merges the export at `path` into the history of `me`, the attachments come from the zip
+// written next to it by the export
+pub fn import_history(
+    connection: &mut Connection,
+    me: &str,
+    path: &str,
+    dry_run: bool,
+) -> Result<ImportReport, String> {
+    import_history_in(connection, &data_path(ATTACHMENTS_DIR), me, path, dry_run)
+}
+
+// EH
+// the same with the attachment files saved in `attachments_dir`
+pub fn import_history_in(
+    connection: &mut Connection,
+    attachments_dir: &str,
+    me: &str,
+    path: &str,
+    dry_run: bool,
+) -> Result<ImportReport, String> {
+    let export = read_export(path)?;
+    if export.account.username != me {
+        return Err(format!(
+            "this history belongs to @{}, sign in as them to import it",
+            export.account.username
+        ));
+    }
+    let mut archive = match File::open(attachments_archive_path(path)) {
+        Ok(file) => match ZipArchive::new(file) {
+            Ok(archive) => Some(archive),
+            Err(e) => return Err(format!("couldn't read the attachments archive \n {}", e)),
+        },
+        Err(_) => None, // exported without attachments
+    };
+
+    let mut report = ImportReport {
+        dry_run,
+        conversations: 0,
+        messages_added: 0,
+        messages_already_here: 0,
+        messages_conflicting: vec![],
+        attachments_added: 0,
+        attachments_missing: vec![],
+        contacts_updated: vec![],
+        contacts_missing: vec![],
+    };
+    let mut staging = Staging {
+        attachments_dir: PathBuf::from(attachments_dir),
+        directory: Path::new(attachments_dir).join(format!(".importing-{}", new_client_id())),
+        files: vec![],
+    };
+    let merged = merge_export(connection, &export, &mut archive, &mut staging, &mut report)
+        .and_then(|_| staging.move_into_place());
+    let _ = fs::remove_dir_all(&staging.directory);
+    merged.map(|_| report)
+}
+
+// EH
+// the rows of the export in one transaction, committed unless it's a dry run
+fn merge_export(
+    connection: &mut Connection,
+    export: &HistoryExport,
+    archive: &mut Option<ZipArchive<File>>,
+    staging: &mut Staging,
+    report: &mut ImportReport,
+) -> Result<(), String> {
+    let transaction = match connection.transaction() {
+        Ok(transaction) => transaction,
+        Err(e) => return Err(format!("couldn't import the history \n {}", e)),
+    };
+    for conversation in &export.conversations {
+        sqlite::save_conversation(&transaction, &conversation.id, conversation.created_at)?;
+        let added = report.messages_added;
+        for exported in &conversation.messages {
+            let message = &exported.message;
+            if message.conversation_id != conversation.id {
+                report.messages_conflicting.push(message.id);
+                continue;
+            }
+            match sqlite::get_message(&transaction, message.id)? {
+                None => {
+                    sqlite::insert_message(&transaction, message)?;
+                    report.messages_added += 1;
+                    for attachment in &exported.attachments {
+                        import_attachment(
+                            &transaction,
+                            archive,
+                            staging,
+                            message.id,
+                            attachment,
+                            report,
+                        )?;
+                    }
+                }
+                Some(stored) if same_message(&stored, message) => {
+                    // a receipt the exporting device got may not have reached this one
+                    sqlite::insert_message(&transaction, message)?;
+                    report.messages_already_here += 1;
+                }
+                Some(_) => report.messages_conflicting.push(message.id),
+            }
+        }
+        if report.messages_added > added {
+            report.conversations += 1;
+        }
+    }
+    for entry in &export.contacts {
+        reconcile_contact(&transaction, entry, report)?;
+    }
+
+    if report.dry_run {
+        return Ok(()); // the transaction is rolled back when dropped
+    }
+    match transaction.commit() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't import the history \n {}", e)),
+    }
+}
+
+fn read_export(path: &str) -> Result<HistoryExport, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => return Err(format!("couldn't read {} \n {}", path, e)),
+    };
+    let export: HistoryExport = match serde_json::from_str(&content) {
+        Ok(export) => export,
+        Err(e) => {
+            return Err(format!(
+                "{} isn't a chatuza export, only the JSON ones can be imported \n {}",
+                path, e
+            ))
+        }
+    };
+    if export.kind != EXPORT_KIND {
+        return Err(format!("{} isn't a chatuza export", path));
+    }
+    if export.version > EXPORT_VERSION {
+        return Err(format!(
+            "{} was exported by a newer version of chatuza, update the app to import it",
+            path
+        ));
+    }
+    Ok(export)
+}
+
+// the same message as far as the server is concerned, the status only moves forward
+fn same_message(stored: &ChatMessage, imported: &ChatMessage) -> bool {
+    stored.conversation_id == imported.conversation_id
+        && stored.sender == imported.sender
+        && stored.body == imported.body
+        && stored.sent_at == imported.sent_at
+}
+
+fn import_attachment(
+    connection: &Connection,
+    archive: &mut Option<ZipArchive<File>>,
+    staging: &mut Staging,
+    message_id: u64,
+    attachment: &ExportedAttachment,
+    report: &mut ImportReport,
+) -> Result<(), String> {
+    let (archive, archive_path) = match (archive.as_mut(), &attachment.archive_path) {
+        (Some(archive), Some(archive_path)) => (archive, archive_path),
+        _ => {
+            report
+                .attachments_missing
+                .push(attachment.file_name.clone());
+            return Ok(());
+        }
+    };
+    let mut file = match archive.by_name(archive_path) {
+        Ok(file) => file,
+        Err(_) => {
+            report
+                .attachments_missing
+                .push(attachment.file_name.clone());
+            return Ok(());
+        }
+    };
+    // only the name is kept, a path in the export can't point outside the attachments
+    let file_name = match Path::new(&attachment.file_name).file_name() {
+        Some(file_name) => file_name.to_string_lossy().to_string(),
+        None => format!("attachment-{}", message_id),
+    };
+    let target = staging.free_target(message_id, &file_name);
+    if !report.dry_run {
+        if let Err(e) = staging.stage(&mut file, &target) {
+            return Err(format!(
+                "couldn't save the attachment {} \n {}",
+                file_name, e
+            ));
+        }
+    }
+    sqlite::insert_attachment(
+        connection,
+        &Attachment {
+            id: 0,
+            message_id,
+            file_name,
+            mime_type: attachment.mime_type.clone(),
+            size: attachment.size,
+            path: target.to_string_lossy().to_string(),
+        },
+    )?;
+    report.attachments_added += 1;
+    Ok(())
+}
+
+// the attachment files of an import, written to `directory` until the rows are committed
+struct Staging {
+    attachments_dir: PathBuf,
+    directory: PathBuf,
+    // (staged file, where it goes)
+    files: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Staging {
+    // where a file of `message_id` can go without replacing another one, "cat (1).png" when
+    // "cat.png" is taken
+    fn free_target(&self, message_id: u64, file_name: &str) -> PathBuf {
+        let directory = self.attachments_dir.join(message_id.to_string());
+        let name = Path::new(file_name);
+        let stem = match name.file_stem() {
+            Some(stem) => stem.to_string_lossy().to_string(),
+            None => file_name.to_string(),
+        };
+        let mut target = directory.join(file_name);
+        let mut copy = 1;
+        while target.exists() || self.files.iter().any(|(_, taken)| *taken == target) {
+            target = match name.extension() {
+                Some(extension) => directory.join(format!(
+                    "{} ({}).{}",
+                    stem,
+                    copy,
+                    extension.to_string_lossy()
+                )),
+                None => directory.join(format!("{} ({})", stem, copy)),
+            };
+            copy += 1;
+        }
+        target
+    }
+
+    fn stage(&mut self, content: &mut impl Read, target: &Path) -> io::Result<()> {
+        fs::create_dir_all(&self.directory)?;
+        let staged = self.directory.join(self.files.len().to_string());
+        let mut output = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&staged)?;
+        io::copy(content, &mut output)?;
+        self.files.push((staged, target.to_path_buf()));
+        Ok(())
+    }
+
+    // EH
+    // once the rows are committed, a file written meanwhile at a target is left alone
+    fn move_into_place(&self) -> Result<(), String> {
+        for (staged, target) in &self.files {
+            let moved = match target.exists() {
+                true => Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "another file took its place",
+                )),
+                false => target
+                    .parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|_| fs::rename(staged, target)),
+            };
+            if let Err(e) = moved {
+                return Err(format!(
+                    "couldn't save the attachment {} \n {}",
+                    target.to_string_lossy(),
+                    e
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// what the user wrote about the contact fills what's empty here, the addresses are merged.
+// The profile stays the one the server gave last
+fn reconcile_contact(
+    connection: &Connection,
+    exported: &AddressBookEntry,
+    report: &mut ImportReport,
+) -> Result<(), String> {
+    let username = &exported.profile.username;
+    let local = match sqlite::get_address_book_entry(connection, username)? {
+        Some(local) => local,
+        None => {
+            report.contacts_missing.push(username.clone());
+            return Ok(());
+        }
+    };
+    let nickname = local.nickname.clone().or(exported.nickname.clone());
+    let notes = local.notes.clone().or(exported.notes.clone());
+    let custom_avatar_url = local
+        .custom_avatar_url
+        .clone()
+        .or(exported.custom_avatar_url.clone());
+    let mut updated = (&nickname, &notes, &custom_avatar_url)
+        != (&local.nickname, &local.notes, &local.custom_avatar_url);
+    if updated {
+        sqlite::update_contact_details(
+            connection,
+            username,
+            nickname.as_deref(),
+            notes.as_deref(),
+            custom_avatar_url.as_deref(),
+        )?;
+    }
+    for address in &exported.addresses {
+        let mut address = address.clone();
+        address.username = username.clone();
+        // the profile address of then isn't the one of the profile now
+        if address.label == PROFILE_ADDRESS_LABEL && address.address != local.profile.wallet_pubkey
+        {
+            address.label = FORMER_PROFILE_ADDRESS_LABEL.to_string();
+        }
+        updated |= sqlite::merge_contact_address(connection, &address)?;
+    }
+    if updated {
+        report.contacts_updated.push(username.clone());
+    }
+    Ok(())
+}
+
+pub mod structs {
+    use serde::{Deserialize, Serialize};
+
+    // what an import changed, or would change on a dry run
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct ImportReport {
+        pub dry_run: bool,
+        // conversations that gained messages
+        pub conversations: usize,
+        pub messages_added: usize,
+        pub messages_already_here: usize,
+        // ids of the messages that differ from the ones here, the ones here are kept
+        pub messages_conflicting: Vec<u64>,
+        pub attachments_added: usize,
+        // file names of the attachments that weren't in the archive
+        pub attachments_missing: Vec<String>,
+        pub contacts_updated: Vec<String>,
+        // usernames of the exported contacts that aren't contacts anymore, add them to bring
+        // back what was kept about them
+        pub contacts_missing: Vec<String>,
+    }
+}
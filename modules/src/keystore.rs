@@ -0,0 +1,196 @@
+// Password protected keystore, the signing key never touches the disk in clear.
+// The key is derived with scrypt and the keypair bytes are sealed with chacha20-poly1305.
+use crate::structs::EncryptedKeystore;
+use crypto::aead::{AeadDecryptor, AeadEncryptor};
+use crypto::chacha20poly1305::ChaCha20Poly1305;
+use crypto::hkdf::{hkdf_expand, hkdf_extract};
+use crypto::scrypt::{scrypt, ScryptParams};
+use crypto::sha2::Sha256;
+use rand::Rng;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signer::{keypair::Keypair, Signer};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use zeroize::Zeroizing;
+
+const KEYSTORE_VERSION: u8 = 1;
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+// EH
+pub fn write_keystore(path: &str, keypair: &Keypair, password: &str) -> Result<(), String> {
+    if password.is_empty() {
+        return Err("the wallet password can't be empty".to_string());
+    }
+    let salt: [u8; 16] = rand::thread_rng().gen();
+    let key = derive_key(password, &salt, SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P);
+    let secret = Zeroizing::new(keypair.to_bytes());
+    let (nonce, ciphertext, tag) = seal(&key, &secret[..]);
+
+    let keystore = EncryptedKeystore {
+        version: KEYSTORE_VERSION,
+        pub_key: keypair.pubkey().to_string(),
+        scrypt_log_n: SCRYPT_LOG_N,
+        scrypt_r: SCRYPT_R,
+        scrypt_p: SCRYPT_P,
+        salt: salt.to_vec(),
+        nonce,
+        ciphertext,
+        tag,
+    };
+    let keystore_text = match serde_json::to_string(&keystore) {
+        Ok(text) => text,
+        Err(e) => return Err(format!("couldn't serialize the keystore \n {}", e)),
+    };
+    // written next to it and renamed over it, a crash or a full disk leaves the old one whole
+    let writing_path = format!("{}.writing", path);
+    let written = File::create(&writing_path)
+        .and_then(|mut file| {
+            file.write_all(keystore_text.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&writing_path, path));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&writing_path);
+        return Err(format!("error while saving the key pair \n {}", e));
+    }
+    Ok(())
+}
+
+// EH
+pub fn unlock_keystore(path: &str, password: &str) -> Result<Keypair, String> {
+    let keystore = match read_keystore(path)? {
+        Some(keystore) => keystore,
+        None => return migrate_plain_keystore(path, password),
+    };
+    if keystore.version != KEYSTORE_VERSION {
+        return Err(format!(
+            "the keystore version {} is not supported by this version of the app",
+            keystore.version
+        ));
+    }
+    let key = derive_key(
+        password,
+        &keystore.salt,
+        keystore.scrypt_log_n,
+        keystore.scrypt_r,
+        keystore.scrypt_p,
+    );
+    let secret = match open_sealed(&key, &keystore.nonce, &keystore.ciphertext, &keystore.tag) {
+        Some(secret) => secret,
+        None => return Err("wrong password".to_string()),
+    };
+    match Keypair::from_bytes(&secret) {
+        Ok(kp) => Ok(kp),
+        Err(e) => Err(format!("the keystore content is corrupted \n {:?}", e)),
+    }
+}
+
+// EH
+// the public key is kept in clear so the account can be shown while the wallet is locked
+pub fn read_keystore_pubkey(path: &str) -> Result<Pubkey, String> {
+    match read_keystore(path)? {
+        Some(keystore) => match Pubkey::from_str(&keystore.pub_key) {
+            Ok(pk) => Ok(pk),
+            Err(e) => Err(format!("the keystore public key is corrupted \n {:?}", e)),
+        },
+        None => match solana_sdk::signer::keypair::read_keypair_file(path) {
+            Ok(kp) => Ok(kp.pubkey()),
+            Err(e) => Err(format!("Error while reading the signer info \n {:?}", e)),
+        },
+    }
+}
+
+// EH
+pub fn change_keystore_password(
+    path: &str,
+    old_password: &str,
+    new_password: &str,
+) -> Result<(), String> {
+    let keypair = unlock_keystore(path, old_password)?;
+    write_keystore(path, &keypair, new_password)
+}
+
+// None when the file is a legacy plain solana keypair file
+fn read_keystore(path: &str) -> Result<Option<EncryptedKeystore>, String> {
+    if !Path::new(path).is_file() {
+        return Err("there is no account on this device, create or import one first".to_string());
+    }
+    let keystore_text = match fs::read_to_string(path) {
+        Ok(text) => Zeroizing::new(text),
+        Err(e) => return Err(format!("Error while reading the signer info \n {}", e)),
+    };
+    if keystore_text.trim_start().starts_with('[') {
+        return Ok(None);
+    }
+    match serde_json::from_str::<EncryptedKeystore>(&keystore_text) {
+        Ok(keystore) => Ok(Some(keystore)),
+        Err(e) => Err(format!("the keystore file is corrupted \n {}", e)),
+    }
+}
+
+// accounts created before the keystore existed are sealed with the first password given
+fn migrate_plain_keystore(path: &str, password: &str) -> Result<Keypair, String> {
+    let keypair = match solana_sdk::signer::keypair::read_keypair_file(path) {
+        Ok(kp) => kp,
+        Err(e) => return Err(format!("Error while reading the signer info \n {:?}", e)),
+    };
+    write_keystore(path, &keypair, password)?;
+    Ok(keypair)
+}
+
+// encrypts with a random nonce, returns (nonce, ciphertext, tag)
+pub(crate) fn seal(key: &[u8; 32], plaintext: &[u8]) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+    let nonce: [u8; 8] = rand::thread_rng().gen();
+    let mut ciphertext = vec![0u8; plaintext.len()];
+    let mut tag = vec![0u8; 16];
+    ChaCha20Poly1305::new(&key[..], &nonce, &[]).encrypt(plaintext, &mut ciphertext, &mut tag);
+    (nonce.to_vec(), ciphertext, tag)
+}
+
+// None when the key is wrong or the data was tampered with
+pub(crate) fn open_sealed(
+    key: &[u8; 32],
+    nonce: &[u8],
+    ciphertext: &[u8],
+    tag: &[u8],
+) -> Option<Zeroizing<Vec<u8>>> {
+    if nonce.len() != 8 || tag.len() != 16 {
+        return None;
+    }
+    let mut plaintext = Zeroizing::new(vec![0u8; ciphertext.len()]);
+    if ChaCha20Poly1305::new(&key[..], nonce, &[]).decrypt(ciphertext, &mut plaintext, tag) {
+        Some(plaintext)
+    } else {
+        None
+    }
+}
+
+// a key for local data that only the unlocked wallet can read, one per `purpose`
+pub(crate) fn derive_storage_key(keypair: &Keypair, purpose: &str) -> Zeroizing<[u8; 32]> {
+    let secret = Zeroizing::new(keypair.secret().to_bytes());
+    let mut prk = Zeroizing::new([0u8; 32]);
+    hkdf_extract(Sha256::new(), b"chatuza-storage", &secret[..], &mut prk[..]);
+    let mut key = Zeroizing::new([0u8; 32]);
+    hkdf_expand(Sha256::new(), &prk[..], purpose.as_bytes(), &mut key[..]);
+    key
+}
+
+// the key of the local database, so it opens with the same password as the wallet
+pub(crate) fn derive_database_key(password: &str, salt: &[u8]) -> Zeroizing<[u8; 32]> {
+    derive_key(password, salt, SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P)
+}
+
+fn derive_key(password: &str, salt: &[u8], log_n: u8, r: u32, p: u32) -> Zeroizing<[u8; 32]> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    scrypt(
+        password.as_bytes(),
+        salt,
+        &ScryptParams::new(log_n, r, p),
+        &mut key[..],
+    );
+    key
+}
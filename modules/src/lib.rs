@@ -1,2 +1,12 @@
+pub mod export;
+pub mod import;
+mod keystore;
+pub mod migrations;
+pub mod p2p_connection;
+pub mod profiles;
+pub mod retention;
+pub mod server_interaction;
+mod shamir;
 pub mod solana_wallet;
+pub mod sqlite;
 pub use solana_wallet::structs;
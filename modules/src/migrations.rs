@@ -0,0 +1,261 @@
+// Schema history of the local database, applied in order when it's opened.
+// A migration is never edited once released, changes go in a new one at the end of the list.
+// Databases made before the schema_version table existed are recognized by their tables
+use crate::solana_wallet::unix_now;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::fs;
+use std::path::Path;
+
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "outbox of the messages not accepted by the server",
+        sql: "CREATE TABLE outbox (
+            client_id TEXT PRIMARY KEY,
+            conversation_id TEXT NOT NULL,
+            body TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            state TEXT NOT NULL,
+            last_error TEXT
+        );",
+    },
+    Migration {
+        version: 2,
+        description: "message history with receipts, settings",
+        sql: "CREATE TABLE messages (
+            id INTEGER PRIMARY KEY,
+            conversation_id TEXT NOT NULL,
+            sender TEXT NOT NULL,
+            body TEXT NOT NULL,
+            sent_at INTEGER NOT NULL,
+            client_id TEXT,
+            status INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE INDEX messages_conversation ON messages (conversation_id, id);
+        CREATE TABLE settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );",
+    },
+    Migration {
+        version: 3,
+        description: "accounts, contacts, conversations and attachments",
+        sql: "CREATE TABLE accounts (
+            pub_key TEXT PRIMARY KEY,
+            username TEXT,
+            display_name TEXT,
+            created_at INTEGER NOT NULL,
+            last_used_at INTEGER NOT NULL
+        );
+        CREATE TABLE contacts (
+            username TEXT PRIMARY KEY,
+            display_name TEXT NOT NULL,
+            wallet_pubkey TEXT NOT NULL,
+            avatar_url TEXT,
+            added_at INTEGER NOT NULL
+        );
+        CREATE TABLE conversations (
+            id TEXT PRIMARY KEY,
+            created_at INTEGER NOT NULL,
+            last_message_id INTEGER,
+            last_message_at INTEGER
+        );
+        INSERT INTO conversations (id, created_at, last_message_id, last_message_at)
+            SELECT conversation_id, min(sent_at), max(id), max(sent_at)
+            FROM messages GROUP BY conversation_id;
+        CREATE TABLE attachments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            message_id INTEGER NOT NULL REFERENCES messages (id) ON DELETE CASCADE,
+            file_name TEXT NOT NULL,
+            mime_type TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            path TEXT NOT NULL
+        );
+        CREATE INDEX attachments_message ON attachments (message_id);",
+    },
+    Migration {
+        version: 4,
+        description: "full-text index of the message bodies",
+        // the index reads the bodies from messages, the triggers keep it in step
+        sql: "CREATE VIRTUAL TABLE messages_fts USING fts5 (
+            body,
+            content = 'messages',
+            content_rowid = 'id',
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+        CREATE TRIGGER messages_fts_insert AFTER INSERT ON messages BEGIN
+            INSERT INTO messages_fts (rowid, body) VALUES (new.id, new.body);
+        END;
+        CREATE TRIGGER messages_fts_delete AFTER DELETE ON messages BEGIN
+            INSERT INTO messages_fts (messages_fts, rowid, body) VALUES ('delete', old.id, old.body);
+        END;
+        CREATE TRIGGER messages_fts_update AFTER UPDATE OF body ON messages BEGIN
+            INSERT INTO messages_fts (messages_fts, rowid, body) VALUES ('delete', old.id, old.body);
+            INSERT INTO messages_fts (rowid, body) VALUES (new.id, new.body);
+        END;
+        INSERT INTO messages_fts (messages_fts) VALUES ('rebuild');",
+    },
+    Migration {
+        version: 5,
+        description: "address book: wallet addresses, nicknames and notes of the contacts",
+        // the address of the chatuza profile is the first one of every contact, unverified
+        sql: "ALTER TABLE contacts ADD COLUMN nickname TEXT;
+        ALTER TABLE contacts ADD COLUMN notes TEXT;
+        ALTER TABLE contacts ADD COLUMN custom_avatar_url TEXT;
+        ALTER TABLE contacts ADD COLUMN previous_wallet_pubkey TEXT;
+        CREATE TABLE contact_addresses (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL REFERENCES contacts (username) ON DELETE CASCADE,
+            address TEXT NOT NULL,
+            label TEXT NOT NULL,
+            verified_at INTEGER,
+            added_at INTEGER NOT NULL,
+            UNIQUE (username, address)
+        );
+        INSERT INTO contact_addresses (username, address, label, added_at)
+            SELECT username, wallet_pubkey, 'chatuza profile', added_at FROM contacts;",
+    },
+    Migration {
+        version: 6,
+        description: "disappearing messages timers, deletions that leave no trace in the index",
+        sql: "ALTER TABLE conversations ADD COLUMN retention_secs INTEGER;
+        ALTER TABLE conversations ADD COLUMN retention_set_by TEXT;
+        ALTER TABLE conversations ADD COLUMN retention_set_at INTEGER;
+        CREATE INDEX messages_sent_at ON messages (sent_at);
+        INSERT INTO messages_fts (messages_fts, rank) VALUES ('secure-delete', 1);",
+    },
+];
+
+// the version this build of the app works with
+pub const SCHEMA_VERSION: u32 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+// EH
+// 0 for an empty database
+pub fn schema_version(connection: &Connection) -> Result<u32, String> {
+    let versioned = table_exists(connection, "schema_version")?;
+    if !versioned {
+        return legacy_version(connection);
+    }
+    match connection.query_row(
+        "SELECT coalesce(max(version), 0) FROM schema_version",
+        [],
+        |row| row.get(0),
+    ) {
+        Ok(version) => Ok(version),
+        Err(e) => Err(format!("couldn't read the local database version \n {}", e)),
+    }
+}
+
+// EH
+// brings the database at `path` to SCHEMA_VERSION, copying the file aside first.
+// All the missing migrations run in one transaction, a failure leaves the database as it was
+pub fn migrate(connection: &mut Connection, path: &str) -> Result<(), String> {
+    let versioned = table_exists(connection, "schema_version")?;
+    let current = schema_version(connection)?;
+    if versioned && current == SCHEMA_VERSION {
+        return Ok(());
+    }
+    if current > SCHEMA_VERSION {
+        return Err(format!(
+            "the local database was written by a newer version of chatuza (schema {}, this one knows {}), update the app",
+            current, SCHEMA_VERSION
+        ));
+    }
+    // a legacy database already at the last version is only stamped
+    if current > 0 && current < SCHEMA_VERSION {
+        backup(connection, path, current)?;
+    }
+
+    let transaction = match connection.transaction() {
+        Ok(transaction) => transaction,
+        Err(e) => return Err(format!("couldn't upgrade the local database \n {}", e)),
+    };
+    if let Err(e) = transaction.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            applied_at INTEGER NOT NULL
+        );",
+    ) {
+        return Err(format!("couldn't upgrade the local database \n {}", e));
+    }
+    if !versioned && current > 0 {
+        // a database from before the version table, what it has is recorded as applied
+        record_version(&transaction, current)?;
+    }
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        if let Err(e) = transaction.execute_batch(migration.sql) {
+            return Err(format!(
+                "couldn't upgrade the local database to version {} ({}) \n {}",
+                migration.version, migration.description, e
+            ));
+        }
+        record_version(&transaction, migration.version)?;
+    }
+    match transaction.commit() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't upgrade the local database \n {}", e)),
+    }
+}
+
+// the file next to the database keeping it as it was at `version`
+pub fn backup_path(path: &str, version: u32) -> String {
+    format!("{}.v{}.bak", path, version)
+}
+
+fn backup(connection: &Connection, path: &str, version: u32) -> Result<(), String> {
+    if !Path::new(path).exists() {
+        return Ok(()); // in memory
+    }
+    // everything still in the write ahead log goes into the file first
+    if let Err(e) = connection.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(())) {
+        return Err(format!("couldn't back up the local database \n {}", e));
+    }
+    match fs::copy(path, backup_path(path, version)) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't back up the local database \n {}", e)),
+    }
+}
+
+fn record_version(connection: &Connection, version: u32) -> Result<(), String> {
+    match connection.execute(
+        "INSERT OR IGNORE INTO schema_version (version, applied_at) VALUES (?1, ?2)",
+        params![version, unix_now()],
+    ) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't upgrade the local database \n {}", e)),
+    }
+}
+
+// the releases before the version table created their tables directly
+fn legacy_version(connection: &Connection) -> Result<u32, String> {
+    if table_exists(connection, "accounts")? {
+        Ok(3)
+    } else if table_exists(connection, "messages")? {
+        Ok(2)
+    } else if table_exists(connection, "outbox")? {
+        Ok(1)
+    } else {
+        Ok(0)
+    }
+}
+
+fn table_exists(connection: &Connection, table: &str) -> Result<bool, String> {
+    match connection
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            params![table],
+            |_| Ok(()),
+        )
+        .optional()
+    {
+        Ok(found) => Ok(found.is_some()),
+        Err(e) => Err(format!("couldn't read the local database \n {}", e)),
+    }
+}
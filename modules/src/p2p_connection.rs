@@ -0,0 +1,902 @@
+// Persistent websocket to the Chatuza server carrying the chat messages.
+// The connection authenticates with the session token, keeps itself alive with pings,
+// reconnects with an exponential backoff and resumes after the last acknowledged message.
+// Outgoing messages go through the outbox of the local database first, so nothing written
+// offline is lost: they are sent again on every connection until the server accepts them.
+use crate::retention;
+use crate::server_interaction::ChatuzaServerClient;
+use crate::solana_wallet::unix_now;
+use crate::sqlite::{self, Database};
+use futures_util::stream::{SplitSink, SplitStream};
+use futures_util::{SinkExt, StreamExt};
+use rand::Rng;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use structs::{
+    ChatMessage, ClientFrame, ConnectionEvent, MessageStatus, OutboxEntry, OutboxState,
+    PresenceStatus, RetentionUpdate, ServerFrame,
+};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::watch;
+use tokio::time::{interval, sleep, Instant};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+const WS_PATH: &str = "/api/ws";
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(20);
+// nothing received for this long means the connection is dead even if the socket looks open
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(60);
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+// "false" stops telling senders their messages were read
+pub const SEND_READ_RECEIPTS_SETTING: &str = "send_read_receipts";
+// the last message id acknowledged to the server, where the next run resumes from
+pub const LAST_ACKED_SETTING: &str = "last_acked_message_id";
+// typing notifications of one conversation are sent at most this often
+const TYPING_RATE: Duration = Duration::from_secs(3);
+// a message the server never accepted after this many connections is marked failed
+pub const MAX_SEND_ATTEMPTS: u32 = 5;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type SocketWriter = SplitSink<Socket, Message>;
+
+enum Command {
+    Frame(ClientFrame),
+    // an outbox entry to send, if it's still pending
+    Deliver(String),
+}
+
+// what the connection task shares with the handles
+struct Shared {
+    server: ChatuzaServerClient,
+    database: Database,
+    events: UnboundedSender<ConnectionEvent>,
+    last_acked: AtomicU64, // 0 while nothing was acknowledged
+    connected: AtomicBool,
+    // announced again on every connection
+    presence: Mutex<PresenceStatus>,
+    // the users whose presence we follow, usually the contacts
+    watched: Mutex<Vec<String>>,
+    typing_sent: Mutex<HashMap<String, Instant>>,
+}
+
+// Handle of the background connection task, cheap to clone.
+// The task stops on `shutdown` or when every handle is dropped
+#[derive(Clone)]
+pub struct ChatConnection {
+    commands: UnboundedSender<Command>,
+    shutdown: Arc<watch::Sender<bool>>,
+    shared: Arc<Shared>,
+}
+
+impl ChatConnection {
+    // spawns the connection on the current tokio runtime, the events come out of the receiver.
+    // `resume_after` is the last message id acknowledged by a previous run
+    pub fn connect(
+        server: ChatuzaServerClient,
+        database: Database,
+        resume_after: Option<u64>,
+    ) -> (ChatConnection, UnboundedReceiver<ConnectionEvent>) {
+        let (commands_tx, commands_rx) = mpsc::unbounded_channel();
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let shared = Arc::new(Shared {
+            server,
+            database,
+            events: events_tx,
+            last_acked: AtomicU64::new(resume_after.unwrap_or(0)),
+            connected: AtomicBool::new(false),
+            presence: Mutex::new(PresenceStatus::Online),
+            watched: Mutex::new(vec![]),
+            typing_sent: Mutex::new(HashMap::new()),
+        });
+
+        tokio::spawn(run(shared.clone(), commands_rx, shutdown_rx));
+        (
+            ChatConnection {
+                commands: commands_tx,
+                shutdown: Arc::new(shutdown_tx),
+                shared,
+            },
+            events_rx,
+        )
+    }
+
+    // EH
+    // frames sent while disconnected wait for the next connection
+    pub fn send(&self, frame: ClientFrame) -> Result<(), String> {
+        self.command(Command::Frame(frame))
+    }
+
+    // EH
+    // queues the message in the outbox, it's delivered as soon as the server is reachable
+    pub fn send_message(&self, conversation_id: &str, body: &str) -> Result<OutboxEntry, String> {
+        let entry = OutboxEntry {
+            client_id: new_client_id(),
+            conversation_id: conversation_id.to_string(),
+            body: body.to_string(),
+            created_at: unix_now(),
+            attempts: 0,
+            state: OutboxState::Pending,
+            last_error: None,
+        };
+        let database = self.shared.database.connection()?;
+        sqlite::insert_outbox(&database, &entry)?;
+        self.command(Command::Deliver(entry.client_id.clone()))?;
+        Ok(entry)
+    }
+
+    // EH
+    // puts a failed message back in the queue
+    pub fn retry_message(&self, client_id: &str) -> Result<OutboxEntry, String> {
+        let entry = {
+            let database = self.shared.database.connection()?;
+            let mut entry = match sqlite::get_outbox(&database, client_id)? {
+                Some(entry) => entry,
+                None => return Err("the message was already sent".to_string()),
+            };
+            entry.attempts = 0;
+            entry.state = OutboxState::Pending;
+            entry.last_error = None;
+            sqlite::update_outbox(&database, &entry)?;
+            entry
+        };
+        self.command(Command::Deliver(entry.client_id.clone()))?;
+        Ok(entry)
+    }
+
+    // EH
+    // the messages of the conversation up to `up_to` were shown to the user,
+    // the sender only hears about it when read receipts are enabled
+    pub fn mark_read(&self, conversation_id: &str, up_to: u64) -> Result<(), String> {
+        let send_receipt = {
+            let database = self.shared.database.connection()?;
+            let me = match self.shared.server.current_user() {
+                Some(user) => user.username,
+                None => return Err("sign in to start chatting".to_string()),
+            };
+            if !sqlite::mark_read(&database, conversation_id, &me, up_to)? {
+                return Ok(()); // nothing new
+            }
+            sqlite::get_setting(&database, SEND_READ_RECEIPTS_SETTING)?.as_deref() != Some("false")
+        };
+        if send_receipt {
+            self.send(ClientFrame::Read {
+                conversation_id: conversation_id.to_string(),
+                up_to,
+            })?;
+        }
+        Ok(())
+    }
+
+    // EH
+    pub fn set_presence(&self, status: PresenceStatus) -> Result<(), String> {
+        {
+            let mut presence = self.shared.presence.lock().unwrap();
+            if *presence == status {
+                return Ok(());
+            }
+            *presence = status;
+        }
+        self.send(ClientFrame::Presence { status })
+    }
+
+    // EH
+    // replaces the followed users, the server answers with their current presence
+    pub fn watch_presence(&self, usernames: Vec<String>) -> Result<(), String> {
+        *self.shared.watched.lock().unwrap() = usernames.clone();
+        self.send(ClientFrame::Subscribe { usernames })
+    }
+
+    // EH
+    // called on every keystroke, only reaches the server every few seconds and never queues
+    pub fn typing(&self, conversation_id: &str) -> Result<(), String> {
+        if !self.shared.connected.load(Ordering::SeqCst) {
+            return Ok(()); // stale by the time we reconnect
+        }
+        {
+            let mut typing_sent = self.shared.typing_sent.lock().unwrap();
+            let now = Instant::now();
+            if let Some(last) = typing_sent.get(conversation_id) {
+                if now.duration_since(*last) < TYPING_RATE {
+                    return Ok(());
+                }
+            }
+            typing_sent.insert(conversation_id.to_string(), now);
+        }
+        self.send(ClientFrame::Typing {
+            conversation_id: conversation_id.to_string(),
+        })
+    }
+
+    // EH
+    // asks the server to make the messages of the conversation disappear `retention_secs` after
+    // they were sent, None keeps them. It applies once the server hands the change to both sides
+    pub fn set_retention(
+        &self,
+        conversation_id: &str,
+        retention_secs: Option<u64>,
+    ) -> Result<(), String> {
+        self.send(ClientFrame::Retention {
+            conversation_id: conversation_id.to_string(),
+            retention_secs,
+        })
+    }
+
+    pub fn last_acked(&self) -> Option<u64> {
+        match self.shared.last_acked.load(Ordering::SeqCst) {
+            0 => None,
+            id => Some(id),
+        }
+    }
+
+    pub fn shutdown(&self) {
+        let _ = self.shutdown.send(true);
+    }
+
+    fn command(&self, command: Command) -> Result<(), String> {
+        match self.commands.send(command) {
+            Ok(_) => Ok(()),
+            Err(_) => Err("the chat connection is closed".to_string()),
+        }
+    }
+}
+
+// EH
+// where a new run resumes from: the last acknowledged message, or the newest one kept for
+// databases older than the setting
+pub fn resume_point(connection: &Connection) -> Result<Option<u64>, String> {
+    let saved = sqlite::get_setting(connection, LAST_ACKED_SETTING)?
+        .and_then(|value| value.parse::<u64>().ok());
+    let newest = sqlite::last_message_id(connection)?;
+    Ok(saved.or(newest))
+}
+
+// the conversation of two users, the same on both sides
+pub fn direct_conversation_id(user_a: &str, user_b: &str) -> String {
+    if user_a <= user_b {
+        format!("direct:{}:{}", user_a, user_b)
+    } else {
+        format!("direct:{}:{}", user_b, user_a)
+    }
+}
+
+// the other user of a direct conversation of `me`, None when `me` isn't in it
+pub fn direct_peer(conversation_id: &str, me: &str) -> Option<String> {
+    let (user_a, user_b) = conversation_id.strip_prefix("direct:")?.split_once(':')?;
+    if user_a == me {
+        Some(user_b.to_string())
+    } else if user_b == me {
+        Some(user_a.to_string())
+    } else {
+        None
+    }
+}
+
+// random id given by the client to a message before the server knows about it,
+// the server drops a message whose id it already stored so resending is always safe
+pub fn new_client_id() -> String {
+    let bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+async fn run(
+    shared: Arc<Shared>,
+    mut commands: UnboundedReceiver<Command>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut attempt: u32 = 0;
+    loop {
+        let reason = match open_socket(&shared.server).await {
+            Ok(socket) => {
+                attempt = 0;
+                let _ = shared.events.send(ConnectionEvent::Connected);
+                match serve(socket, &shared, &mut commands, &mut shutdown).await {
+                    Some(reason) => reason,
+                    None => return, // shut down
+                }
+            }
+            Err(e) => e,
+        };
+        if *shutdown.borrow() {
+            return;
+        }
+
+        let delay = reconnect_delay(attempt);
+        attempt = attempt.saturating_add(1);
+        let _ = shared.events.send(ConnectionEvent::Disconnected {
+            reason,
+            retry_in_secs: delay.as_secs(),
+        });
+        tokio::select! {
+            _ = sleep(delay) => {}
+            _ = shutdown.changed() => return,
+        }
+    }
+}
+
+async fn open_socket(server: &ChatuzaServerClient) -> Result<Socket, String> {
+    let token = server.access_token().await?;
+    let mut request = match server.ws_url(WS_PATH).into_client_request() {
+        Ok(request) => request,
+        Err(e) => {
+            return Err(format!(
+                "couldn't build the chat connection request \n {}",
+                e
+            ))
+        }
+    };
+    match HeaderValue::from_str(&format!("Bearer {}", token)) {
+        Ok(header) => request.headers_mut().insert("Authorization", header),
+        Err(e) => return Err(format!("the session token is invalid \n {}", e)),
+    };
+    match connect_async(request).await {
+        Ok((socket, _)) => Ok(socket),
+        Err(e) => Err(format!("couldn't open the chat connection \n {}", e)),
+    }
+}
+
+// runs one connection, returns why it ended or None when shut down
+async fn serve(
+    socket: Socket,
+    shared: &Shared,
+    commands: &mut UnboundedReceiver<Command>,
+    shutdown: &mut watch::Receiver<bool>,
+) -> Option<String> {
+    let (mut write, mut read) = socket.split();
+
+    let after = match shared.last_acked.load(Ordering::SeqCst) {
+        0 => None,
+        id => Some(id),
+    };
+    if let Err(e) = write.send(text_frame(&ClientFrame::Resume { after })).await {
+        return Some(format!("couldn't resume the chat \n {}", e));
+    }
+    // deliveries queued while offline are part of this flush, they're skipped once sent
+    let pending = match shared
+        .database
+        .connection()
+        .and_then(|database| sqlite::pending_outbox(&database))
+    {
+        Ok(pending) => pending,
+        Err(e) => {
+            shared.local_error(e);
+            vec![]
+        }
+    };
+    for entry in pending {
+        if let Err(e) = deliver(&mut write, shared, entry).await {
+            return Some(e);
+        }
+    }
+    let status = *shared.presence.lock().unwrap();
+    if let Err(e) = send_frame(&mut write, &ClientFrame::Presence { status }).await {
+        return Some(e);
+    }
+    let usernames = shared.watched.lock().unwrap().clone();
+    if !usernames.is_empty() {
+        if let Err(e) = send_frame(&mut write, &ClientFrame::Subscribe { usernames }).await {
+            return Some(e);
+        }
+    }
+    shared.connected.store(true, Ordering::SeqCst);
+    let ended = exchange(&mut write, &mut read, shared, commands, shutdown).await;
+    shared.connected.store(false, Ordering::SeqCst);
+    ended
+}
+
+async fn exchange(
+    write: &mut SocketWriter,
+    read: &mut SplitStream<Socket>,
+    shared: &Shared,
+    commands: &mut UnboundedReceiver<Command>,
+    shutdown: &mut watch::Receiver<bool>,
+) -> Option<String> {
+    let mut heartbeat = interval(HEARTBEAT_INTERVAL);
+    let mut last_seen = Instant::now();
+    loop {
+        tokio::select! {
+            incoming = read.next() => {
+                last_seen = Instant::now();
+                let text = match incoming {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => {
+                        return Some("the server closed the chat connection".to_string())
+                    }
+                    Some(Ok(_)) => continue, // pings are answered by tungstenite, pongs only count as activity
+                    Some(Err(e)) => return Some(format!("the chat connection broke \n {}", e)),
+                };
+                let frame = match serde_json::from_str::<ServerFrame>(&text) {
+                    Ok(frame) => frame,
+                    Err(_) => continue, // unknown frames of newer servers are skipped
+                };
+                if let Err(e) = handle_frame(write, shared, frame).await {
+                    return Some(e);
+                }
+            }
+            command = commands.recv() => {
+                let sent = match command {
+                    Some(Command::Frame(frame)) => send_frame(write, &frame).await,
+                    Some(Command::Deliver(client_id)) => {
+                        let entry = match shared.database.connection().and_then(|database| sqlite::get_outbox(&database, &client_id)) {
+                            Ok(entry) => entry.filter(|entry| entry.state == OutboxState::Pending),
+                            Err(e) => {
+                                shared.local_error(e);
+                                None
+                            }
+                        };
+                        match entry {
+                            Some(entry) => deliver(write, shared, entry).await,
+                            None => Ok(()),
+                        }
+                    }
+                    None => {
+                        let _ = write.send(Message::Close(None)).await;
+                        return None; // every handle was dropped
+                    }
+                };
+                if let Err(e) = sent {
+                    return Some(e);
+                }
+            }
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > HEARTBEAT_TIMEOUT {
+                    return Some("the chat server stopped answering".to_string());
+                }
+                if let Err(e) = write.send(Message::Ping(vec![])).await {
+                    return Some(format!("the chat connection broke \n {}", e));
+                }
+            }
+            _ = shutdown.changed() => {
+                // frames queued right before the shutdown, like going offline, still go out
+                while let Ok(command) = commands.try_recv() {
+                    if let Command::Frame(frame) = command {
+                        let _ = send_frame(write, &frame).await;
+                    }
+                }
+                let _ = write.send(Message::Close(None)).await;
+                return None;
+            }
+        }
+    }
+}
+
+async fn handle_frame(
+    write: &mut SocketWriter,
+    shared: &Shared,
+    frame: ServerFrame,
+) -> Result<(), String> {
+    match frame {
+        ServerFrame::Message(message) => {
+            let id = message.id;
+            if let Err(e) = shared
+                .database
+                .connection()
+                .and_then(|database| sqlite::insert_message(&database, &message))
+            {
+                // left unacknowledged, the reconnection resumes before it and gets it again
+                shared.local_error(e);
+                return Err(format!(
+                    "couldn't keep the message {}, fetching it again",
+                    id
+                ));
+            }
+            let _ = shared.events.send(ConnectionEvent::Message(message));
+            // acknowledged once stored, a reconnection resumes after it
+            send_frame(write, &ClientFrame::Ack { id }).await?;
+            shared.last_acked.fetch_max(id, Ordering::SeqCst);
+            shared.save_last_acked();
+        }
+        ServerFrame::Accepted {
+            client_id,
+            id,
+            sent_at,
+        } => shared.accepted(&client_id, id, sent_at),
+        ServerFrame::Rejected { client_id, reason } => {
+            match shared
+                .database
+                .connection()
+                .and_then(|database| sqlite::get_outbox(&database, &client_id))
+            {
+                Ok(Some(entry)) => shared.fail(entry, reason),
+                Ok(None) => {}
+                Err(e) => shared.local_error(e),
+            }
+        }
+        ServerFrame::Receipt {
+            conversation_id,
+            up_to,
+            status,
+        } => shared.receipt(conversation_id, up_to, status),
+        ServerFrame::Presence(update) => {
+            let _ = shared.events.send(ConnectionEvent::Presence(update));
+        }
+        ServerFrame::Typing {
+            conversation_id,
+            username,
+        } => {
+            let _ = shared.events.send(ConnectionEvent::Typing {
+                conversation_id,
+                username,
+            });
+        }
+        ServerFrame::Retention(update) => shared.retention(update),
+        ServerFrame::Error { message } => {
+            let _ = shared.events.send(ConnectionEvent::ServerError { message });
+        }
+    }
+    Ok(())
+}
+
+// every connection counts as one attempt, the message stays pending until accepted
+async fn deliver(
+    write: &mut SocketWriter,
+    shared: &Shared,
+    mut entry: OutboxEntry,
+) -> Result<(), String> {
+    if entry.attempts >= MAX_SEND_ATTEMPTS {
+        let reason = format!(
+            "the server didn't accept the message after {} attempts",
+            MAX_SEND_ATTEMPTS
+        );
+        shared.fail(entry, reason);
+        return Ok(());
+    }
+    entry.attempts += 1;
+    entry.state = OutboxState::Sent;
+    if let Err(e) = shared
+        .database
+        .connection()
+        .and_then(|database| sqlite::update_outbox(&database, &entry))
+    {
+        shared.local_error(e);
+    }
+    let frame = ClientFrame::Send {
+        client_id: entry.client_id.clone(),
+        conversation_id: entry.conversation_id.clone(),
+        body: entry.body.clone(),
+    };
+    send_frame(write, &frame).await?;
+    let _ = shared.events.send(ConnectionEvent::Outbox(entry));
+    Ok(())
+}
+
+async fn send_frame(write: &mut SocketWriter, frame: &ClientFrame) -> Result<(), String> {
+    match write.send(text_frame(frame)).await {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("the chat connection broke \n {}", e)),
+    }
+}
+
+impl Shared {
+    // the stored message replaces its outbox entry
+    fn accepted(&self, client_id: &str, id: u64, sent_at: u64) {
+        let database = match self.database.connection() {
+            Ok(database) => database,
+            Err(e) => return self.local_error(e),
+        };
+        let entry = match sqlite::get_outbox(&database, client_id) {
+            Ok(Some(entry)) => entry,
+            Ok(None) => return, // accepted twice after a resend
+            Err(e) => return self.local_error(e),
+        };
+        let sender = match self.server.current_user() {
+            Some(user) => user.username,
+            None => return,
+        };
+        let message = ChatMessage {
+            id,
+            conversation_id: entry.conversation_id,
+            sender,
+            body: entry.body,
+            sent_at,
+            client_id: Some(entry.client_id),
+            status: MessageStatus::Stored,
+        };
+        if let Err(e) = sqlite::insert_message(&database, &message) {
+            return self.local_error(e);
+        }
+        if let Err(e) = sqlite::remove_outbox(&database, client_id) {
+            return self.local_error(e);
+        }
+        let _ = self.events.send(ConnectionEvent::Message(message));
+    }
+
+    // our messages of the conversation up to `up_to` reached the other side
+    fn receipt(&self, conversation_id: String, up_to: u64, status: MessageStatus) {
+        let sender = match self.server.current_user() {
+            Some(user) => user.username,
+            None => return,
+        };
+        let database = match self.database.connection() {
+            Ok(database) => database,
+            Err(e) => return self.local_error(e),
+        };
+        if let Err(e) = sqlite::apply_receipt(&database, &conversation_id, &sender, up_to, status) {
+            return self.local_error(e);
+        }
+        let _ = self.events.send(ConnectionEvent::Receipt {
+            conversation_id,
+            up_to,
+            status,
+        });
+    }
+
+    // the timer of a conversation changed, a change older than the one we have is dropped
+    fn retention(&self, mut update: RetentionUpdate) {
+        update.retention_secs = retention::at_least_minimum(update.retention_secs);
+        let applied = self
+            .database
+            .connection()
+            .and_then(|database| sqlite::apply_retention(&database, &update));
+        match applied {
+            Ok(true) => {
+                let _ = self.events.send(ConnectionEvent::Retention(update));
+            }
+            Ok(false) => {}
+            Err(e) => self.local_error(e),
+        }
+    }
+
+    fn fail(&self, mut entry: OutboxEntry, reason: String) {
+        entry.state = OutboxState::Failed;
+        entry.last_error = Some(reason);
+        if let Err(e) = self
+            .database
+            .connection()
+            .and_then(|database| sqlite::update_outbox(&database, &entry))
+        {
+            self.local_error(e);
+        }
+        let _ = self.events.send(ConnectionEvent::Outbox(entry));
+    }
+
+    // kept in the database so a restart doesn't fetch again what retention already erased
+    fn save_last_acked(&self) {
+        let last_acked = self.last_acked.load(Ordering::SeqCst).to_string();
+        if let Err(e) = self
+            .database
+            .connection()
+            .and_then(|database| sqlite::set_setting(&database, LAST_ACKED_SETTING, &last_acked))
+        {
+            self.local_error(e);
+        }
+    }
+
+    fn local_error(&self, message: String) {
+        let _ = self.events.send(ConnectionEvent::LocalError { message });
+    }
+}
+
+fn text_frame(frame: &ClientFrame) -> Message {
+    // client frames only hold strings and numbers, serializing them can't fail
+    Message::Text(serde_json::to_string(frame).unwrap_or_default())
+}
+
+// exponential backoff with up to a second of jitter so clients don't reconnect in lockstep
+fn reconnect_delay(attempt: u32) -> Duration {
+    let exponential = RECONNECT_BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt));
+    let jitter = Duration::from_millis(rand::thread_rng().gen_range(0..1000));
+    exponential.min(RECONNECT_MAX_DELAY) + jitter
+}
+
+pub mod structs {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct ChatMessage {
+        pub id: u64, // server side ordering id
+        pub conversation_id: String,
+        pub sender: String, // username
+        pub body: String,
+        pub sent_at: u64, // unix seconds
+        // set on our own messages, links them to their outbox entry
+        #[serde(default)]
+        pub client_id: Option<String>,
+        // how far our own message got, received messages are Read once shown
+        #[serde(default)]
+        pub status: MessageStatus,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum PresenceStatus {
+        Online,
+        Away,
+        Offline,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct PresenceUpdate {
+        pub username: String,
+        pub status: PresenceStatus,
+        pub last_seen: Option<u64>, // unix seconds, when the user was last online
+    }
+
+    // a message can only move forward through these, in this order
+    #[derive(
+        Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default,
+    )]
+    #[serde(rename_all = "snake_case")]
+    pub enum MessageStatus {
+        // delivered to the server
+        #[default]
+        Stored,
+        // delivered to a device of the recipient
+        Delivered,
+        Read,
+    }
+
+    impl MessageStatus {
+        pub fn rank(&self) -> i64 {
+            *self as i64
+        }
+
+        pub fn from_rank(rank: i64) -> MessageStatus {
+            match rank {
+                2 => MessageStatus::Read,
+                1 => MessageStatus::Delivered,
+                _ => MessageStatus::Stored,
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    pub enum ClientFrame {
+        // first frame of every connection, the server replays what came after `after`
+        Resume {
+            after: Option<u64>,
+        },
+        Ack {
+            id: u64,
+        },
+        Send {
+            client_id: String,
+            conversation_id: String,
+            body: String,
+        },
+        // read receipt for the messages of the conversation up to `up_to`
+        Read {
+            conversation_id: String,
+            up_to: u64,
+        },
+        Presence {
+            status: PresenceStatus,
+        },
+        // follow the presence of these users, replaces the previous list
+        Subscribe {
+            usernames: Vec<String>,
+        },
+        Typing {
+            conversation_id: String,
+        },
+        // the disappearing messages timer of the conversation, for both participants
+        Retention {
+            conversation_id: String,
+            retention_secs: Option<u64>,
+        },
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    pub enum ServerFrame {
+        Message(ChatMessage),
+        // our message `client_id` was stored as `id`, sent again it would be dropped
+        Accepted {
+            client_id: String,
+            id: u64,
+            sent_at: u64,
+        },
+        // the server won't take the message, sending it again won't help
+        Rejected {
+            client_id: String,
+            reason: String,
+        },
+        // the recipient got (its client acked) or read our messages up to `up_to`
+        Receipt {
+            conversation_id: String,
+            up_to: u64,
+            status: MessageStatus,
+        },
+        Presence(PresenceUpdate),
+        Typing {
+            conversation_id: String,
+            username: String,
+        },
+        // sent to both participants when the timer changes, and on every resume
+        Retention(RetentionUpdate),
+        Error {
+            message: String,
+        },
+    }
+
+    // the messages of the conversation disappear `retention_secs` after they were sent
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct RetentionUpdate {
+        pub conversation_id: String,
+        // None keeps the messages
+        pub retention_secs: Option<u64>,
+        pub set_by: String,
+        pub set_at: u64, // unix seconds, given by the server
+    }
+
+    // what the connection task reports to the app
+    #[derive(Serialize, Debug, Clone)]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    pub enum ConnectionEvent {
+        Connected,
+        Disconnected {
+            reason: String,
+            retry_in_secs: u64,
+        },
+        Message(ChatMessage),
+        // an outbox entry changed state
+        Outbox(OutboxEntry),
+        Receipt {
+            conversation_id: String,
+            up_to: u64,
+            status: MessageStatus,
+        },
+        Presence(PresenceUpdate),
+        Typing {
+            conversation_id: String,
+            username: String,
+        },
+        Retention(RetentionUpdate),
+        ServerError {
+            message: String,
+        },
+        // the local database failed
+        LocalError {
+            message: String,
+        },
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum OutboxState {
+        // waiting for a connection
+        Pending,
+        // written to the server, not accepted yet
+        Sent,
+        Failed,
+    }
+
+    impl OutboxState {
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                OutboxState::Pending => "pending",
+                OutboxState::Sent => "sent",
+                OutboxState::Failed => "failed",
+            }
+        }
+
+        // unknown values are retried
+        pub fn parse(value: &str) -> OutboxState {
+            match value {
+                "sent" => OutboxState::Sent,
+                "failed" => OutboxState::Failed,
+                _ => OutboxState::Pending,
+            }
+        }
+    }
+
+    // a message written locally that the server didn't accept yet
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct OutboxEntry {
+        pub client_id: String,
+        pub conversation_id: String,
+        pub body: String,
+        pub created_at: u64,
+        pub attempts: u32,
+        pub state: OutboxState,
+        pub last_error: Option<String>,
+    }
+}
@@ -0,0 +1,148 @@
+// Profiles keep separate identities on one machine: the keystore, the session, the database
+// and the attachments of each one live in a directory of their own, the files keep their names
+// in it. The default profile is the working directory, where everything was kept before
+use crate::solana_wallet::KEYPAIR_FILE;
+use std::fs;
+use std::path::Path;
+use std::sync::RwLock;
+use structs::ProfileInfo;
+
+pub const DEFAULT_PROFILE: &str = "default";
+// the profile to open on launch, `--profile <name>` or `--profile=<name>`
+pub const PROFILE_FLAG: &str = "--profile";
+const PROFILES_DIR: &str = "profiles";
+const MAX_NAME_LENGTH: usize = 32;
+
+// the directory of the open profile, empty for the default one
+static DATA_DIR: RwLock<String> = RwLock::new(String::new());
+
+// where `file` is kept for the open profile
+pub fn data_path(file: &str) -> String {
+    profile_path(&DATA_DIR.read().unwrap(), file)
+}
+
+// EH
+// the files of the app are read and written in the directory of `name` from now on,
+// it's created for a new profile
+pub fn open_profile(name: &str) -> Result<(), String> {
+    open_profile_in("", name)
+}
+
+// EH
+// `open_profile` with the profiles kept under `root` instead of the working directory
+pub fn open_profile_in(root: &str, name: &str) -> Result<(), String> {
+    check_profile_name(name)?;
+    let directory = profile_dir(root, name);
+    if !directory.is_empty() {
+        if let Err(e) = fs::create_dir_all(&directory) {
+            return Err(format!("couldn't create the profile {} \n {}", name, e));
+        }
+    }
+    *DATA_DIR.write().unwrap() = directory;
+    Ok(())
+}
+
+// EH
+// the default profile first, then the others by name
+pub fn list_profiles() -> Result<Vec<ProfileInfo>, String> {
+    list_profiles_in("")
+}
+
+// EH
+pub fn list_profiles_in(root: &str) -> Result<Vec<ProfileInfo>, String> {
+    let mut names = vec![];
+    match fs::read_dir(profile_path(root, PROFILES_DIR)) {
+        Ok(entries) => {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if entry.path().is_dir() && check_profile_name(&name).is_ok() {
+                    names.push(name);
+                }
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {} // no profile created yet
+        Err(e) => return Err(format!("couldn't list the profiles \n {}", e)),
+    }
+    names.retain(|name| name != DEFAULT_PROFILE);
+    names.sort();
+    names.insert(0, DEFAULT_PROFILE.to_string());
+    Ok(names
+        .into_iter()
+        .map(|name| ProfileInfo {
+            has_wallet: Path::new(&profile_path(&profile_dir(root, &name), KEYPAIR_FILE)).is_file(),
+            name,
+        })
+        .collect())
+}
+
+// EH
+// the profile asked for on the command line, Err when the flag has no name after it
+pub fn profile_from_args(args: &[String]) -> Result<Option<String>, String> {
+    let flag_with_value = format!("{}=", PROFILE_FLAG);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let name = if arg == PROFILE_FLAG {
+            match args.next() {
+                Some(name) => name.clone(),
+                None => return Err(format!("{} needs the name of a profile", PROFILE_FLAG)),
+            }
+        } else if let Some(name) = arg.strip_prefix(&flag_with_value) {
+            name.to_string()
+        } else {
+            continue;
+        };
+        check_profile_name(&name)?;
+        return Ok(Some(name));
+    }
+    Ok(None)
+}
+
+// EH
+// the name is a directory name, it's kept to what's safe in a path on every system
+pub fn check_profile_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        return Err(format!(
+            "a profile name has 1 to {} characters",
+            MAX_NAME_LENGTH
+        ));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err("a profile name can only have letters, digits, - and _".to_string());
+    }
+    Ok(())
+}
+
+// `root` itself for the default profile, empty for the working directory
+fn profile_dir(root: &str, name: &str) -> String {
+    if name == DEFAULT_PROFILE {
+        return root.to_string();
+    }
+    Path::new(&profile_path(root, PROFILES_DIR))
+        .join(name)
+        .to_string_lossy()
+        .to_string()
+}
+
+fn profile_path(directory: &str, file: &str) -> String {
+    if directory.is_empty() {
+        return file.to_string();
+    }
+    Path::new(directory)
+        .join(file)
+        .to_string_lossy()
+        .to_string()
+}
+
+pub mod structs {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct ProfileInfo {
+        pub name: String,
+        // false until an account is created in it
+        pub has_wallet: bool,
+    }
+}
@@ -0,0 +1,144 @@
+// Disappearing messages and the cap on the size of the history.
+// The timer of a conversation comes from the server, so both sides delete the same messages.
+// The cap is a setting of this device, the oldest messages go first once it's reached.
+// Nothing deleted stays readable: the database overwrites freed pages, the index drops the
+// words of the messages instead of marking them deleted, and the attachment files are zeroed
+// before they're removed
+use crate::sqlite::{self, structs::StoredMessage};
+use rusqlite::Connection;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use structs::{ExpiredMessages, PurgeOutput};
+
+// bytes of message bodies and attachments kept at most, no cap when missing
+pub const STORAGE_CAP_SETTING: &str = "storage_cap_bytes";
+// the shortest timer a conversation can be given
+pub const MIN_RETENTION_SECS: u64 = 60 * 60;
+
+// a shorter timer from the server or a peer is raised to the minimum instead of wiping the
+// conversation at the next purge
+pub fn at_least_minimum(retention_secs: Option<u64>) -> Option<u64> {
+    retention_secs.map(|secs| secs.max(MIN_RETENTION_SECS))
+}
+
+// EH
+// deletes what expired at `now` and what's over the cap, grouped by conversation.
+// The rows are gone once an attachment fails to be erased, so every file is still tried and
+// the failures come back with the deleted messages
+pub fn purge(connection: &mut Connection, now: u64) -> Result<PurgeOutput, String> {
+    let mut doomed = sqlite::expired_messages(connection, now)?;
+    if let Some(cap) = storage_cap(connection)? {
+        let over = over_cap(connection, cap, &doomed)?;
+        doomed.extend(over);
+    }
+    if doomed.is_empty() {
+        return Ok(PurgeOutput::default());
+    }
+
+    let ids: Vec<u64> = doomed.iter().map(|message| message.id).collect();
+    let paths = sqlite::delete_messages(connection, &ids)?;
+    let mut failures: Vec<String> = paths
+        .iter()
+        .filter_map(|path| shred_file(path).err())
+        .collect();
+    if let Err(e) = sqlite::flush_deleted(connection) {
+        failures.push(e);
+    }
+
+    let mut expired: Vec<ExpiredMessages> = vec![];
+    for message in doomed {
+        match expired
+            .iter_mut()
+            .find(|group| group.conversation_id == message.conversation_id)
+        {
+            Some(group) => group.ids.push(message.id),
+            None => expired.push(ExpiredMessages {
+                conversation_id: message.conversation_id,
+                ids: vec![message.id],
+            }),
+        }
+    }
+    Ok(PurgeOutput { expired, failures })
+}
+
+// EH
+pub fn storage_cap(connection: &Connection) -> Result<Option<u64>, String> {
+    let value = sqlite::get_setting(connection, STORAGE_CAP_SETTING)?;
+    Ok(value.and_then(|value| value.parse().ok()))
+}
+
+// EH
+// None lifts the cap, the next purge applies a new one
+pub fn set_storage_cap(connection: &Connection, cap: Option<u64>) -> Result<(), String> {
+    let value = match cap {
+        Some(cap) => cap.to_string(),
+        None => "none".to_string(),
+    };
+    sqlite::set_setting(connection, STORAGE_CAP_SETTING, &value)
+}
+
+// the oldest messages to delete for the rest to fit in `cap`, besides the ones already going
+fn over_cap(
+    connection: &Connection,
+    cap: u64,
+    going: &[StoredMessage],
+) -> Result<Vec<StoredMessage>, String> {
+    let kept: Vec<StoredMessage> = sqlite::stored_messages(connection)?
+        .into_iter()
+        .filter(|message| !going.iter().any(|gone| gone.id == message.id))
+        .collect();
+    let mut total: u64 = kept.iter().map(|message| message.size).sum();
+    let mut over = vec![];
+    for message in kept {
+        if total <= cap {
+            break;
+        }
+        total -= message.size;
+        over.push(message);
+    }
+    Ok(over)
+}
+
+// overwrites the file with zeros before removing it, a file already gone is fine
+fn shred_file(path: &str) -> Result<(), String> {
+    if !Path::new(path).exists() {
+        return Ok(());
+    }
+    let erased = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .and_then(|mut file| {
+            let length = file.metadata()?.len();
+            let zeros = vec![0u8; 64 * 1024];
+            let mut written = 0;
+            while written < length {
+                let chunk = (length - written).min(zeros.len() as u64) as usize;
+                file.write_all(&zeros[..chunk])?;
+                written += chunk as u64;
+            }
+            file.sync_all()
+        });
+    match erased.and_then(|_| fs::remove_file(path)) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't erase the attachment {} \n {}", path, e)),
+    }
+}
+
+pub mod structs {
+    use serde::{Deserialize, Serialize};
+
+    // messages of a conversation deleted by its timer or the cap
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct ExpiredMessages {
+        pub conversation_id: String,
+        pub ids: Vec<u64>,
+    }
+
+    #[derive(Debug, Default)]
+    pub struct PurgeOutput {
+        pub expired: Vec<ExpiredMessages>,
+        // what couldn't be erased after the messages were deleted, one error each
+        pub failures: Vec<String>,
+    }
+}
@@ -0,0 +1,698 @@
+use crate::keystore::{open_sealed, seal};
+use crate::profiles::data_path;
+use crate::solana_wallet::unix_now;
+use crate::structs::{
+    CreateTokenAccount, CreateTokenAccountResponse, FundWallet, SealedData, SecretString,
+};
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use solana_sdk::signer::{keypair::Keypair, Signer};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use structs::{
+    AddContactRequest, ChallengeRequest, Page, RefreshRequest, Session, SessionResponse,
+    SignInChallenge, SignInRequest, SignUpRequest, StoredSession, UserProfile, WalletSignInRequest,
+};
+use zeroize::Zeroizing;
+
+pub const DEFAULT_SERVER_URL: &str = "http://localhost:8000";
+// overrides the server url, handy to point the app at a staging or local server
+pub const SERVER_URL_ENV: &str = "CHATUZA_SERVER_URL";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const DEFAULT_MAX_RETRIES: u32 = 2;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+// the access token is refreshed when it expires in less than this
+const REFRESH_MARGIN_SECS: u64 = 60;
+const SESSION_FILE: &str = "session.json";
+const SEARCH_CACHE_TTL: Duration = Duration::from_secs(60);
+const SEARCH_CACHE_CAPACITY: usize = 100;
+pub const SEARCH_MIN_QUERY_LEN: usize = 2;
+pub const SEARCH_PAGE_SIZE: u32 = 20;
+
+type SearchCache = HashMap<(String, u32), (Instant, Page<UserProfile>)>;
+
+// Client of the Chatuza server http api.
+// Errors are returned as messages displayable to the user, like the rest of the crate
+#[derive(Clone, Debug)]
+pub struct ChatuzaServerClient {
+    base_url: String,
+    http: Client,
+    max_retries: u32,
+    // shared by the clones so a refresh done by one request serves the others
+    session: Arc<Mutex<Option<Session>>>,
+    // recent search pages by (query, page), typing back and forth doesn't hit the server
+    search_cache: Arc<Mutex<SearchCache>>,
+}
+
+impl ChatuzaServerClient {
+    // EH
+    pub fn new(base_url: &str) -> Result<Self, String> {
+        let http = match Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            // the client is shared by the short lived runtimes of the blocking wallet calls,
+            // pooled connections would die with the runtime that opened them
+            .pool_max_idle_per_host(0)
+            .build()
+        {
+            Ok(http) => http,
+            Err(e) => return Err(format!("couldn't build the http client due to {}", e)),
+        };
+        Ok(ChatuzaServerClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http,
+            max_retries: DEFAULT_MAX_RETRIES,
+            session: Arc::new(Mutex::new(None)),
+            search_cache: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    // EH
+    // uses CHATUZA_SERVER_URL when set, the local development server otherwise
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var(SERVER_URL_ENV) {
+            Ok(url) if !url.trim().is_empty() => Self::new(url.trim()),
+            _ => Self::new(DEFAULT_SERVER_URL),
+        }
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    // EH
+    pub async fn create_token_account(
+        &self,
+        request: &CreateTokenAccount,
+    ) -> Result<CreateTokenAccountResponse, String> {
+        self.post_form_once("/api/create-token-account", request)
+            .await
+    }
+
+    // EH
+    // returns the signature of the funding transaction
+    pub async fn fund_wallet(&self, request: &FundWallet) -> Result<String, String> {
+        self.post_form_once("/api/fund-wallet", request).await
+    }
+
+    // Accounts
+
+    // EH
+    pub async fn sign_up(&self, request: &SignUpRequest) -> Result<UserProfile, String> {
+        let response: SessionResponse = self.post_form_once("/api/auth/sign-up", request).await?;
+        Ok(self.start_session(response))
+    }
+
+    // EH
+    pub async fn sign_in(&self, request: &SignInRequest) -> Result<UserProfile, String> {
+        let response: SessionResponse = self.post_form("/api/auth/sign-in", request).await?;
+        Ok(self.start_session(response))
+    }
+
+    // Sign-In With Solana, no password: the wallet key signs a one time challenge of the server
+
+    // EH
+    pub async fn request_sign_in_challenge(
+        &self,
+        wallet_pubkey: &str,
+    ) -> Result<SignInChallenge, String> {
+        let request = ChallengeRequest {
+            wallet_pubkey: wallet_pubkey.to_string(),
+        };
+        self.post_form("/api/auth/challenge", &request).await
+    }
+
+    // EH
+    // the exact text to sign, refused when the challenge is expired or issued for another server
+    pub fn wallet_sign_in_message(
+        &self,
+        challenge: &SignInChallenge,
+        wallet_pubkey: &str,
+    ) -> Result<String, String> {
+        if challenge.domain != self.domain() {
+            return Err(format!(
+                "the sign in challenge was issued for {} instead of {}, refusing to sign it",
+                challenge.domain,
+                self.domain()
+            ));
+        }
+        if challenge.expires_at <= unix_now() {
+            return Err("the sign in challenge expired, try again".to_string());
+        }
+        Ok(format!(
+            "{} wants you to sign in with your Solana account:\n{}\n\n{}\n\nURI: {}\nVersion: 1\nNonce: {}\nIssued At: {}\nExpiration Time: {}",
+            challenge.domain,
+            wallet_pubkey,
+            challenge.statement,
+            self.base_url,
+            challenge.nonce,
+            challenge.issued_at,
+            challenge.expires_at
+        ))
+    }
+
+    // EH
+    // `signature` is the base58 ed25519 signature of the message by the wallet key
+    pub async fn complete_wallet_sign_in(
+        &self,
+        wallet_pubkey: &str,
+        message: &str,
+        signature: &str,
+    ) -> Result<UserProfile, String> {
+        let request = WalletSignInRequest {
+            wallet_pubkey: wallet_pubkey.to_string(),
+            message: message.to_string(),
+            signature: signature.to_string(),
+        };
+        // the challenge nonce is single use, a replay would be refused
+        let response: SessionResponse = self
+            .post_form_once("/api/auth/wallet-sign-in", &request)
+            .await?;
+        Ok(self.start_session(response))
+    }
+
+    // EH
+    pub async fn sign_in_with_wallet(&self, keypair: &Keypair) -> Result<UserProfile, String> {
+        let wallet_pubkey = keypair.pubkey().to_string();
+        let challenge = self.request_sign_in_challenge(&wallet_pubkey).await?;
+        let message = self.wallet_sign_in_message(&challenge, &wallet_pubkey)?;
+        let signature = keypair.sign_message(message.as_bytes());
+        self.complete_wallet_sign_in(&wallet_pubkey, &message, &signature.to_string())
+            .await
+    }
+
+    // EH
+    // the local session is dropped even when the server can't be told about it
+    pub async fn sign_out(&self) -> Result<(), String> {
+        let revoked: Result<Value, String> = self.post_form_authed("/api/auth/sign-out", &()).await;
+        self.set_session(None);
+        revoked.map(|_| ())
+    }
+
+    // EH
+    pub async fn fetch_profile(&self) -> Result<UserProfile, String> {
+        let profile: UserProfile = self.get_authed("/api/users/me", &[]).await?;
+        if let Some(session) = self.session.lock().unwrap().as_mut() {
+            session.user = profile.clone();
+        }
+        Ok(profile)
+    }
+
+    // Contacts
+
+    // EH
+    // matches usernames, display names and wallet addresses, `page` starts at 0
+    pub async fn search_users(&self, query: &str, page: u32) -> Result<Page<UserProfile>, String> {
+        let query = query.trim().to_lowercase();
+        if query.chars().count() < SEARCH_MIN_QUERY_LEN {
+            return Ok(Page::empty(page, SEARCH_PAGE_SIZE));
+        }
+        let cache_key = (query.clone(), page);
+        if let Some((fetched_at, cached)) = self.search_cache.lock().unwrap().get(&cache_key) {
+            if fetched_at.elapsed() < SEARCH_CACHE_TTL {
+                return Ok(cached.clone());
+            }
+        }
+
+        let params = [
+            ("q", query),
+            ("page", page.to_string()),
+            ("per_page", SEARCH_PAGE_SIZE.to_string()),
+        ];
+        let results: Page<UserProfile> = self.get_authed("/api/users/search", &params).await?;
+
+        let mut cache = self.search_cache.lock().unwrap();
+        cache.retain(|_, (fetched_at, _)| fetched_at.elapsed() < SEARCH_CACHE_TTL);
+        if cache.len() >= SEARCH_CACHE_CAPACITY {
+            let oldest = cache
+                .iter()
+                .min_by_key(|(_, (fetched_at, _))| *fetched_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                cache.remove(&oldest);
+            }
+        }
+        cache.insert(cache_key, (Instant::now(), results.clone()));
+        Ok(results)
+    }
+
+    // EH
+    pub async fn add_contact(&self, username: &str) -> Result<UserProfile, String> {
+        let request = AddContactRequest {
+            username: username.to_string(),
+        };
+        self.post_form_authed("/api/contacts", &request).await
+    }
+
+    // EH
+    pub async fn list_contacts(&self) -> Result<Vec<UserProfile>, String> {
+        self.get_authed("/api/contacts", &[]).await
+    }
+
+    pub fn current_user(&self) -> Option<UserProfile> {
+        self.session
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|session| session.user.clone())
+    }
+
+    // EH
+    // keeps the session across restarts, sealed with a key only the unlocked wallet can derive
+    pub fn save_session(&self, key: &[u8; 32]) -> Result<(), String> {
+        let stored = match self.session.lock().unwrap().as_ref() {
+            Some(session) => StoredSession {
+                user: session.user.clone(),
+                access_token: session.access_token.expose().to_string(),
+                refresh_token: session.refresh_token.expose().to_string(),
+                expires_at: session.expires_at,
+            },
+            None => {
+                let _ = fs::remove_file(data_path(SESSION_FILE));
+                return Ok(());
+            }
+        };
+        let plaintext = match serde_json::to_string(&stored) {
+            Ok(text) => Zeroizing::new(text),
+            Err(e) => return Err(format!("couldn't serialize the session \n {}", e)),
+        };
+        let (nonce, ciphertext, tag) = seal(key, plaintext.as_bytes());
+        let sealed_text = match serde_json::to_string(&SealedData {
+            nonce,
+            ciphertext,
+            tag,
+        }) {
+            Ok(text) => text,
+            Err(e) => return Err(format!("couldn't serialize the session \n {}", e)),
+        };
+        match fs::write(data_path(SESSION_FILE), sealed_text) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("error while saving the session \n {}", e)),
+        }
+    }
+
+    // EH
+    // Ok(None) when there is no saved session, a session of another wallet is discarded
+    pub fn load_session(&self, key: &[u8; 32]) -> Result<Option<UserProfile>, String> {
+        let sealed_text = match fs::read_to_string(data_path(SESSION_FILE)) {
+            Ok(text) => text,
+            Err(_) => return Ok(None),
+        };
+        let sealed: SealedData = match serde_json::from_str(&sealed_text) {
+            Ok(sealed) => sealed,
+            Err(e) => return Err(format!("the saved session is corrupted \n {}", e)),
+        };
+        let plaintext = match open_sealed(key, &sealed.nonce, &sealed.ciphertext, &sealed.tag) {
+            Some(plaintext) => plaintext,
+            None => {
+                let _ = fs::remove_file(data_path(SESSION_FILE));
+                return Ok(None);
+            }
+        };
+        let stored: StoredSession = match serde_json::from_slice(&plaintext) {
+            Ok(stored) => stored,
+            Err(e) => return Err(format!("the saved session is corrupted \n {}", e)),
+        };
+        let user = stored.user.clone();
+        self.set_session(Some(Session {
+            user: stored.user.clone(),
+            access_token: SecretString::new(stored.access_token.clone()),
+            refresh_token: SecretString::new(stored.refresh_token.clone()),
+            expires_at: stored.expires_at,
+        }));
+        Ok(Some(user))
+    }
+
+    fn start_session(&self, response: SessionResponse) -> UserProfile {
+        let user = response.user.clone();
+        self.set_session(Some(Session {
+            user: response.user,
+            access_token: SecretString::new(response.access_token),
+            refresh_token: SecretString::new(response.refresh_token),
+            expires_at: unix_now() + response.expires_in,
+        }));
+        user
+    }
+
+    // the search results of a user aren't shown to the next one, a refresh keeps them
+    fn set_session(&self, session: Option<Session>) {
+        let mut current = self.session.lock().unwrap();
+        let username = |session: &Option<Session>| {
+            session
+                .as_ref()
+                .map(|session| session.user.username.clone())
+        };
+        if session.is_none() || username(&current) != username(&session) {
+            self.search_cache.lock().unwrap().clear();
+        }
+        *current = session;
+    }
+
+    // EH
+    // a valid access token, refreshed first when it's about to expire
+    pub(crate) async fn access_token(&self) -> Result<String, String> {
+        let (access_token, refresh_token, expires_at) = match self.session.lock().unwrap().as_ref()
+        {
+            Some(session) => (
+                session.access_token.expose().to_string(),
+                session.refresh_token.expose().to_string(),
+                session.expires_at,
+            ),
+            None => return Err("you are not signed in".to_string()),
+        };
+        if expires_at > unix_now() + REFRESH_MARGIN_SECS {
+            return Ok(access_token);
+        }
+        let path = "/api/auth/refresh";
+        let url = self.url(path);
+        let request = RefreshRequest { refresh_token };
+        let (status, text) = self
+            .send_raw(path, Replay::Once, || self.http.post(&url).form(&request))
+            .await?;
+        // refresh tokens rotate, so it's sent once, and only a refused token ends the
+        // session, an unreachable server keeps it
+        if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+            self.set_session(None);
+            return Err(format!("your session expired, sign in again \n {}", text));
+        }
+        let response: SessionResponse = parse_response(path, status, &text)?;
+        let access_token = response.access_token.clone();
+        self.start_session(response);
+        Ok(access_token)
+    }
+
+    async fn get_authed<R: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<R, String> {
+        let token = self.access_token().await?;
+        let url = self.url(path);
+        let (status, text) = self
+            .send_raw(path, Replay::Idempotent, || {
+                self.http.get(&url).bearer_auth(&token).query(query)
+            })
+            .await?;
+        self.parse_authed(path, status, &text)
+    }
+
+    async fn post_form_authed<B: Serialize, R: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<R, String> {
+        let token = self.access_token().await?;
+        let url = self.url(path);
+        let (status, text) = self
+            .send_raw(path, Replay::Idempotent, || {
+                self.http.post(&url).bearer_auth(&token).form(body)
+            })
+            .await?;
+        self.parse_authed(path, status, &text)
+    }
+
+    fn parse_authed<R: DeserializeOwned>(
+        &self,
+        path: &str,
+        status: StatusCode,
+        text: &str,
+    ) -> Result<R, String> {
+        if status == StatusCode::UNAUTHORIZED {
+            self.set_session(None);
+            return Err("your session expired, sign in again".to_string());
+        }
+        parse_response(path, status, text)
+    }
+
+    async fn post_form<B: Serialize, R: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<R, String> {
+        let url = self.url(path);
+        let (status, text) = self
+            .send_raw(path, Replay::Idempotent, || self.http.post(&url).form(body))
+            .await?;
+        parse_response(path, status, &text)
+    }
+
+    // for requests doing something twice when replayed, like funding a wallet
+    async fn post_form_once<B: Serialize, R: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<R, String> {
+        let url = self.url(path);
+        let (status, text) = self
+            .send_raw(path, Replay::Once, || self.http.post(&url).form(body))
+            .await?;
+        parse_response(path, status, &text)
+    }
+
+    // the single place requests are sent: retries and transport error mapping
+    async fn send_raw(
+        &self,
+        path: &str,
+        replay: Replay,
+        build: impl Fn() -> RequestBuilder,
+    ) -> Result<(StatusCode, String), String> {
+        let mut attempt: u32 = 0;
+        let response = loop {
+            match build().send().await {
+                // a gateway error may come after the server handled the request
+                Ok(res)
+                    if replay == Replay::Idempotent
+                        && is_retryable_status(res.status())
+                        && attempt < self.max_retries => {}
+                Ok(res) => break res,
+                // only failures where the server never got the request are safe to replay
+                Err(e) if e.is_connect() && attempt < self.max_retries => {}
+                Err(e) => return Err(map_request_error(path, e)),
+            }
+            tokio::time::sleep(RETRY_BASE_DELAY * 2u32.pow(attempt)).await;
+            attempt += 1;
+        };
+
+        let status = response.status();
+        match response.text().await {
+            Ok(text) => Ok((status, text)),
+            Err(e) => Err(format!("couldn't fetch the response text due to \n {}", e)),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    // the websocket address of `path`, wss when the api is served over https
+    pub(crate) fn ws_url(&self, path: &str) -> String {
+        match self.base_url.split_once("://") {
+            Some(("https", rest)) => format!("wss://{}{}", rest, path),
+            Some((_, rest)) => format!("ws://{}{}", rest, path),
+            None => format!("ws://{}{}", self.base_url, path),
+        }
+    }
+
+    // host[:port] of the server, what sign in challenges are bound to
+    fn domain(&self) -> String {
+        let without_scheme = match self.base_url.split_once("://") {
+            Some((_, rest)) => rest,
+            None => self.base_url.as_str(),
+        };
+        without_scheme
+            .split('/')
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    }
+}
+
+fn parse_response<R: DeserializeOwned>(
+    path: &str,
+    status: StatusCode,
+    response_text: &str,
+) -> Result<R, String> {
+    if !status.is_success() {
+        return Err(format!(
+            "server returned {} for {} \n {}",
+            status, path, response_text
+        ));
+    }
+    // the server answers with a serialized rust Result, {"Ok": ..} or {"Err": ..}
+    match serde_json::from_str::<Result<R, Value>>(response_text) {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(Value::String(e))) => Err(e),
+        Ok(Err(e)) => Err(format!("server refused the request \n {}", e)),
+        Err(e) => Err(format!(
+            "failed to parse the response due to {} \n server returned {}",
+            e, response_text
+        )),
+    }
+}
+
+// whether a request can be sent again once the server may have received it
+#[derive(Clone, Copy, PartialEq)]
+enum Replay {
+    Idempotent,
+    Once,
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+fn map_request_error(path: &str, e: reqwest::Error) -> String {
+    if e.is_connect() {
+        format!(
+            "couldn't reach the chatuza server for {}, check your connection",
+            path
+        )
+    } else if e.is_timeout() {
+        format!("the chatuza server took too long to answer {}", path)
+    } else if e.is_builder() {
+        format!("couldn't build the {} request due to {}", path, e)
+    } else {
+        format!("sending request failed due to \n {}", e)
+    }
+}
+
+pub mod structs {
+    use crate::structs::SecretString;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct UserProfile {
+        pub username: String,
+        pub display_name: String,
+        pub wallet_pubkey: String,
+        pub avatar_url: Option<String>,
+    }
+
+    #[derive(Serialize)]
+    pub struct SignUpRequest {
+        pub username: String,
+        pub display_name: String,
+        pub wallet_pubkey: String,
+        pub avatar_url: Option<String>,
+        pub password: String,
+    }
+
+    // the password never ends up in a log
+    impl std::fmt::Debug for SignUpRequest {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("SignUpRequest")
+                .field("username", &self.username)
+                .field("display_name", &self.display_name)
+                .field("wallet_pubkey", &self.wallet_pubkey)
+                .field("avatar_url", &self.avatar_url)
+                .field("password", &"[REDACTED]")
+                .finish()
+        }
+    }
+
+    #[derive(Serialize)]
+    pub struct SignInRequest {
+        pub username: String,
+        pub password: String,
+    }
+
+    impl std::fmt::Debug for SignInRequest {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("SignInRequest")
+                .field("username", &self.username)
+                .field("password", &"[REDACTED]")
+                .finish()
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Page<T> {
+        pub items: Vec<T>,
+        pub page: u32,
+        pub per_page: u32,
+        pub total: u64,
+    }
+
+    impl<T> Page<T> {
+        pub fn empty(page: u32, per_page: u32) -> Self {
+            Page {
+                items: vec![],
+                page,
+                per_page,
+                total: 0,
+            }
+        }
+
+        pub fn has_more(&self) -> bool {
+            ((self.page as u64) + 1) * (self.per_page as u64) < self.total
+        }
+    }
+
+    #[derive(Serialize)]
+    pub struct AddContactRequest {
+        pub username: String,
+    }
+
+    #[derive(Serialize)]
+    pub struct ChallengeRequest {
+        pub wallet_pubkey: String,
+    }
+
+    #[derive(Deserialize, Debug, Clone)]
+    pub struct SignInChallenge {
+        pub domain: String,
+        pub statement: String,
+        pub nonce: String,
+        pub issued_at: u64,  // unix seconds
+        pub expires_at: u64, // unix seconds
+    }
+
+    #[derive(Serialize, Debug)]
+    pub struct WalletSignInRequest {
+        pub wallet_pubkey: String,
+        pub message: String,
+        pub signature: String, // base58
+    }
+
+    #[derive(Serialize)]
+    pub struct RefreshRequest {
+        pub refresh_token: String,
+    }
+
+    #[derive(Deserialize)]
+    pub struct SessionResponse {
+        pub user: UserProfile,
+        pub access_token: String,
+        pub refresh_token: String,
+        pub expires_in: u64, // seconds
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct Session {
+        pub user: UserProfile,
+        pub access_token: SecretString,
+        pub refresh_token: SecretString,
+        pub expires_at: u64, // unix seconds
+    }
+
+    // plaintext of session.json before sealing
+    #[derive(Serialize, Deserialize)]
+    pub(crate) struct StoredSession {
+        pub user: UserProfile,
+        pub access_token: String,
+        pub refresh_token: String,
+        pub expires_at: u64,
+    }
+}
@@ -0,0 +1,126 @@
+// Shamir secret sharing over GF(256), the same field SLIP-39 uses (x^8 + x^4 + x^3 + x + 1).
+// Every byte of the secret is the constant term of its own random polynomial of degree threshold - 1,
+// share `x` holds the evaluation of all those polynomials at `x`.
+use rand::Rng;
+
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product: u8 = 0;
+    while b != 0 {
+        if b & 1 == 1 {
+            product ^= a;
+        }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+// a^254 == a^-1 in GF(256), callers never pass 0
+fn gf_inv(a: u8) -> u8 {
+    let mut result: u8 = 1;
+    let mut base = a;
+    let mut exp: u8 = 254;
+    while exp != 0 {
+        if exp & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exp >>= 1;
+    }
+    result
+}
+
+// EH
+// returns `count` shares as (x, y bytes), x starting at 1
+pub fn split_secret(secret: &[u8], threshold: u8, count: u8) -> Result<Vec<(u8, Vec<u8>)>, String> {
+    // with a threshold of 1 every share is the secret itself
+    if threshold < 2 {
+        return Err(format!(
+            "at least 2 shares have to be needed to rebuild the secret, got {}",
+            threshold
+        ));
+    }
+    if threshold > count {
+        return Err(format!(
+            "the threshold can't be more than the number of shares, got {} of {}",
+            threshold, count
+        ));
+    }
+    let mut rng = rand::thread_rng();
+    let mut shares: Vec<(u8, Vec<u8>)> = (1..=count)
+        .map(|x| (x, Vec::with_capacity(secret.len())))
+        .collect();
+
+    for byte in secret {
+        let mut coefficients = vec![*byte];
+        for _ in 1..threshold {
+            coefficients.push(rng.gen());
+        }
+        for (x, y) in shares.iter_mut() {
+            // horner evaluation from the highest degree down
+            let mut value: u8 = 0;
+            for coefficient in coefficients.iter().rev() {
+                value = gf_mul(value, *x) ^ coefficient;
+            }
+            y.push(value);
+        }
+    }
+    Ok(shares)
+}
+
+// EH
+// lagrange interpolation at x = 0 from the first `threshold` shares, the shares past those
+// have to sit on the same polynomial, a share corrupted or taken from another split is refused
+pub fn combine_shares(shares: &[(u8, Vec<u8>)], threshold: u8) -> Result<Vec<u8>, String> {
+    let secret_len = match shares.first() {
+        Some((_, y)) => y.len(),
+        None => return Err("at least one share is needed to recover the secret".to_string()),
+    };
+    if threshold == 0 || shares.len() < threshold as usize {
+        return Err(format!(
+            "{} shares are needed to recover the secret, only {} given",
+            threshold,
+            shares.len()
+        ));
+    }
+    for (index, (x, y)) in shares.iter().enumerate() {
+        if *x == 0 {
+            return Err("share number 0 is invalid".to_string());
+        }
+        if y.len() != secret_len {
+            return Err(format!("share number {} has a different length", x));
+        }
+        if shares[..index].iter().any(|(other_x, _)| other_x == x) {
+            return Err(format!("share number {} was given twice", x));
+        }
+    }
+
+    let (needed, extra) = shares.split_at(threshold as usize);
+    for (x, y) in extra {
+        if interpolate(needed, *x) != *y {
+            return Err(format!("share number {} doesn't match the other shares", x));
+        }
+    }
+    Ok(interpolate(needed, 0))
+}
+
+// the value at `x` of the polynomials going through `shares`
+fn interpolate(shares: &[(u8, Vec<u8>)], x: u8) -> Vec<u8> {
+    let mut values = vec![0u8; shares[0].1.len()];
+    for (i, (xi, yi)) in shares.iter().enumerate() {
+        let mut basis: u8 = 1;
+        for (j, (xj, _)) in shares.iter().enumerate() {
+            if i != j {
+                basis = gf_mul(basis, gf_mul(x ^ xj, gf_inv(xj ^ xi)));
+            }
+        }
+        for (value, y) in values.iter_mut().zip(yi) {
+            *value ^= gf_mul(basis, *y);
+        }
+    }
+    values
+}
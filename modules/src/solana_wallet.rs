@@ -1,38 +1,57 @@
 extern crate crypto;
+use crate::keystore::{
+    change_keystore_password, derive_storage_key, read_keystore_pubkey, unlock_keystore,
+    write_keystore,
+};
+use crate::profiles::data_path;
+use crate::server_interaction::ChatuzaServerClient;
+use crate::shamir::{combine_shares, split_secret};
 use crate::structs::TransferResponse;
-use crate::structs::{MnemonicInput, NewAccountOutput};
+use crate::structs::{
+    ArchivedAccount, BackupAnswer, BackupQuiz, BackupStatus, Cluster, CreateTokenAccount,
+    FundWallet, FundingPath, FundingPlan, FundingStrategy, MnemonicInput, MnemonicShare,
+    NetworkSettings, NewAccountOutput, SecretString,
+};
 use bip39::Mnemonic;
 use rand::Rng;
-use reqwest::{Client, Request};
-use serde_json::Value;
 use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_request::TokenAccountsFilter;
 use solana_sdk::hash::Hash;
 use solana_sdk::{
+    native_token::LAMPORTS_PER_SOL,
     pubkey::Pubkey,
     signature::Signature,
-    signer::{keypair::Keypair, EncodableKey, SeedDerivable, Signer},
+    signer::{keypair::Keypair, SeedDerivable, Signer},
     transaction::Transaction,
 };
 use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use std::fs;
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+// in the directory of the open profile
+pub(crate) const KEYPAIR_FILE: &str = "sec.json";
+const BACKUP_FILE: &str = "backup.json";
+const ARCHIVE_DIR: &str = "archive";
+
+// deleted accounts stay restorable from the archive for this long
+pub const ARCHIVE_GRACE_PERIOD_SECS: u64 = 30 * 24 * 60 * 60;
+
+// transfers of at least this many tokens (without decimals) need a confirmed mnemonic backup
+pub const LARGE_TRANSFER_THRESHOLD: u64 = 100;
+
+// selects the solana cluster, devnet when unset
+pub const CLUSTER_ENV: &str = "CHATUZA_CLUSTER";
+
+// what the airdrop funding gives a new wallet, enough for its fees
+const AIRDROP_LAMPORTS: u64 = LAMPORTS_PER_SOL / 100;
 
 // Notice that because these functions are client side function we return the direct displayable messages to the user
 // EH
-pub fn add_existing_account<'a>(
-    mnemonic_in: &MnemonicInput,
-) -> Result<NewAccountOutput<'a>, String> {
-    // decrypting the
-    let mi: String = mnemonic_in.to_string();
-    let kp: Keypair;
-    match Keypair::from_seed(mi.as_bytes()) {
-        Ok(_kp) => kp = _kp,
-        Err(e) => {
-            return Err(format!(
-                "could't build the key pair from the seed due to \n {:?}",
-                e
-            ))
-        }
-    }
+pub fn add_existing_account(mnemonic_in: &MnemonicInput) -> Result<NewAccountOutput, String> {
+    let kp = keypair_from_mnemonic(mnemonic_in)?;
     let pk: Pubkey;
     match kp.try_pubkey() {
         Ok(_pk) => pk = _pk,
@@ -46,18 +65,33 @@ pub fn add_existing_account<'a>(
     Ok(NewAccountOutput {
         mnemonic: None,
         pub_key: pk.to_string(),
-        keypair: kp.to_base58_string(),
+        keypair: SecretString::new(kp.to_base58_string()),
     })
 }
 
-// Generate new account
-pub fn gen_new_account<'a>(regenerate: bool) -> Result<NewAccountOutput<'a>, String> {
-    if Path::new("sec.json").is_file() && !regenerate {
-        return Err(format!(
+// EH
+fn keypair_from_mnemonic(mnemonic_in: &MnemonicInput) -> Result<Keypair, String> {
+    // decrypting the
+    let mi: SecretString = mnemonic_in.phrase();
+    match Keypair::from_seed(mi.expose().as_bytes()) {
+        Ok(kp) => Ok(kp),
+        Err(e) => Err(format!(
+            "could't build the key pair from the seed due to \n {:?}",
+            e
+        )),
+    }
+}
+
+// Generate new account, the keypair is saved encrypted with `password`.
+// an existing account is never overwritten, it has to go through `replace_account`
+pub fn gen_new_account(password: &str) -> Result<NewAccountOutput, String> {
+    if Path::new(&data_path(KEYPAIR_FILE)).is_file() {
+        return Err(
             "you already have a account !
             if you want a new account delete the previous one
             ❌ DISCLAIMER : deleting an account can result to permanent loss of the account owned assets ❌"
-        ));
+                .to_string(),
+        );
     }
     // generating new mnemonic and its hash
     let backup_unattached: Mnemonic;
@@ -65,20 +99,9 @@ pub fn gen_new_account<'a>(regenerate: bool) -> Result<NewAccountOutput<'a>, Str
         Ok(mne) => backup_unattached = mne,
         Err(e) => return Err(format!("{}", e)),
     } // handled in the called function
-      // generating new key pair
-    let kp: Keypair;
-    match Keypair::from_seed(backup_unattached.to_string().as_bytes()) {
-        Ok(_kp) => kp = _kp,
-        Err(e) => {
-            return Err(format!(
-                "could't build the key pair from the seed due to \n {:?}",
-                e
-            ))
-        }
-    }
-    let mut mi_arr: [&'a str; 12] = Default::default();
-    let mi = backup_unattached.word_iter().collect::<Vec<&'_ str>>();
-    mi_arr.copy_from_slice(&mi);
+    let mnemonic_in = mnemonic_input_from(&backup_unattached)?;
+    // generating new key pair
+    let kp = keypair_from_mnemonic(&mnemonic_in)?;
 
     let pk: Pubkey;
     match kp.try_pubkey() {
@@ -91,61 +114,525 @@ pub fn gen_new_account<'a>(regenerate: bool) -> Result<NewAccountOutput<'a>, Str
         }
     }
     // saving keypair
-    if let Err(_) = kp.write_to_file("sec.json") {
-        return Err(format!("error while saving the key pair"));
-    }
+    write_keystore(&data_path(KEYPAIR_FILE), &kp, password)?;
+    // the fresh account starts as not backed up until the user passes the quiz
+    write_backup_status(&BackupStatus {
+        pub_key: pk.to_string(),
+        backed_up: false,
+    })?;
 
     Ok(NewAccountOutput {
-        mnemonic: Some(MnemonicInput { words: mi_arr }),
+        mnemonic: Some(mnemonic_in),
         pub_key: pk.to_string(),
-        keypair: kp.to_base58_string(),
+        keypair: SecretString::new(kp.to_base58_string()),
     })
 }
+
+// copies the words out of the bip39 type into owned strings that are wiped on drop
+fn mnemonic_input_from(mnemonic: &Mnemonic) -> Result<MnemonicInput, String> {
+    let words: Vec<String> = mnemonic.words().map(String::from).collect();
+    match words.try_into() {
+        Ok(words) => Ok(MnemonicInput { words }),
+        Err(words) => Err(format!(
+            "expected a 12 words recovery phrase, got {} words",
+            words.len()
+        )),
+    }
+}
 // EH
 fn get_mnemonic() -> Result<Mnemonic, String> {
     // GEN RAW
-    let mut entropy: [u8; 16] = [0; 16];
-    rand::thread_rng().fill(&mut entropy);
-    match Mnemonic::from_entropy(&entropy) {
+    let mut entropy: Zeroizing<[u8; 16]> = Zeroizing::new([0; 16]);
+    rand::thread_rng().fill(&mut entropy[..]);
+    match Mnemonic::from_entropy(&entropy[..]) {
         Ok(m) => Ok(m),
         Err(e) => return Err(format!("Error while generating the Mnemonic \n {:?}", e)),
     }
 }
 
+// Backup verification
+
+// EH
+// picks `count` distinct word positions (0 based) the user has to fill in from the noted mnemonic
+pub fn get_backup_quiz(count: usize) -> Result<BackupQuiz, String> {
+    if count == 0 || count > 12 {
+        return Err(format!(
+            "the backup quiz must ask between 1 and 12 words, {} requested",
+            count
+        ));
+    }
+    let mut positions = rand::seq::index::sample(&mut rand::thread_rng(), 12, count).into_vec();
+    positions.sort_unstable();
+    Ok(BackupQuiz { positions })
+}
+
+// EH
+// the mnemonic must be the one of the current account and the answers must fill exactly the
+// positions `quiz` asked, each with its word
+pub fn confirm_backup(
+    mnemonic_in: &MnemonicInput,
+    quiz: &BackupQuiz,
+    answers: &[BackupAnswer],
+) -> Result<(), String> {
+    let current_pk = check_phrase_owner(mnemonic_in)?;
+    check_quiz_answers(mnemonic_in, quiz, answers)?;
+    write_backup_status(&BackupStatus {
+        pub_key: current_pk.to_string(),
+        backed_up: true,
+    })
+}
+
+// EH
+// typing the whole phrase back proves it was noted, no quiz needed
+pub fn confirm_backup_with_phrase(mnemonic_in: &MnemonicInput) -> Result<(), String> {
+    let current_pk = check_phrase_owner(mnemonic_in)?;
+    write_backup_status(&BackupStatus {
+        pub_key: current_pk.to_string(),
+        backed_up: true,
+    })
+}
+
+// EH
+// an answer per asked position, none missing, none added, every word right
+pub fn check_quiz_answers(
+    mnemonic_in: &MnemonicInput,
+    quiz: &BackupQuiz,
+    answers: &[BackupAnswer],
+) -> Result<(), String> {
+    let mut answered: Vec<usize> = answers.iter().map(|answer| answer.position).collect();
+    answered.sort_unstable();
+    let mut asked = quiz.positions.clone();
+    asked.sort_unstable();
+    if asked.is_empty() || answered != asked {
+        return Err("answer every word the backup quiz asked, and only those".to_string());
+    }
+    for answer in answers {
+        match mnemonic_in.words.get(answer.position) {
+            Some(word) if word.eq_ignore_ascii_case(answer.word.trim()) => {}
+            Some(_) => {
+                return Err(format!(
+                    "word #{} is wrong, check your written recovery phrase and try again",
+                    answer.position + 1
+                ))
+            }
+            None => return Err(format!("there is no word #{}", answer.position + 1)),
+        }
+    }
+    Ok(())
+}
+
+// the current account, when the phrase is the one it was generated from
+fn check_phrase_owner(mnemonic_in: &MnemonicInput) -> Result<Pubkey, String> {
+    let current_pk = read_account_pubkey()?;
+    let quiz_pk = match keypair_from_mnemonic(mnemonic_in)?.try_pubkey() {
+        Ok(pk) => pk,
+        Err(e) => {
+            return Err(format!(
+                "could't build the addr from key pair derived from the seed due to \n {:?}",
+                e
+            ))
+        }
+    };
+    if quiz_pk != current_pk {
+        return Err("this recovery phrase doesn't belong to the current account".to_string());
+    }
+    Ok(current_pk)
+}
+
+// EH
+pub fn is_backed_up() -> Result<bool, String> {
+    let current_pk = read_account_pubkey()?;
+    let status_text = match fs::read_to_string(data_path(BACKUP_FILE)) {
+        Ok(text) => text,
+        Err(_) => return Ok(false), // no status recorded, never confirmed
+    };
+    match serde_json::from_str::<BackupStatus>(&status_text) {
+        Ok(status) => Ok(status.backed_up && status.pub_key == current_pk.to_string()),
+        Err(e) => Err(format!("the backup status file is corrupted \n {}", e)),
+    }
+}
+
+// the reminder displayed to the user until the backup is confirmed
+pub fn backup_reminder() -> Option<String> {
+    match is_backed_up() {
+        Ok(true) => None,
+        _ => Some(format!(
+            "⚠️ your recovery phrase is not confirmed yet !
+            transfers of {} tokens or more are blocked until you pass the backup quiz",
+            LARGE_TRANSFER_THRESHOLD
+        )),
+    }
+}
+
+// Social recovery
+
+// EH
+// splits the mnemonic entropy into `count` shares, any `threshold` of them rebuild the account
+pub fn split_mnemonic(
+    mnemonic_in: &MnemonicInput,
+    threshold: u8,
+    count: u8,
+) -> Result<Vec<MnemonicShare>, String> {
+    let mnemonic = match Mnemonic::parse(mnemonic_in.phrase().expose()) {
+        Ok(m) => m,
+        Err(e) => return Err(format!("the recovery phrase is invalid \n {:?}", e)),
+    };
+    let set_id: u16 = rand::thread_rng().gen();
+    let entropy = Zeroizing::new(mnemonic.to_entropy());
+    let shares = split_secret(&entropy, threshold, count)?;
+
+    let mut mnemonic_shares: Vec<MnemonicShare> = Vec::with_capacity(shares.len());
+    for (index, share) in shares {
+        // 16 bytes of share data fit a 12 words phrase just like the original entropy
+        let share_mnemonic = match Mnemonic::from_entropy(&share) {
+            Ok(m) => m,
+            Err(e) => return Err(format!("Error while encoding the share \n {:?}", e)),
+        };
+        mnemonic_shares.push(MnemonicShare {
+            set_id,
+            threshold,
+            index,
+            words: share_mnemonic.to_string(),
+        });
+    }
+    Ok(mnemonic_shares)
+}
+
+// EH
+pub fn recover_from_shares(shares: &[MnemonicShare]) -> Result<NewAccountOutput, String> {
+    let first = match shares.first() {
+        Some(share) => share,
+        None => return Err("no shares were given".to_string()),
+    };
+    if shares
+        .iter()
+        .any(|share| share.set_id != first.set_id || share.threshold != first.threshold)
+    {
+        return Err("the given shares don't belong to the same backup".to_string());
+    }
+    if shares.len() < first.threshold as usize {
+        return Err(format!(
+            "{} shares are needed to recover the account, only {} given",
+            first.threshold,
+            shares.len()
+        ));
+    }
+
+    let mut raw_shares: Vec<(u8, Vec<u8>)> = Vec::with_capacity(shares.len());
+    for share in shares {
+        match Mnemonic::parse(share.words.as_str()) {
+            Ok(m) => raw_shares.push((share.index, m.to_entropy())),
+            Err(e) => {
+                return Err(format!(
+                    "share number {} is invalid \n {:?}",
+                    share.index, e
+                ))
+            }
+        }
+    }
+    let entropy = Zeroizing::new(combine_shares(&raw_shares, first.threshold)?);
+    let mnemonic = match Mnemonic::from_entropy(&entropy) {
+        Ok(m) => m,
+        Err(e) => return Err(format!("Error while rebuilding the Mnemonic \n {:?}", e)),
+    };
+
+    let mnemonic_in = mnemonic_input_from(&mnemonic)?;
+
+    let mut recovered = add_existing_account(&mnemonic_in)?;
+    recovered.mnemonic = Some(mnemonic_in); // the user has to note the rebuilt phrase again
+    Ok(recovered)
+}
+
+// EH
+// only the phrase of the current account is split, shares of a mistyped phrase would rebuild nothing
+pub fn split_account_mnemonic(
+    mnemonic_in: &MnemonicInput,
+    threshold: u8,
+    count: u8,
+) -> Result<Vec<MnemonicShare>, String> {
+    check_phrase_owner(mnemonic_in)?;
+    split_mnemonic(mnemonic_in, threshold, count)
+}
+
+// EH
+// rebuilds the account on a device without one and saves it under `password`
+pub fn restore_from_shares(
+    shares: &[MnemonicShare],
+    password: &str,
+) -> Result<NewAccountOutput, String> {
+    if Path::new(&data_path(KEYPAIR_FILE)).is_file() {
+        return Err(
+            "you already have a account ! delete it before recovering another one".to_string(),
+        );
+    }
+    let recovered = recover_from_shares(shares)?;
+    let kp = Keypair::from_base58_string(recovered.keypair.expose());
+    write_keystore(&data_path(KEYPAIR_FILE), &kp, password)?;
+    // the rebuilt phrase is a new backup to write down, so it starts as not backed up
+    write_backup_status(&BackupStatus {
+        pub_key: recovered.pub_key.clone(),
+        backed_up: false,
+    })?;
+    Ok(recovered)
+}
+
+fn write_backup_status(status: &BackupStatus) -> Result<(), String> {
+    let status_text = match serde_json::to_string(status) {
+        Ok(text) => text,
+        Err(e) => return Err(format!("couldn't serialize the backup status \n {}", e)),
+    };
+    match fs::write(data_path(BACKUP_FILE), status_text) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("error while saving the backup status \n {}", e)),
+    }
+}
+
+// Account deletion
+
+// EH
+// `confirmation` must be the address of the account, typed by the user.
+// the account must be empty, its keystore is moved to the archive and can be restored during the grace period
+pub fn delete_account(password: &str, confirmation: &str) -> Result<ArchivedAccount, String> {
+    let pk = read_account_pubkey()?;
+    if confirmation.trim() != pk.to_string() {
+        return Err("type the address of the account to confirm its deletion".to_string());
+    }
+    unlock_wallet(password)?; // only the owner can delete the account
+    check_account_is_empty(&pk)?;
+
+    if let Err(e) = fs::create_dir_all(data_path(ARCHIVE_DIR)) {
+        return Err(format!("couldn't create the account archive \n {}", e));
+    }
+    let archived_at = unix_now();
+    let archived = ArchivedAccount {
+        pub_key: pk.to_string(),
+        archived_at,
+        restorable_until: archived_at + ARCHIVE_GRACE_PERIOD_SECS,
+    };
+    // the keystore is already sealed with the password, it's archived as is
+    if let Err(e) = fs::rename(data_path(KEYPAIR_FILE), archive_path(&archived)) {
+        return Err(format!("couldn't archive the account \n {}", e));
+    }
+    // with its backup status, a restored account doesn't have to be backed up again
+    if Path::new(&data_path(BACKUP_FILE)).is_file() {
+        if let Err(e) = fs::rename(data_path(BACKUP_FILE), archive_backup_path(&archived)) {
+            return Err(format!("couldn't archive the backup status \n {}", e));
+        }
+    }
+    Ok(archived)
+}
+
+// EH
+// archives the current account then generates a new one with the same password
+pub fn replace_account(password: &str, confirmation: &str) -> Result<NewAccountOutput, String> {
+    delete_account(password, confirmation)?;
+    gen_new_account(password)
+}
+
+// EH
+// lists the restorable accounts, the ones past their grace period are purged on the way
+pub fn list_archived_accounts() -> Result<Vec<ArchivedAccount>, String> {
+    let entries = match fs::read_dir(data_path(ARCHIVE_DIR)) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(vec![]), // nothing was ever archived
+    };
+    let now = unix_now();
+    let mut archived_accounts: Vec<ArchivedAccount> = vec![];
+    for entry in entries.flatten() {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let archived = match parse_archive_name(&file_name) {
+            Some(archived) => archived,
+            None => continue,
+        };
+        if archived.restorable_until <= now {
+            if let Err(e) = fs::remove_file(entry.path()) {
+                return Err(format!(
+                    "couldn't purge the expired archive {} \n {}",
+                    file_name, e
+                ));
+            }
+            let _ = fs::remove_file(archive_backup_path(&archived));
+            continue;
+        }
+        archived_accounts.push(archived);
+    }
+    archived_accounts.sort_by_key(|archived| std::cmp::Reverse(archived.archived_at));
+    Ok(archived_accounts)
+}
+
+// EH
+// brings back the latest archive of `pub_key`, only possible when there is no current account
+pub fn restore_archived_account(pub_key: &str, password: &str) -> Result<(), String> {
+    if Path::new(&data_path(KEYPAIR_FILE)).is_file() {
+        return Err("delete the current account before restoring an archived one".to_string());
+    }
+    let archived = match list_archived_accounts()?
+        .into_iter()
+        .find(|archived| archived.pub_key == pub_key)
+    {
+        Some(archived) => archived,
+        None => {
+            return Err(format!(
+                "there is no restorable archive for {}, the grace period may be over",
+                pub_key
+            ))
+        }
+    };
+    let path = archive_path(&archived);
+    unlock_keystore(&path, password)?;
+    if let Err(e) = fs::rename(&path, data_path(KEYPAIR_FILE)) {
+        return Err(format!("couldn't restore the account \n {}", e));
+    }
+    // archives from before the backup status was kept have none, the account asks for a backup
+    let backup_path = archive_backup_path(&archived);
+    if Path::new(&backup_path).is_file() {
+        if let Err(e) = fs::rename(&backup_path, data_path(BACKUP_FILE)) {
+            return Err(format!("couldn't restore the backup status \n {}", e));
+        }
+    }
+    Ok(())
+}
+
+fn check_account_is_empty(pk: &Pubkey) -> Result<(), String> {
+    let rpc = RpcClient::new(Cluster::from_env().rpc_url().to_string());
+    match rpc.get_balance(pk) {
+        Ok(0) => {}
+        Ok(lamports) => {
+            return Err(format!(
+                "the account still holds {} lamports, transfer them before deleting it",
+                lamports
+            ))
+        }
+        Err(e) => return Err(format!("couldn't check the account balance \n {}", e)),
+    }
+    // the tokens can be held under either token program
+    let mut token_accounts = vec![];
+    for program_id in [spl_token::id(), spl_token_2022::id()] {
+        match rpc.get_token_accounts_by_owner(pk, TokenAccountsFilter::ProgramId(program_id)) {
+            Ok(accounts) => token_accounts.extend(accounts),
+            Err(e) => return Err(format!("couldn't check the token balances \n {}", e)),
+        }
+    }
+    for token_account in token_accounts {
+        let token_account_pk = match token_account.pubkey.parse::<Pubkey>() {
+            Ok(pk) => pk,
+            Err(e) => {
+                return Err(format!(
+                    "the rpc returned an invalid token account \n {}",
+                    e
+                ))
+            }
+        };
+        match rpc.get_token_account_balance(&token_account_pk) {
+            Ok(balance) if balance.amount == "0" => {}
+            Ok(balance) => {
+                return Err(format!(
+                    "the token account {} still holds {} tokens, transfer them before deleting the account",
+                    token_account_pk, balance.ui_amount_string
+                ))
+            }
+            Err(e) => return Err(format!("couldn't check the token balances \n {}", e)),
+        }
+    }
+    Ok(())
+}
+
+fn archive_path(archived: &ArchivedAccount) -> String {
+    format!(
+        "{}/{}-{}.json",
+        data_path(ARCHIVE_DIR),
+        archived.pub_key,
+        archived.archived_at
+    )
+}
+
+// next to the keystore, its name isn't read back as an archive by `parse_archive_name`
+fn archive_backup_path(archived: &ArchivedAccount) -> String {
+    format!(
+        "{}/{}-{}.backup",
+        data_path(ARCHIVE_DIR),
+        archived.pub_key,
+        archived.archived_at
+    )
+}
+
+fn parse_archive_name(file_name: &str) -> Option<ArchivedAccount> {
+    let (pub_key, archived_at) = file_name.strip_suffix(".json")?.rsplit_once('-')?;
+    let archived_at: u64 = archived_at.parse().ok()?;
+    Some(ArchivedAccount {
+        pub_key: pub_key.to_string(),
+        archived_at,
+        restorable_until: archived_at + ARCHIVE_GRACE_PERIOD_SECS,
+    })
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+// Keystore
+
+pub fn read_account_pubkey() -> Result<Pubkey, String> {
+    read_keystore_pubkey(&data_path(KEYPAIR_FILE))
+}
+
+// EH
+// the returned keypair is the only signing material, drop it to lock the wallet again
+pub fn unlock_wallet(password: &str) -> Result<Keypair, String> {
+    unlock_keystore(&data_path(KEYPAIR_FILE), password)
+}
+
+// a key to seal local data (session, database...) that only this unlocked wallet can derive
+pub fn storage_key(keypair: &Keypair, purpose: &str) -> Zeroizing<[u8; 32]> {
+    derive_storage_key(keypair, purpose)
+}
+
+// EH
+pub fn change_wallet_password(old_password: &str, new_password: &str) -> Result<(), String> {
+    change_keystore_password(&data_path(KEYPAIR_FILE), old_password, new_password)
+}
+
 //EH
+#[allow(clippy::too_many_arguments)]
 pub fn transfer_spl(
-    program_id: &Pubkey, // supporting various programs
+    server: &ChatuzaServerClient,
+    network: &NetworkSettings,
+    sender_keypair: &Keypair, // unlocked by the user password
+    program_id: &Pubkey,      // supporting various programs
     mint_pubkey: &Pubkey,
     recipient_wallet_pubkey: &Pubkey,
     amount: u64, // without decimals
     decimals: u8,
 ) -> Result<TransferResponse, String> {
-    let rpc = RpcClient::new("https://api.devnet.solana.com".to_string());
-    let lbh: Hash;
-    match rpc.get_latest_blockhash() {
-        Ok(hash) => lbh = hash,
+    let rpc = RpcClient::new(network.cluster.rpc_url().to_string());
+    let lbh: Hash = match rpc.get_latest_blockhash() {
+        Ok(hash) => hash,
         Err(e) => {
             return Err(format!(
                 "Error while etching the latest block hash \n {:?}",
                 e
             ))
         }
-    }
-    let sender_keypair: Keypair;
-    match Keypair::read_from_file("sec.json") {
-        Ok(_kp) => sender_keypair = _kp,
-        Err(e) => return Err(format!("Error while reading the signer info \n {:?}", e)),
+    };
+    if amount >= LARGE_TRANSFER_THRESHOLD && !is_backed_up()? {
+        return Err(format!(
+            "transfers of {} tokens or more need a confirmed recovery phrase backup,
+            pass the backup quiz first so you don't lose access to your assets",
+            LARGE_TRANSFER_THRESHOLD
+        ));
     }
     let sender_pubkey = sender_keypair.try_pubkey().unwrap(); // panic impossible
 
     // getting the token account of two side of the deal
     let sender_token_acc: Pubkey =
-        get_associated_token_address_with_program_id(&sender_pubkey, &mint_pubkey, &program_id);
-    println!("{}", sender_token_acc.to_string());
+        get_associated_token_address_with_program_id(&sender_pubkey, mint_pubkey, program_id);
     let recipient_token_acc: Pubkey = get_associated_token_address_with_program_id(
-        &recipient_wallet_pubkey,
-        &mint_pubkey,
-        &program_id,
+        recipient_wallet_pubkey,
+        mint_pubkey,
+        program_id,
     );
 
     if rpc.get_account(&sender_token_acc).is_err() {
@@ -155,28 +642,41 @@ pub fn transfer_spl(
         ));
     }
 
-    if let Err(_) = rpc.get_account(&recipient_wallet_pubkey) {
-        // wallet is unfunded, funding
-        if let Err(e) = fund_account(recipient_wallet_pubkey.to_string()) {
-            return Err(format!(
-                "failed to fund the recipient wallet due to \n {}",
-                e
-            ));
-        }
-    }; // reentrance impossible, second time account will be considered as active.
-
-    let mut create_recipient_acc_sigs: Vec<Option<String>> = vec![None, None];
+    let wallet_unfunded = rpc.get_account(recipient_wallet_pubkey).is_err();
+    let token_account_missing = rpc.get_account(&recipient_token_acc).is_err();
+    let plan = plan_funding(network, mint_pubkey, wallet_unfunded, token_account_missing)?;
+    let mut response = TransferResponse {
+        funding: plan.path,
+        funding_account_sig: None,
+        create_account_sigs: vec![],
+        transfer_sig: String::new(),
+    };
 
-    if let Err(_) = rpc.get_account(&recipient_token_acc) {
-        // account was not created, creating
-        let tmp_signatures: Vec<String>;
+    if plan.fund_wallet {
+        if network.funding == FundingStrategy::BackendFaucet {
+            match fund_account(server, recipient_wallet_pubkey.to_string()) {
+                Ok(sig) => response.funding_account_sig = Some(sig),
+                Err(e) => {
+                    return Err(format!(
+                        "failed to fund the recipient wallet due to \n {}",
+                        e
+                    ))
+                }
+            }
+        } else {
+            response.funding_account_sig =
+                Some(airdrop(&rpc, recipient_wallet_pubkey)?.to_string());
+        }
+    } // reentrance impossible, second time account will be considered as active.
+    if plan.backend_creates_account {
         match create_token_account(
+            server,
             recipient_wallet_pubkey.to_string(),
             mint_pubkey.to_string(),
             program_id.to_string(),
             lbh.to_string(),
         ) {
-            Ok(tmp) => tmp_signatures = tmp,
+            Ok(sigs) => response.create_account_sigs = sigs,
             Err(e) => {
                 return Err(format!(
                     "failed to create the associated token account due to \n {}",
@@ -184,217 +684,313 @@ pub fn transfer_spl(
                 ))
             }
         }
-        create_recipient_acc_sigs = vec![
-            Some(tmp_signatures[0].to_owned()),
-            Some(tmp_signatures[1].to_owned()),
-        ];
-    };
+    }
 
-    let transfer_signature: Signature;
-    // sending the transaction
+    let mut instructions = vec![];
+    if plan.sender_creates_account {
+        instructions.push(create_associated_token_account_idempotent(
+            &sender_pubkey,
+            recipient_wallet_pubkey,
+            mint_pubkey,
+            program_id,
+        ));
+    }
     match spl_token::instruction::transfer_checked(
-        &program_id,
+        program_id,
         &sender_token_acc,
-        &mint_pubkey,
+        mint_pubkey,
         &recipient_token_acc,
         &sender_pubkey,
         &[&sender_pubkey],
-        amount * (10 as u64).pow(9),
+        amount * 10_u64.pow(9),
         decimals,
     ) {
-        Ok(ix) => match rpc.send_and_confirm_transaction(&Transaction::new_signed_with_payer(
-            &[ix],
-            Some(&sender_pubkey),
-            &[&sender_keypair],
-            lbh,
-        )) {
-            Ok(sig) => transfer_signature = sig,
-            Err(e) => return Err(format!("transaction failed due to \n {}", e)),
-        },
+        Ok(ix) => instructions.push(ix),
         Err(e) => return Err(format!("failed to create the instruction due to \n {}", e)),
     };
 
-    Ok(TransferResponse {
-        create_account_sig: create_recipient_acc_sigs[0].to_owned(),
-        funding_account_sig: create_recipient_acc_sigs[1].to_owned(),
-        transfer_sig: transfer_signature.to_string(),
-    })
+    // sending the transaction
+    match rpc.send_and_confirm_transaction(&Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&sender_pubkey),
+        &[sender_keypair],
+        lbh,
+    )) {
+        Ok(sig) => response.transfer_sig = sig.to_string(),
+        Err(e) => return Err(format!("transaction failed due to \n {}", e)),
+    };
+    Ok(response)
+}
+
+// EH
+// what the transfer has to do for a recipient without a funded wallet or a token account,
+// anything but the backend creates the missing token account in the transfer itself
+pub fn plan_funding(
+    network: &NetworkSettings,
+    mint_pubkey: &Pubkey,
+    wallet_unfunded: bool,
+    token_account_missing: bool,
+) -> Result<FundingPlan, String> {
+    let mut plan = FundingPlan {
+        path: FundingPath::NotNeeded,
+        fund_wallet: false,
+        backend_creates_account: false,
+        sender_creates_account: false,
+    };
+    match network.funding {
+        FundingStrategy::BackendFaucet => {
+            plan.fund_wallet = wallet_unfunded;
+            plan.backend_creates_account = token_account_missing;
+            if wallet_unfunded || token_account_missing {
+                plan.path = FundingPath::BackendFaucet;
+            }
+        }
+        FundingStrategy::Airdrop => {
+            plan.fund_wallet = wallet_unfunded;
+            plan.sender_creates_account = token_account_missing;
+            if wallet_unfunded {
+                plan.path = FundingPath::Airdrop;
+            } else if token_account_missing {
+                plan.path = FundingPath::SenderPaidRent;
+            }
+        }
+        FundingStrategy::SenderPaysRent => {
+            plan.sender_creates_account = token_account_missing;
+            if token_account_missing {
+                plan.path = FundingPath::SenderPaidRent;
+            }
+        }
+        FundingStrategy::Disabled => {
+            if token_account_missing {
+                return Err(format!(
+                    "the recipient has no account for the {} token yet and funding it is disabled on {}, \
+                    they have to create it first or you can pay its rent yourself",
+                    mint_pubkey,
+                    network.cluster.as_str()
+                ));
+            }
+        }
+    }
+    Ok(plan)
+}
+
+// EH
+// free lamports for the fees of a new wallet, only devnet, testnet and local validators give them
+fn airdrop(rpc: &RpcClient, wallet: &Pubkey) -> Result<Signature, String> {
+    let signature = match rpc.request_airdrop(wallet, AIRDROP_LAMPORTS) {
+        Ok(signature) => signature,
+        Err(e) => {
+            return Err(format!(
+                "the airdrop to the recipient wallet failed \n {}",
+                e
+            ))
+        }
+    };
+    match rpc.poll_for_signature(&signature) {
+        Ok(_) => Ok(signature),
+        Err(e) => Err(format!(
+            "the airdrop to the recipient wallet wasn't confirmed \n {}",
+            e
+        )),
+    }
 }
 
 // EH
 #[tokio::main]
 pub async fn create_token_account(
+    server: &ChatuzaServerClient,
     wallet_address: String,
     token_mint_address: String,
     token_program_id: String,
     lbh: String,
 ) -> Result<Vec<String>, String> {
-    let client = Client::new();
+    let request = CreateTokenAccount {
+        wallet_address,
+        token_mint_address,
+        token_program_id,
+        lbh,
+    };
+    match server.create_token_account(&request).await {
+        Ok(response) => Ok(response.signatures),
+        Err(e) => Err(e),
+    }
+}
 
-    let form_data = [
-        ("wallet_address", wallet_address),
-        ("token_mint_address", token_mint_address),
-        ("token_program_id", token_program_id),
-        ("lbh", lbh),
-    ];
+// EH
+#[tokio::main]
+pub async fn fund_account(
+    server: &ChatuzaServerClient,
+    wallet_address: String,
+) -> Result<String, String> {
+    server.fund_wallet(&FundWallet { wallet_address }).await
+}
 
-    let encoded_form_data = form_data
-        .iter()
-        .map(|(key, value)| format!("{}={}", key, value))
-        .collect::<Vec<_>>()
-        .join("&");
-
-    let request = client
-        .post("http://localhost:8000/api/create-token-account")
-        .header("Content-Type", "application/x-www-form-urlencoded")
-        .body(encoded_form_data);
-
-    let built_request: Request;
-    match request.build() {
-        Ok(req) => built_request = req,
-        Err(e) => {
-            return Err(format!(
-                "couldn't build the create account request due to {}",
-                e
-            ))
-        }
-    }
+pub mod structs {
+    use serde::{Deserialize, Serialize};
+    use zeroize::Zeroize;
 
-    let result: reqwest::Response;
-    match client.execute(built_request).await {
-        Ok(res) => result = res,
-        Err(e) => return Err(format!("sending request failed due to \n {}", e)),
+    #[derive(Debug, Default)]
+    pub struct NewAccountOutput {
+        pub mnemonic: Option<MnemonicInput>, // noted by the user
+        pub pub_key: String, // to interact with the account (the local database refers to it)
+        pub keypair: SecretString, // to sign the transactions (sealed in the keystore file)
     }
 
-    if result.status() != 200 {
-        return Err(format!("server returned {}", result.status()));
-    }
+    // holds key material, wiped when dropped and never printed.
+    // there is deliberately no Serialize impl, the only way out is an explicit `expose`
+    #[derive(Default, Clone)]
+    pub struct SecretString(String);
+
+    impl SecretString {
+        pub fn new(secret: String) -> Self {
+            SecretString(secret)
+        }
 
-    let response_text: String;
-    match result.text().await {
-        Ok(text) => response_text = text,
-        Err(e) => return Err(format!("couldn't fetch the response text due to \n {}", e)),
+        pub fn expose(&self) -> &str {
+            &self.0
+        }
     }
-    let response_val: Value;
-    match serde_json::from_str(response_text.as_str()) {
-        Ok(val) => response_val = val,
-        Err(e) => return Err(format!("failed to parse the response due to {}", e)),
+
+    impl Drop for SecretString {
+        fn drop(&mut self) {
+            self.0.zeroize();
+        }
     }
 
-    let signatures: Vec<String>;
-    match response_val["Ok"]["signatures"].as_array() {
-        Some(sigs) => signatures = sigs.iter().map(|signature| signature.to_string()).collect(),
-        None => {
-            return Err(format!(
-                "failed to read the signatures ! \n server returned {}",
-                response_val
-            ))
+    impl std::fmt::Debug for SecretString {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "SecretString([REDACTED])")
         }
     }
 
-    Ok(signatures)
-}
+    pub struct MnemonicOutput {
+        pub hashed: String,
+        pub raw: SecretString,
+    }
 
-#[tokio::main]
-pub async fn fund_account(wallet_address: String) -> Result<String, String> {
-    let client = Client::new();
+    impl std::fmt::Debug for MnemonicOutput {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("MnemonicOutput")
+                .field("hashed", &self.hashed)
+                .field("raw", &self.raw)
+                .finish()
+        }
+    }
 
-    let form_data = [("wallet_address", wallet_address)];
+    #[derive(Default, Clone)]
+    pub struct MnemonicInput {
+        pub words: [String; 12],
+    }
 
-    let encoded_form_data = form_data
-        .iter()
-        .map(|(key, value)| format!("{}={}", key, value))
-        .collect::<Vec<_>>()
-        .join("&");
-
-    let request = client
-        .post("http://localhost:8000/api/fund-wallet")
-        .header("Content-Type", "application/x-www-form-urlencoded")
-        .body(encoded_form_data);
-
-    let built_request: Request;
-    match request.build() {
-        Ok(req) => built_request = req,
-        Err(e) => {
-            return Err(format!(
-                "couldn't build the fund wallet request due to {}",
-                e
-            ))
+    impl MnemonicInput {
+        // the space separated phrase the seed is derived from
+        pub fn phrase(&self) -> SecretString {
+            SecretString::new(self.words.join(" "))
         }
     }
 
-    let result: reqwest::Response;
-    match client.execute(built_request).await {
-        Ok(res) => result = res,
-        Err(e) => return Err(format!("sending request failed due to \n {}", e)),
+    impl Drop for MnemonicInput {
+        fn drop(&mut self) {
+            self.words.iter_mut().for_each(|word| word.zeroize());
+        }
     }
 
-    if result.status() != 200 {
-        return Err(format!("server returned {}", result.status()));
+    impl std::fmt::Debug for MnemonicInput {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "MnemonicInput([REDACTED; 12])")
+        }
     }
 
-    let response_text: String;
-    match result.text().await {
-        Ok(text) => response_text = text,
-        Err(e) => return Err(format!("couldn't fetch the response text due to \n {}", e)),
-    }
-    let response_val: Value;
-    match serde_json::from_str(response_text.as_str()) {
-        Ok(val) => response_val = val,
-        Err(e) => return Err(format!("failed to parse the response due to {}", e)),
+    // content of sec.json, only the public key is readable without the password
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct EncryptedKeystore {
+        pub version: u8,
+        pub pub_key: String,
+        pub scrypt_log_n: u8,
+        pub scrypt_r: u32,
+        pub scrypt_p: u32,
+        pub salt: Vec<u8>,
+        pub nonce: Vec<u8>,
+        pub ciphertext: Vec<u8>,
+        pub tag: Vec<u8>,
     }
 
-    // println!("{:?}", response_val);
-    let signature: String;
-    match response_val["Ok"].as_str() {
-        Some(sig) => signature = sig.to_string(),
-        None => {
-            return Err(format!(
-                "failed to read the signature ! \n server returned {}",
-                response_val
-            ))
-        }
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct ArchivedAccount {
+        pub pub_key: String,
+        pub archived_at: u64, // unix seconds
+        pub restorable_until: u64,
     }
 
-    Ok(signature)
-}
+    // an encrypted blob of local data, see `keystore::seal`
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct SealedData {
+        pub nonce: Vec<u8>,
+        pub ciphertext: Vec<u8>,
+        pub tag: Vec<u8>,
+    }
 
-pub mod structs {
-    use bip39::Mnemonic;
-    use serde::Deserialize;
+    // persisted in backup.json, tied to the account it was confirmed for
+    #[derive(Serialize, Deserialize, Debug, Default)]
+    pub struct BackupStatus {
+        pub pub_key: String,
+        pub backed_up: bool,
+    }
 
-    #[derive(Debug, Default)]
-    pub struct NewAccountOutput<'b> {
-        pub mnemonic: Option<MnemonicInput<'b>>, // noted by the user
-        pub pub_key: String, // to interact with the account (stored on the client sqlite)
-        pub keypair: String, // to sign the transactions (stored on the client sqlite)
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct BackupQuiz {
+        pub positions: Vec<usize>, // 0 based word positions asked to the user
     }
 
-    #[derive(Debug)]
-    pub struct MnemonicOutput {
-        pub hashed: String,
-        pub raw: Mnemonic,
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct BackupAnswer {
+        pub position: usize,
+        pub word: String,
     }
-    #[derive(Debug)]
-    pub struct MnemonicInput<'a> {
-        pub words: [&'a str; 12],
+
+    // one piece of a split recovery phrase, shareable as a plain chat message
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct MnemonicShare {
+        pub set_id: u16, // random id shared by all the pieces of one split
+        pub threshold: u8,
+        pub index: u8,
+        pub words: String,
     }
 
-    impl std::fmt::Display for MnemonicInput<'_> {
+    impl std::fmt::Display for MnemonicShare {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            let mut temp_str: String = Default::default();
-            for (index, word) in self.words.iter().enumerate() {
-                if index == 0 {
-                    temp_str.push_str(format!("{}", word).as_str());
-                } else {
-                    temp_str.push_str(format!(" {}", word).as_str());
-                }
+            write!(
+                f,
+                "chatuza-share {:04x}-{}-{} {}",
+                self.set_id, self.threshold, self.index, self.words
+            )
+        }
+    }
+
+    impl std::str::FromStr for MnemonicShare {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let invalid = || format!("{} is not a chatuza recovery share", s);
+            let mut parts = s.trim().splitn(3, ' ');
+            if parts.next() != Some("chatuza-share") {
+                return Err(invalid());
             }
-            write!(f, "{}", temp_str)
+            let header: Vec<&str> = parts.next().ok_or_else(invalid)?.split('-').collect();
+            let words = parts.next().ok_or_else(invalid)?.to_string();
+            if header.len() != 3 {
+                return Err(invalid());
+            }
+            Ok(MnemonicShare {
+                set_id: u16::from_str_radix(header[0], 16).map_err(|_| invalid())?,
+                threshold: header[1].parse().map_err(|_| invalid())?,
+                index: header[2].parse().map_err(|_| invalid())?,
+                words,
+            })
         }
     }
 
-    #[derive(Default)]
+    #[derive(Serialize, Default)]
     pub struct CreateTokenAccount {
         pub wallet_address: String,
         pub token_mint_address: String,
@@ -407,10 +1003,145 @@ pub mod structs {
         pub signatures: Vec<String>,
     }
 
-    #[derive(Debug)]
+    #[derive(Serialize, Default)]
+    pub struct FundWallet {
+        pub wallet_address: String,
+    }
+
+    #[derive(Serialize, Debug)]
     pub struct TransferResponse {
-        pub create_account_sig: Option<String>,
+        // how the recipient accounts were made ready for the tokens
+        pub funding: FundingPath,
+        // the faucet or airdrop transaction funding the recipient wallet
         pub funding_account_sig: Option<String>,
+        // the backend transactions creating the recipient token account
+        pub create_account_sigs: Vec<String>,
         pub transfer_sig: String,
     }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Cluster {
+        Mainnet,
+        Testnet,
+        Devnet,
+        Localnet,
+    }
+
+    impl Cluster {
+        pub fn rpc_url(&self) -> &'static str {
+            match self {
+                Cluster::Mainnet => "https://api.mainnet-beta.solana.com",
+                Cluster::Testnet => "https://api.testnet.solana.com",
+                Cluster::Devnet => "https://api.devnet.solana.com",
+                Cluster::Localnet => "http://127.0.0.1:8899",
+            }
+        }
+
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                Cluster::Mainnet => "mainnet",
+                Cluster::Testnet => "testnet",
+                Cluster::Devnet => "devnet",
+                Cluster::Localnet => "localnet",
+            }
+        }
+
+        // nobody hands out free sol on mainnet
+        pub fn default_funding(&self) -> FundingStrategy {
+            match self {
+                Cluster::Mainnet => FundingStrategy::Disabled,
+                Cluster::Devnet => FundingStrategy::BackendFaucet,
+                Cluster::Testnet | Cluster::Localnet => FundingStrategy::Airdrop,
+            }
+        }
+
+        // the backend faucet only runs on devnet
+        pub fn supports(&self, funding: FundingStrategy) -> bool {
+            match funding {
+                FundingStrategy::BackendFaucet => *self == Cluster::Devnet,
+                FundingStrategy::Airdrop => *self != Cluster::Mainnet,
+                FundingStrategy::SenderPaysRent | FundingStrategy::Disabled => true,
+            }
+        }
+
+        // CHATUZA_CLUSTER, devnet when unset or unknown
+        pub fn from_env() -> Cluster {
+            match std::env::var(super::CLUSTER_ENV) {
+                Ok(name) => name.parse().unwrap_or(Cluster::Devnet),
+                Err(_) => Cluster::Devnet,
+            }
+        }
+    }
+
+    impl std::str::FromStr for Cluster {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.trim().to_lowercase().as_str() {
+                "mainnet" | "mainnet-beta" => Ok(Cluster::Mainnet),
+                "testnet" => Ok(Cluster::Testnet),
+                "devnet" => Ok(Cluster::Devnet),
+                "localnet" | "localhost" => Ok(Cluster::Localnet),
+                _ => Err(format!("{} is not a solana cluster", s)),
+            }
+        }
+    }
+
+    // how a transfer to a wallet without the token account gets it made
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum FundingStrategy {
+        // the chatuza backend funds the wallet and creates the token account
+        BackendFaucet,
+        // the cluster airdrops the wallet its fees, the sender creates the token account
+        Airdrop,
+        // the sender creates the token account and pays its rent
+        SenderPaysRent,
+        // transfers to a missing token account are refused
+        Disabled,
+    }
+
+    // which funding a transfer actually needed
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum FundingPath {
+        // the recipient had everything already
+        NotNeeded,
+        BackendFaucet,
+        Airdrop,
+        SenderPaidRent,
+    }
+
+    // the funding steps a transfer takes, decided before any of them is sent
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct FundingPlan {
+        pub path: FundingPath,
+        // through the backend faucet or an airdrop
+        pub fund_wallet: bool,
+        pub backend_creates_account: bool,
+        // in the transfer transaction, the sender pays the rent
+        pub sender_creates_account: bool,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+    pub struct NetworkSettings {
+        pub cluster: Cluster,
+        pub funding: FundingStrategy,
+    }
+
+    impl NetworkSettings {
+        pub fn for_cluster(cluster: Cluster) -> Self {
+            NetworkSettings {
+                cluster,
+                funding: cluster.default_funding(),
+            }
+        }
+    }
+
+    impl Default for NetworkSettings {
+        fn default() -> Self {
+            Self::for_cluster(Cluster::from_env())
+        }
+    }
 }
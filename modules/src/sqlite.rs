@@ -0,0 +1,1513 @@
+// Local database of the app, everything that has to survive a restart and isn't a key.
+// The keys stay in the keystore files, rows here only point at them by public key.
+// It's encrypted with sqlcipher, the key is derived from the wallet password and a salt kept
+// in a key file next to it, so it opens together with the wallet and belongs to one account
+use crate::keystore::derive_database_key;
+use crate::migrations;
+use crate::p2p_connection::structs::{
+    ChatMessage, MessageStatus, OutboxEntry, OutboxState, RetentionUpdate,
+};
+use crate::profiles::data_path;
+use crate::retention;
+use crate::server_interaction::structs::UserProfile;
+use crate::solana_wallet::unix_now;
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
+use rand::Rng;
+use rusqlite::{params, Connection, ErrorCode, OptionalExtension, Row};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use structs::{
+    AccountRecord, AddressBookEntry, Attachment, ContactAddress, Conversation, DatabaseKeyFile,
+    MessagePage, SearchHit, SnippetPart, StoredMessage,
+};
+use zeroize::Zeroizing;
+
+const DATABASE_FILE: &str = "chatuza.db";
+// the chat connection, the commands and a background job or two, more would only wait on locks
+const POOL_SIZE: u32 = 4;
+// sqlite allows one writer at a time, the others wait this long before failing
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+// what an unencrypted sqlite file starts with
+const PLAIN_HEADER: &[u8] = b"SQLite format 3\0";
+// the files a database is made of, besides the main one
+const DATABASE_FILE_SUFFIXES: [&str; 4] = ["", "-wal", "-shm", ".key"];
+// marks the matched words in the snippets, characters that can't be typed in a message
+const HIGHLIGHT_START: char = '\u{2}';
+const HIGHLIGHT_END: char = '\u{3}';
+// words of context around the matches
+const SNIPPET_WORDS: u32 = 12;
+// labels of the addresses taken from the chatuza profile of a contact
+pub const PROFILE_ADDRESS_LABEL: &str = "chatuza profile";
+pub const FORMER_PROFILE_ADDRESS_LABEL: &str = "former chatuza profile";
+// the attachment files are kept in <message id>/<file name> in it, next to the database
+pub const ATTACHMENTS_DIR: &str = "attachments";
+
+pub type DatabaseConnection = PooledConnection<SqliteConnectionManager>;
+
+// sqlcipher raw key, x'<64 hex digits>'
+type DatabaseKey = Zeroizing<String>;
+
+// Pool of connections to the local database, cheap to clone and shared by the whole app.
+// The pool is replaced when the database is re-keyed, every clone sees the new one
+#[derive(Clone, Debug)]
+pub struct Database {
+    path: String,
+    encrypted: bool,
+    pool: Arc<RwLock<Pool<SqliteConnectionManager>>>,
+}
+
+impl Database {
+    // EH
+    pub fn connection(&self) -> Result<DatabaseConnection, String> {
+        let pool = self.pool.read().unwrap().clone();
+        match pool.get() {
+            Ok(connection) => Ok(connection),
+            Err(e) => Err(format!("couldn't reach the local database \n {}", e)),
+        }
+    }
+
+    // EH
+    // re-encrypts the database with the key of `new_password`, called when the wallet password
+    // changes. The connections opened with the old key are dropped with the old pool
+    pub fn change_password(&self, new_password: &str) -> Result<(), String> {
+        if !self.encrypted {
+            return Err("the local database isn't encrypted".to_string());
+        }
+        let mut pool = self.pool.write().unwrap();
+        let key_file = read_key_file(&self.path)?;
+        let key = database_key(new_password, &key_file.salt);
+        let connection = match pool.get() {
+            Ok(connection) => connection,
+            Err(e) => return Err(format!("couldn't reach the local database \n {}", e)),
+        };
+        if let Err(e) = connection.pragma_update(None, "rekey", &*key) {
+            return Err(format!("couldn't re-encrypt the local database \n {}", e));
+        }
+        drop(connection);
+        *pool = build_pool(&self.path, Some(key))?;
+        Ok(())
+    }
+}
+
+// EH
+// the database of `account`, the one of another account is set aside until it comes back
+pub fn open_database(account: &str, password: &str) -> Result<Database, String> {
+    open_encrypted_database_at(&data_path(DATABASE_FILE), account, password)
+}
+
+// EH
+// an unencrypted database somewhere else than the profile directory, used by the tests.
+// An older schema is upgraded before the database is handed out
+pub fn open_database_at(path: &str) -> Result<Database, String> {
+    open_pool(path, None)
+}
+
+// EH
+pub fn open_encrypted_database_at(
+    path: &str,
+    account: &str,
+    password: &str,
+) -> Result<Database, String> {
+    switch_account(path, account)?;
+    let key_file = if Path::new(&key_file_path(path)).is_file() {
+        read_key_file(path)?
+    } else {
+        // a new database, or one from before the encryption
+        let key_file = DatabaseKeyFile {
+            pub_key: account.to_string(),
+            salt: rand::thread_rng().gen::<[u8; 16]>().to_vec(),
+        };
+        write_key_file(path, &key_file)?;
+        key_file
+    };
+    let key = database_key(password, &key_file.salt);
+    if is_plain_database(path) {
+        encrypt_plain_database(path, &key)?;
+    }
+    check_key(path, &key)?;
+    open_pool(path, Some(key))
+}
+
+fn open_pool(path: &str, key: Option<DatabaseKey>) -> Result<Database, String> {
+    let database = Database {
+        path: path.to_string(),
+        encrypted: key.is_some(),
+        pool: Arc::new(RwLock::new(build_pool(path, key)?)),
+    };
+    let mut connection = database.connection()?;
+    migrations::migrate(&mut connection, path)?;
+    Ok(database)
+}
+
+fn build_pool(
+    path: &str,
+    key: Option<DatabaseKey>,
+) -> Result<Pool<SqliteConnectionManager>, String> {
+    let manager = SqliteConnectionManager::file(path).with_init(move |connection| {
+        // the key has to come before anything else is read
+        if let Some(key) = key.as_ref() {
+            connection.pragma_update(None, "key", &**key)?;
+        }
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        // wal lets the readers go on while a message is written,
+        // secure_delete overwrites what's deleted instead of only unlinking it
+        connection.execute_batch(
+            "PRAGMA journal_mode = WAL; PRAGMA foreign_keys = ON; PRAGMA secure_delete = ON;",
+        )
+    });
+    match Pool::builder().max_size(POOL_SIZE).build(manager) {
+        Ok(pool) => Ok(pool),
+        Err(e) => Err(format!("couldn't open the local database \n {}", e)),
+    }
+}
+
+fn database_key(password: &str, salt: &[u8]) -> DatabaseKey {
+    let key = derive_database_key(password, salt);
+    let mut raw = Zeroizing::new(String::with_capacity(67));
+    raw.push_str("x'");
+    for byte in key.iter() {
+        raw.push_str(&format!("{:02x}", byte));
+    }
+    raw.push('\'');
+    raw
+}
+
+// a wrong key only shows when the first page is read, done here with a single connection so the
+// pool isn't left retrying
+fn check_key(path: &str, key: &DatabaseKey) -> Result<(), String> {
+    let connection = match Connection::open(path) {
+        Ok(connection) => connection,
+        Err(e) => return Err(format!("couldn't open the local database \n {}", e)),
+    };
+    if let Err(e) = connection.pragma_update(None, "key", &**key) {
+        return Err(format!("couldn't open the local database \n {}", e));
+    }
+    match connection.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(())) {
+        Ok(_) => Ok(()),
+        Err(e) if e.sqlite_error_code() == Some(ErrorCode::NotADatabase) => Err(format!(
+            "the local database can't be decrypted with this password, it was encrypted with another one or is damaged. The wallet still works, move {} away to start a new history",
+            path
+        )),
+        Err(e) => Err(format!("couldn't open the local database \n {}", e)),
+    }
+}
+
+fn is_plain_database(path: &str) -> bool {
+    match fs::read(path) {
+        Ok(content) => content.starts_with(PLAIN_HEADER),
+        Err(_) => false,
+    }
+}
+
+// the history written before the encryption is copied into an encrypted database that
+// replaces it, the unencrypted copies made before upgrades go with it
+fn encrypt_plain_database(path: &str, key: &DatabaseKey) -> Result<(), String> {
+    let encrypted_path = format!("{}.encrypting", path);
+    let _ = fs::remove_file(&encrypted_path);
+    let connection = match Connection::open(path) {
+        Ok(connection) => connection,
+        Err(e) => return Err(format!("couldn't encrypt the local database \n {}", e)),
+    };
+    if let Err(e) = connection
+        .execute(
+            "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+            params![encrypted_path, &**key],
+        )
+        .and_then(|_| connection.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(())))
+        .and_then(|_| connection.execute_batch("DETACH DATABASE encrypted"))
+    {
+        return Err(format!("couldn't encrypt the local database \n {}", e));
+    }
+    if let Err((_, e)) = connection.close() {
+        return Err(format!("couldn't encrypt the local database \n {}", e));
+    }
+    if let Err(e) = fs::rename(&encrypted_path, path) {
+        return Err(format!("couldn't encrypt the local database \n {}", e));
+    }
+    for version in 1..=migrations::SCHEMA_VERSION {
+        let _ = fs::remove_file(migrations::backup_path(path, version));
+    }
+    Ok(())
+}
+
+// the database of another account is renamed after it, and the one of `account` brought back
+fn switch_account(path: &str, account: &str) -> Result<(), String> {
+    if !Path::new(&key_file_path(path)).is_file() {
+        return Ok(());
+    }
+    let owner = read_key_file(path)?.pub_key;
+    if owner == account {
+        return Ok(());
+    }
+    move_database(path, &format!("{}.{}", path, owner))?;
+    let set_aside = format!("{}.{}", path, account);
+    if Path::new(&set_aside).is_file() {
+        move_database(&set_aside, path)?;
+    }
+    Ok(())
+}
+
+fn move_database(from: &str, to: &str) -> Result<(), String> {
+    for suffix in DATABASE_FILE_SUFFIXES {
+        let source = format!("{}{}", from, suffix);
+        if !Path::new(&source).exists() {
+            continue;
+        }
+        if let Err(e) = fs::rename(&source, format!("{}{}", to, suffix)) {
+            return Err(format!(
+                "couldn't move the local database of the account \n {}",
+                e
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn key_file_path(path: &str) -> String {
+    format!("{}.key", path)
+}
+
+fn read_key_file(path: &str) -> Result<DatabaseKeyFile, String> {
+    let text = match fs::read_to_string(key_file_path(path)) {
+        Ok(text) => text,
+        Err(e) => {
+            return Err(format!(
+                "couldn't read the local database key file \n {}",
+                e
+            ))
+        }
+    };
+    match serde_json::from_str(&text) {
+        Ok(key_file) => Ok(key_file),
+        Err(e) => Err(format!("the local database key file is corrupted \n {}", e)),
+    }
+}
+
+fn write_key_file(path: &str, key_file: &DatabaseKeyFile) -> Result<(), String> {
+    let text = match serde_json::to_string(key_file) {
+        Ok(text) => text,
+        Err(e) => {
+            return Err(format!(
+                "couldn't serialize the local database key file \n {}",
+                e
+            ))
+        }
+    };
+    match fs::write(key_file_path(path), text) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!(
+            "couldn't save the local database key file \n {}",
+            e
+        )),
+    }
+}
+
+// Accounts
+
+// EH
+// remembers the wallet and, once signed in, the chatuza user it belongs to
+pub fn save_account(connection: &Connection, account: &AccountRecord) -> Result<(), String> {
+    match connection.execute(
+        "INSERT INTO accounts (pub_key, username, display_name, created_at, last_used_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (pub_key) DO UPDATE SET
+            username = coalesce(excluded.username, username),
+            display_name = coalesce(excluded.display_name, display_name),
+            last_used_at = excluded.last_used_at",
+        params![
+            account.pub_key,
+            account.username,
+            account.display_name,
+            account.created_at,
+            account.last_used_at
+        ],
+    ) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't save the account \n {}", e)),
+    }
+}
+
+// EH
+pub fn get_account(
+    connection: &Connection,
+    pub_key: &str,
+) -> Result<Option<AccountRecord>, String> {
+    match connection
+        .query_row(
+            "SELECT * FROM accounts WHERE pub_key = ?1",
+            params![pub_key],
+            account_from_row,
+        )
+        .optional()
+    {
+        Ok(account) => Ok(account),
+        Err(e) => Err(format!("couldn't read the accounts \n {}", e)),
+    }
+}
+
+// EH
+// most recently used first
+pub fn list_accounts(connection: &Connection) -> Result<Vec<AccountRecord>, String> {
+    select_rows(
+        connection,
+        "SELECT * FROM accounts ORDER BY last_used_at DESC",
+        [],
+        account_from_row,
+        "couldn't read the accounts",
+    )
+}
+
+// EH
+pub fn delete_account(connection: &Connection, pub_key: &str) -> Result<(), String> {
+    match connection.execute("DELETE FROM accounts WHERE pub_key = ?1", params![pub_key]) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't delete the account \n {}", e)),
+    }
+}
+
+// Contacts
+
+// EH
+// a contact seen again keeps the date it was first added and what the user wrote about it.
+// A profile coming with another wallet address is recorded as a change for the user to review
+pub fn save_contact(connection: &Connection, contact: &UserProfile) -> Result<(), String> {
+    let stored = get_contact(connection, &contact.username)?;
+    match connection.execute(
+        "INSERT INTO contacts (username, display_name, wallet_pubkey, avatar_url, added_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (username) DO UPDATE SET
+            display_name = excluded.display_name,
+            wallet_pubkey = excluded.wallet_pubkey,
+            avatar_url = excluded.avatar_url",
+        params![
+            contact.username,
+            contact.display_name,
+            contact.wallet_pubkey,
+            contact.avatar_url,
+            unix_now()
+        ],
+    ) {
+        Ok(_) => (),
+        Err(e) => return Err(format!("couldn't save the contact \n {}", e)),
+    }
+    let former = match stored {
+        Some(stored) if stored.wallet_pubkey != contact.wallet_pubkey => stored.wallet_pubkey,
+        Some(_) => return Ok(()),
+        None => return insert_address(connection, contact, PROFILE_ADDRESS_LABEL, None),
+    };
+    // the address last reviewed stays the one compared with, a change undone is no change
+    if let Err(e) = connection.execute(
+        "UPDATE contacts SET previous_wallet_pubkey = nullif(coalesce(previous_wallet_pubkey, ?2), ?3)
+         WHERE username = ?1",
+        params![contact.username, former, contact.wallet_pubkey],
+    ) {
+        return Err(format!("couldn't save the contact \n {}", e));
+    }
+    if let Err(e) = connection.execute(
+        "UPDATE contact_addresses SET label = ?3 WHERE username = ?1 AND address = ?2 AND label = ?4",
+        params![
+            contact.username,
+            former,
+            FORMER_PROFILE_ADDRESS_LABEL,
+            PROFILE_ADDRESS_LABEL
+        ],
+    ) {
+        return Err(format!("couldn't save the contact \n {}", e));
+    }
+    insert_address(connection, contact, PROFILE_ADDRESS_LABEL, None)
+}
+
+// EH
+// replaces the stored contacts with the list of the server
+pub fn replace_contacts(
+    connection: &mut Connection,
+    contacts: &[UserProfile],
+) -> Result<(), String> {
+    let transaction = match connection.transaction() {
+        Ok(transaction) => transaction,
+        Err(e) => return Err(format!("couldn't save the contacts \n {}", e)),
+    };
+    let usernames: Vec<&str> = contacts
+        .iter()
+        .map(|contact| contact.username.as_str())
+        .collect();
+    for contact in contacts {
+        save_contact(&transaction, contact)?;
+    }
+    let stored = list_contacts(&transaction)?;
+    for contact in stored {
+        if !usernames.contains(&contact.username.as_str()) {
+            remove_contact(&transaction, &contact.username)?;
+        }
+    }
+    match transaction.commit() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't save the contacts \n {}", e)),
+    }
+}
+
+// EH
+pub fn get_contact(connection: &Connection, username: &str) -> Result<Option<UserProfile>, String> {
+    match connection
+        .query_row(
+            "SELECT * FROM contacts WHERE username = ?1",
+            params![username],
+            contact_from_row,
+        )
+        .optional()
+    {
+        Ok(contact) => Ok(contact),
+        Err(e) => Err(format!("couldn't read the contacts \n {}", e)),
+    }
+}
+
+// EH
+// in the order they were added
+pub fn list_contacts(connection: &Connection) -> Result<Vec<UserProfile>, String> {
+    select_rows(
+        connection,
+        "SELECT * FROM contacts ORDER BY added_at, username",
+        [],
+        contact_from_row,
+        "couldn't read the contacts",
+    )
+}
+
+// EH
+pub fn remove_contact(connection: &Connection, username: &str) -> Result<(), String> {
+    match connection.execute(
+        "DELETE FROM contacts WHERE username = ?1",
+        params![username],
+    ) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't remove the contact \n {}", e)),
+    }
+}
+
+// Address book
+
+// EH
+// the contacts with their addresses, in the order they were added
+pub fn list_address_book(connection: &Connection) -> Result<Vec<AddressBookEntry>, String> {
+    let mut entries = select_rows(
+        connection,
+        "SELECT * FROM contacts ORDER BY added_at, username",
+        [],
+        address_book_entry_from_row,
+        "couldn't read the address book",
+    )?;
+    let addresses = select_rows(
+        connection,
+        "SELECT * FROM contact_addresses ORDER BY added_at, id",
+        [],
+        contact_address_from_row,
+        "couldn't read the address book",
+    )?;
+    for address in addresses {
+        if let Some(entry) = entries
+            .iter_mut()
+            .find(|entry| entry.profile.username == address.username)
+        {
+            entry.addresses.push(address);
+        }
+    }
+    Ok(entries)
+}
+
+// EH
+pub fn get_address_book_entry(
+    connection: &Connection,
+    username: &str,
+) -> Result<Option<AddressBookEntry>, String> {
+    let mut entry = match connection
+        .query_row(
+            "SELECT * FROM contacts WHERE username = ?1",
+            params![username],
+            address_book_entry_from_row,
+        )
+        .optional()
+    {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return Ok(None),
+        Err(e) => return Err(format!("couldn't read the address book \n {}", e)),
+    };
+    entry.addresses = select_rows(
+        connection,
+        "SELECT * FROM contact_addresses WHERE username = ?1 ORDER BY added_at, id",
+        params![username],
+        contact_address_from_row,
+        "couldn't read the address book",
+    )?;
+    Ok(Some(entry))
+}
+
+// EH
+// what the user knows of a contact, None clears it
+pub fn update_contact_details(
+    connection: &Connection,
+    username: &str,
+    nickname: Option<&str>,
+    notes: Option<&str>,
+    custom_avatar_url: Option<&str>,
+) -> Result<(), String> {
+    match connection.execute(
+        "UPDATE contacts SET nickname = ?2, notes = ?3, custom_avatar_url = ?4 WHERE username = ?1",
+        params![username, nickname, notes, custom_avatar_url],
+    ) {
+        Ok(0) => Err(format!("{} isn't in the address book", username)),
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't save the contact \n {}", e)),
+    }
+}
+
+// EH
+// an address given by the user is verified by them
+pub fn add_contact_address(
+    connection: &Connection,
+    username: &str,
+    address: &str,
+    label: &str,
+) -> Result<ContactAddress, String> {
+    let contact = match get_contact(connection, username)? {
+        Some(contact) => contact,
+        None => return Err(format!("{} isn't in the address book", username)),
+    };
+    match connection.query_row(
+        "INSERT INTO contact_addresses (username, address, label, verified_at, added_at)
+         VALUES (?1, ?2, ?3, ?4, ?4)
+         ON CONFLICT (username, address) DO UPDATE SET
+            label = excluded.label,
+            verified_at = excluded.verified_at
+         RETURNING *",
+        params![contact.username, address, label, unix_now()],
+        contact_address_from_row,
+    ) {
+        Ok(address) => Ok(address),
+        Err(e) => Err(format!("couldn't save the address \n {}", e)),
+    }
+}
+
+// EH
+pub fn label_contact_address(connection: &Connection, id: u64, label: &str) -> Result<(), String> {
+    match connection.execute(
+        "UPDATE contact_addresses SET label = ?2 WHERE id = ?1",
+        params![id, label],
+    ) {
+        Ok(0) => Err("the address isn't in the address book".to_string()),
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't save the address \n {}", e)),
+    }
+}
+
+// EH
+// the user checked the address with the contact. Verifying the address of the profile
+// accepts its change
+pub fn verify_contact_address(connection: &Connection, id: u64) -> Result<(), String> {
+    match connection.execute(
+        "UPDATE contact_addresses SET verified_at = ?2 WHERE id = ?1",
+        params![id, unix_now()],
+    ) {
+        Ok(0) => return Err("the address isn't in the address book".to_string()),
+        Ok(_) => (),
+        Err(e) => return Err(format!("couldn't save the address \n {}", e)),
+    }
+    match connection.execute(
+        "UPDATE contacts SET previous_wallet_pubkey = NULL WHERE wallet_pubkey =
+            (SELECT address FROM contact_addresses WHERE id = ?1 AND username = contacts.username)",
+        params![id],
+    ) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't save the address \n {}", e)),
+    }
+}
+
+// EH
+pub fn remove_contact_address(connection: &Connection, id: u64) -> Result<(), String> {
+    match connection.execute("DELETE FROM contact_addresses WHERE id = ?1", params![id]) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't remove the address \n {}", e)),
+    }
+}
+
+// EH
+// the address a payment to the contact goes to: the one picked, or the verified address of the
+// profile, or the first verified one. Nothing is paid to an unverified address or while the
+// profile address changed and the user didn't pick one
+pub fn payment_address(
+    connection: &Connection,
+    username: &str,
+    address_id: Option<u64>,
+) -> Result<String, String> {
+    let entry = match get_address_book_entry(connection, username)? {
+        Some(entry) => entry,
+        None => return Err(format!("{} isn't in the address book", username)),
+    };
+    let name = entry.name().to_string();
+    let address = match address_id {
+        Some(id) => match entry.addresses.iter().find(|address| address.id == id) {
+            Some(address) => address,
+            None => return Err(format!("that address isn't one of {}", name)),
+        },
+        None => {
+            if let Some(previous) = &entry.previous_wallet_pubkey {
+                return Err(format!(
+                    "the wallet address of {} changed from {} to {}, check it with them and verify it, or pick an address",
+                    name, previous, entry.profile.wallet_pubkey
+                ));
+            }
+            let verified = entry
+                .addresses
+                .iter()
+                .filter(|address| address.verified_at.is_some());
+            match verified
+                .clone()
+                .find(|address| address.address == entry.profile.wallet_pubkey)
+                .or_else(|| verified.clone().next())
+            {
+                Some(address) => address,
+                None => {
+                    return Err(format!(
+                        "no address of {} is verified yet, check one with them first",
+                        name
+                    ))
+                }
+            }
+        }
+    };
+    if address.verified_at.is_none() {
+        return Err(format!(
+            "{} ({}) isn't verified, check it with {} first",
+            address.address, address.label, name
+        ));
+    }
+    Ok(address.address.clone())
+}
+
+// EH
+// an address from an import, added unverified: a verification only counts when it was done on
+// this device, an export could say anything. What's already here is kept as is
+pub fn merge_contact_address(
+    connection: &Connection,
+    address: &ContactAddress,
+) -> Result<bool, String> {
+    match connection.execute(
+        "INSERT INTO contact_addresses (username, address, label, verified_at, added_at)
+         VALUES (?1, ?2, ?3, NULL, ?4)
+         ON CONFLICT (username, address) DO NOTHING",
+        params![
+            address.username,
+            address.address,
+            address.label,
+            address.added_at
+        ],
+    ) {
+        Ok(changed) => Ok(changed > 0),
+        Err(e) => Err(format!("couldn't save the address \n {}", e)),
+    }
+}
+
+fn insert_address(
+    connection: &Connection,
+    contact: &UserProfile,
+    label: &str,
+    verified_at: Option<u64>,
+) -> Result<(), String> {
+    match connection.execute(
+        "INSERT INTO contact_addresses (username, address, label, verified_at, added_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (username, address) DO UPDATE SET label = excluded.label",
+        params![
+            contact.username,
+            contact.wallet_pubkey,
+            label,
+            verified_at,
+            unix_now()
+        ],
+    ) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't save the contact \n {}", e)),
+    }
+}
+
+// Conversations
+
+// EH
+// the conversation row is created with its first message
+pub fn ensure_conversation(connection: &Connection, conversation_id: &str) -> Result<(), String> {
+    match connection.execute(
+        "INSERT INTO conversations (id, created_at) VALUES (?1, ?2) ON CONFLICT (id) DO NOTHING",
+        params![conversation_id, unix_now()],
+    ) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't save the conversation \n {}", e)),
+    }
+}
+
+// EH
+// a conversation from an import starts when it started on the exporting device, if earlier
+pub fn save_conversation(
+    connection: &Connection,
+    conversation_id: &str,
+    created_at: u64,
+) -> Result<(), String> {
+    match connection.execute(
+        "INSERT INTO conversations (id, created_at) VALUES (?1, ?2)
+         ON CONFLICT (id) DO UPDATE SET created_at = min(created_at, excluded.created_at)",
+        params![conversation_id, created_at],
+    ) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't save the conversation \n {}", e)),
+    }
+}
+
+// EH
+// latest activity first, with the count of messages of others not read yet
+pub fn list_conversations(connection: &Connection, me: &str) -> Result<Vec<Conversation>, String> {
+    select_rows(
+        connection,
+        "SELECT conversations.*,
+            (SELECT count(*) FROM messages
+             WHERE messages.conversation_id = conversations.id
+               AND messages.sender != ?1 AND messages.status < ?2) AS unread
+         FROM conversations
+         ORDER BY coalesce(last_message_at, created_at) DESC",
+        params![me, MessageStatus::Read.rank()],
+        conversation_from_row,
+        "couldn't read the conversations",
+    )
+}
+
+// Outbox
+
+// EH
+pub fn insert_outbox(connection: &Connection, entry: &OutboxEntry) -> Result<(), String> {
+    match connection.execute(
+        "INSERT INTO outbox (client_id, conversation_id, body, created_at, attempts, state, last_error)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            entry.client_id,
+            entry.conversation_id,
+            entry.body,
+            entry.created_at,
+            entry.attempts,
+            entry.state.as_str(),
+            entry.last_error
+        ],
+    ) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't queue the message \n {}", e)),
+    }
+}
+
+// EH
+// every message the server didn't accept yet, oldest first
+pub fn list_outbox(connection: &Connection) -> Result<Vec<OutboxEntry>, String> {
+    select_outbox(
+        connection,
+        "SELECT * FROM outbox ORDER BY created_at, rowid",
+    )
+}
+
+// EH
+// what to send again, including messages written on a connection that broke
+pub fn pending_outbox(connection: &Connection) -> Result<Vec<OutboxEntry>, String> {
+    select_outbox(
+        connection,
+        "SELECT * FROM outbox WHERE state != 'failed' ORDER BY created_at, rowid",
+    )
+}
+
+// EH
+pub fn get_outbox(connection: &Connection, client_id: &str) -> Result<Option<OutboxEntry>, String> {
+    match connection
+        .query_row(
+            "SELECT * FROM outbox WHERE client_id = ?1",
+            params![client_id],
+            outbox_from_row,
+        )
+        .optional()
+    {
+        Ok(entry) => Ok(entry),
+        Err(e) => Err(format!("couldn't read the outbox \n {}", e)),
+    }
+}
+
+// EH
+pub fn update_outbox(connection: &Connection, entry: &OutboxEntry) -> Result<(), String> {
+    match connection.execute(
+        "UPDATE outbox SET attempts = ?2, state = ?3, last_error = ?4 WHERE client_id = ?1",
+        params![
+            entry.client_id,
+            entry.attempts,
+            entry.state.as_str(),
+            entry.last_error
+        ],
+    ) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't update the outbox \n {}", e)),
+    }
+}
+
+// EH
+// once the server stored the message it isn't ours to deliver anymore
+pub fn remove_outbox(connection: &Connection, client_id: &str) -> Result<(), String> {
+    match connection.execute(
+        "DELETE FROM outbox WHERE client_id = ?1",
+        params![client_id],
+    ) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't update the outbox \n {}", e)),
+    }
+}
+
+// Messages
+
+// EH
+// a message seen again keeps the furthest status
+pub fn insert_message(connection: &Connection, message: &ChatMessage) -> Result<(), String> {
+    ensure_conversation(connection, &message.conversation_id)?;
+    if let Err(e) = connection.execute(
+        "INSERT INTO messages (id, conversation_id, sender, body, sent_at, client_id, status)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT (id) DO UPDATE SET status = max(status, excluded.status)",
+        params![
+            message.id,
+            message.conversation_id,
+            message.sender,
+            message.body,
+            message.sent_at,
+            message.client_id,
+            message.status.rank()
+        ],
+    ) {
+        return Err(format!("couldn't save the message \n {}", e));
+    }
+    match connection.execute(
+        "UPDATE conversations SET last_message_id = ?2, last_message_at = ?3
+         WHERE id = ?1 AND coalesce(last_message_id, 0) < ?2",
+        params![message.conversation_id, message.id, message.sent_at],
+    ) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't save the conversation \n {}", e)),
+    }
+}
+
+// EH
+pub fn get_message(connection: &Connection, id: u64) -> Result<Option<ChatMessage>, String> {
+    match connection
+        .query_row(
+            "SELECT * FROM messages WHERE id = ?1",
+            params![id],
+            message_from_row,
+        )
+        .optional()
+    {
+        Ok(message) => Ok(message),
+        Err(e) => Err(format!("couldn't read the messages \n {}", e)),
+    }
+}
+
+// EH
+// the newest message kept on this device, None when there's none
+pub fn last_message_id(connection: &Connection) -> Result<Option<u64>, String> {
+    match connection.query_row("SELECT max(id) FROM messages", [], |row| row.get(0)) {
+        Ok(id) => Ok(id),
+        Err(e) => Err(format!("couldn't read the messages \n {}", e)),
+    }
+}
+
+// EH
+// oldest first
+pub fn list_messages(
+    connection: &Connection,
+    conversation_id: &str,
+) -> Result<Vec<ChatMessage>, String> {
+    select_rows(
+        connection,
+        "SELECT * FROM messages WHERE conversation_id = ?1 ORDER BY id",
+        params![conversation_id],
+        message_from_row,
+        "couldn't read the messages",
+    )
+}
+
+// EH
+// the `limit` messages before `before`, the latest ones when None. Oldest first
+pub fn list_messages_before(
+    connection: &Connection,
+    conversation_id: &str,
+    before: Option<u64>,
+    limit: u32,
+) -> Result<MessagePage, String> {
+    // one more than asked tells whether there are others
+    let mut messages = select_rows(
+        connection,
+        "SELECT * FROM messages WHERE conversation_id = ?1 AND (?2 IS NULL OR id < ?2)
+         ORDER BY id DESC LIMIT ?3",
+        params![conversation_id, before, limit + 1],
+        message_from_row,
+        "couldn't read the messages",
+    )?;
+    let has_more = messages.len() > limit as usize;
+    messages.truncate(limit as usize);
+    messages.reverse();
+    Ok(MessagePage { messages, has_more })
+}
+
+// EH
+// the `limit` messages after `after`, oldest first
+pub fn list_messages_after(
+    connection: &Connection,
+    conversation_id: &str,
+    after: u64,
+    limit: u32,
+) -> Result<MessagePage, String> {
+    let mut messages = select_rows(
+        connection,
+        "SELECT * FROM messages WHERE conversation_id = ?1 AND id > ?2 ORDER BY id LIMIT ?3",
+        params![conversation_id, after, limit + 1],
+        message_from_row,
+        "couldn't read the messages",
+    )?;
+    let has_more = messages.len() > limit as usize;
+    messages.truncate(limit as usize);
+    Ok(MessagePage { messages, has_more })
+}
+
+// EH
+// moves the messages of `sender` up to `up_to` forward to `status`, never backward
+pub fn apply_receipt(
+    connection: &Connection,
+    conversation_id: &str,
+    sender: &str,
+    up_to: u64,
+    status: MessageStatus,
+) -> Result<usize, String> {
+    match connection.execute(
+        "UPDATE messages SET status = ?4
+         WHERE conversation_id = ?1 AND sender = ?2 AND id <= ?3 AND status < ?4",
+        params![conversation_id, sender, up_to, status.rank()],
+    ) {
+        Ok(changed) => Ok(changed),
+        Err(e) => Err(format!("couldn't save the receipt \n {}", e)),
+    }
+}
+
+// EH
+// marks what others sent up to `up_to` as read, false when it already was
+pub fn mark_read(
+    connection: &Connection,
+    conversation_id: &str,
+    me: &str,
+    up_to: u64,
+) -> Result<bool, String> {
+    match connection.execute(
+        "UPDATE messages SET status = ?4
+         WHERE conversation_id = ?1 AND sender != ?2 AND id <= ?3 AND status < ?4",
+        params![conversation_id, me, up_to, MessageStatus::Read.rank()],
+    ) {
+        Ok(changed) => Ok(changed > 0),
+        Err(e) => Err(format!("couldn't save the receipt \n {}", e)),
+    }
+}
+
+// Search
+
+// EH
+// the messages with every word of `query`, each one matched as a prefix so results come while
+// typing, best matches first. `conversation_id` narrows it to one conversation
+pub fn search_messages(
+    connection: &Connection,
+    query: &str,
+    conversation_id: Option<&str>,
+    limit: u32,
+) -> Result<Vec<SearchHit>, String> {
+    let match_query = match fts_query(query) {
+        Some(match_query) => match_query,
+        None => return Ok(vec![]),
+    };
+    select_rows(
+        connection,
+        &format!(
+            "SELECT messages.*, snippet(messages_fts, 0, '{}', '{}', '…', {}) AS snippet
+             FROM messages_fts JOIN messages ON messages.id = messages_fts.rowid
+             WHERE messages_fts MATCH ?1 AND (?2 IS NULL OR messages.conversation_id = ?2)
+             ORDER BY rank, messages.id DESC
+             LIMIT ?3",
+            HIGHLIGHT_START, HIGHLIGHT_END, SNIPPET_WORDS
+        ),
+        params![match_query, conversation_id, limit],
+        search_hit_from_row,
+        "couldn't search the messages",
+    )
+}
+
+// every word quoted, so what the user types is never read as fts syntax, and matched as a prefix
+fn fts_query(query: &str) -> Option<String> {
+    let words: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{}\"*", word))
+        .collect();
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.join(" "))
+    }
+}
+
+// Retention
+
+// EH
+// the timer of the conversation as the server last gave it, false when this one is older.
+// It's never shorter than `retention::MIN_RETENTION_SECS`
+pub fn apply_retention(connection: &Connection, update: &RetentionUpdate) -> Result<bool, String> {
+    ensure_conversation(connection, &update.conversation_id)?;
+    match connection.execute(
+        "UPDATE conversations SET retention_secs = ?2, retention_set_by = ?3, retention_set_at = ?4
+         WHERE id = ?1 AND coalesce(retention_set_at, 0) <= ?4",
+        params![
+            update.conversation_id,
+            retention::at_least_minimum(update.retention_secs),
+            update.set_by,
+            update.set_at
+        ],
+    ) {
+        Ok(changed) => Ok(changed > 0),
+        Err(e) => Err(format!(
+            "couldn't save the disappearing messages timer \n {}",
+            e
+        )),
+    }
+}
+
+// EH
+// None until a timer was set in the conversation
+pub fn get_retention(
+    connection: &Connection,
+    conversation_id: &str,
+) -> Result<Option<RetentionUpdate>, String> {
+    match connection
+        .query_row(
+            "SELECT id, retention_secs, retention_set_by, retention_set_at FROM conversations
+             WHERE id = ?1 AND retention_set_at IS NOT NULL",
+            params![conversation_id],
+            |row| {
+                Ok(RetentionUpdate {
+                    conversation_id: row.get("id")?,
+                    retention_secs: row.get("retention_secs")?,
+                    set_by: row.get("retention_set_by")?,
+                    set_at: row.get("retention_set_at")?,
+                })
+            },
+        )
+        .optional()
+    {
+        Ok(update) => Ok(update),
+        Err(e) => Err(format!(
+            "couldn't read the disappearing messages timer \n {}",
+            e
+        )),
+    }
+}
+
+// EH
+// the messages whose conversation timer ran out at `now`
+pub fn expired_messages(connection: &Connection, now: u64) -> Result<Vec<StoredMessage>, String> {
+    select_rows(
+        connection,
+        "SELECT messages.id, messages.conversation_id, length(CAST(messages.body AS BLOB))
+            + (SELECT coalesce(sum(size), 0) FROM attachments WHERE message_id = messages.id) AS size
+         FROM messages JOIN conversations ON conversations.id = messages.conversation_id
+         WHERE conversations.retention_secs IS NOT NULL
+           AND messages.sent_at + conversations.retention_secs <= ?1
+         ORDER BY messages.id",
+        params![now],
+        stored_message_from_row,
+        "couldn't read the expired messages",
+    )
+}
+
+// EH
+// every message with what it takes on disk, its body and its attachments. Oldest first
+pub fn stored_messages(connection: &Connection) -> Result<Vec<StoredMessage>, String> {
+    select_rows(
+        connection,
+        "SELECT messages.id, messages.conversation_id, length(CAST(messages.body AS BLOB))
+            + (SELECT coalesce(sum(size), 0) FROM attachments WHERE message_id = messages.id) AS size
+         FROM messages ORDER BY messages.id",
+        [],
+        stored_message_from_row,
+        "couldn't read the history size",
+    )
+}
+
+// EH
+// removes the messages with their attachment rows and index entries, and returns the paths of
+// the attachment files for the caller to erase. The conversations point at their new last message
+pub fn delete_messages(connection: &mut Connection, ids: &[u64]) -> Result<Vec<String>, String> {
+    let transaction = match connection.transaction() {
+        Ok(transaction) => transaction,
+        Err(e) => return Err(format!("couldn't delete the messages \n {}", e)),
+    };
+    let mut paths = vec![];
+    for id in ids {
+        for attachment in list_attachments(&transaction, *id)? {
+            paths.push(attachment.path);
+        }
+        if let Err(e) = transaction.execute("DELETE FROM messages WHERE id = ?1", params![id]) {
+            return Err(format!("couldn't delete the messages \n {}", e));
+        }
+    }
+    if let Err(e) = transaction.execute_batch(
+        "UPDATE conversations SET
+            last_message_id = (SELECT max(id) FROM messages WHERE conversation_id = conversations.id),
+            last_message_at = (SELECT max(sent_at) FROM messages WHERE conversation_id = conversations.id)
+         WHERE last_message_id IS NOT NULL
+           AND last_message_id NOT IN (SELECT id FROM messages);",
+    ) {
+        return Err(format!("couldn't delete the messages \n {}", e));
+    }
+    match transaction.commit() {
+        Ok(_) => Ok(paths),
+        Err(e) => Err(format!("couldn't delete the messages \n {}", e)),
+    }
+}
+
+// EH
+// moves what's left of deleted rows out of the write ahead log and the free pages
+pub fn flush_deleted(connection: &Connection) -> Result<(), String> {
+    match connection.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(())) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't flush the deleted messages \n {}", e)),
+    }
+}
+
+// Attachments
+
+// EH
+// `attachment.id` is ignored, the new id is returned
+pub fn insert_attachment(connection: &Connection, attachment: &Attachment) -> Result<u64, String> {
+    match connection.execute(
+        "INSERT INTO attachments (message_id, file_name, mime_type, size, path)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            attachment.message_id,
+            attachment.file_name,
+            attachment.mime_type,
+            attachment.size,
+            attachment.path
+        ],
+    ) {
+        Ok(_) => Ok(connection.last_insert_rowid() as u64),
+        Err(e) => Err(format!("couldn't save the attachment \n {}", e)),
+    }
+}
+
+// EH
+pub fn list_attachments(
+    connection: &Connection,
+    message_id: u64,
+) -> Result<Vec<Attachment>, String> {
+    select_rows(
+        connection,
+        "SELECT * FROM attachments WHERE message_id = ?1 ORDER BY id",
+        params![message_id],
+        attachment_from_row,
+        "couldn't read the attachments",
+    )
+}
+
+// Settings
+
+// EH
+pub fn get_setting(connection: &Connection, key: &str) -> Result<Option<String>, String> {
+    match connection
+        .query_row(
+            "SELECT value FROM settings WHERE key = ?1",
+            params![key],
+            |row| row.get(0),
+        )
+        .optional()
+    {
+        Ok(value) => Ok(value),
+        Err(e) => Err(format!("couldn't read the settings \n {}", e)),
+    }
+}
+
+// EH
+pub fn set_setting(connection: &Connection, key: &str, value: &str) -> Result<(), String> {
+    match connection.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    ) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("couldn't save the settings \n {}", e)),
+    }
+}
+
+fn select_outbox(connection: &Connection, query: &str) -> Result<Vec<OutboxEntry>, String> {
+    select_rows(
+        connection,
+        query,
+        [],
+        outbox_from_row,
+        "couldn't read the outbox",
+    )
+}
+
+// every row of `query`, `error` tells the user what couldn't be read
+fn select_rows<T, P: rusqlite::Params>(
+    connection: &Connection,
+    query: &str,
+    params: P,
+    from_row: fn(&Row) -> rusqlite::Result<T>,
+    error: &str,
+) -> Result<Vec<T>, String> {
+    let mut statement = match connection.prepare(query) {
+        Ok(statement) => statement,
+        Err(e) => return Err(format!("{} \n {}", error, e)),
+    };
+    let rows = match statement.query_map(params, from_row) {
+        Ok(rows) => rows,
+        Err(e) => return Err(format!("{} \n {}", error, e)),
+    };
+    match rows.collect::<Result<Vec<_>, _>>() {
+        Ok(rows) => Ok(rows),
+        Err(e) => Err(format!("{} \n {}", error, e)),
+    }
+}
+
+fn account_from_row(row: &Row) -> rusqlite::Result<AccountRecord> {
+    Ok(AccountRecord {
+        pub_key: row.get("pub_key")?,
+        username: row.get("username")?,
+        display_name: row.get("display_name")?,
+        created_at: row.get("created_at")?,
+        last_used_at: row.get("last_used_at")?,
+    })
+}
+
+fn contact_from_row(row: &Row) -> rusqlite::Result<UserProfile> {
+    Ok(UserProfile {
+        username: row.get("username")?,
+        display_name: row.get("display_name")?,
+        wallet_pubkey: row.get("wallet_pubkey")?,
+        avatar_url: row.get("avatar_url")?,
+    })
+}
+
+fn address_book_entry_from_row(row: &Row) -> rusqlite::Result<AddressBookEntry> {
+    Ok(AddressBookEntry {
+        profile: contact_from_row(row)?,
+        nickname: row.get("nickname")?,
+        notes: row.get("notes")?,
+        custom_avatar_url: row.get("custom_avatar_url")?,
+        previous_wallet_pubkey: row.get("previous_wallet_pubkey")?,
+        addresses: vec![],
+    })
+}
+
+fn contact_address_from_row(row: &Row) -> rusqlite::Result<ContactAddress> {
+    Ok(ContactAddress {
+        id: row.get("id")?,
+        username: row.get("username")?,
+        address: row.get("address")?,
+        label: row.get("label")?,
+        verified_at: row.get("verified_at")?,
+        added_at: row.get("added_at")?,
+    })
+}
+
+fn stored_message_from_row(row: &Row) -> rusqlite::Result<StoredMessage> {
+    Ok(StoredMessage {
+        id: row.get("id")?,
+        conversation_id: row.get("conversation_id")?,
+        size: row.get("size")?,
+    })
+}
+
+fn conversation_from_row(row: &Row) -> rusqlite::Result<Conversation> {
+    Ok(Conversation {
+        id: row.get("id")?,
+        created_at: row.get("created_at")?,
+        last_message_id: row.get("last_message_id")?,
+        last_message_at: row.get("last_message_at")?,
+        unread: row.get("unread")?,
+    })
+}
+
+fn attachment_from_row(row: &Row) -> rusqlite::Result<Attachment> {
+    Ok(Attachment {
+        id: row.get("id")?,
+        message_id: row.get("message_id")?,
+        file_name: row.get("file_name")?,
+        mime_type: row.get("mime_type")?,
+        size: row.get("size")?,
+        path: row.get("path")?,
+    })
+}
+
+fn outbox_from_row(row: &Row) -> rusqlite::Result<OutboxEntry> {
+    let state: String = row.get("state")?;
+    Ok(OutboxEntry {
+        client_id: row.get("client_id")?,
+        conversation_id: row.get("conversation_id")?,
+        body: row.get("body")?,
+        created_at: row.get("created_at")?,
+        attempts: row.get("attempts")?,
+        state: OutboxState::parse(&state),
+        last_error: row.get("last_error")?,
+    })
+}
+
+fn search_hit_from_row(row: &Row) -> rusqlite::Result<SearchHit> {
+    let snippet: String = row.get("snippet")?;
+    Ok(SearchHit {
+        message: message_from_row(row)?,
+        snippet: snippet_parts(&snippet),
+    })
+}
+
+// "a \u{2}match\u{3} in it" -> "a ", "match" highlighted, " in it"
+fn snippet_parts(snippet: &str) -> Vec<SnippetPart> {
+    let mut parts = vec![];
+    for (i, piece) in snippet.split(HIGHLIGHT_START).enumerate() {
+        let (highlighted, rest) = match piece.split_once(HIGHLIGHT_END) {
+            Some((highlighted, rest)) if i > 0 => (highlighted, rest),
+            _ => ("", piece),
+        };
+        for (text, highlighted) in [(highlighted, true), (rest, false)] {
+            if !text.is_empty() {
+                parts.push(SnippetPart {
+                    text: text.to_string(),
+                    highlighted,
+                });
+            }
+        }
+    }
+    parts
+}
+
+fn message_from_row(row: &Row) -> rusqlite::Result<ChatMessage> {
+    Ok(ChatMessage {
+        id: row.get("id")?,
+        conversation_id: row.get("conversation_id")?,
+        sender: row.get("sender")?,
+        body: row.get("body")?,
+        sent_at: row.get("sent_at")?,
+        client_id: row.get("client_id")?,
+        status: MessageStatus::from_rank(row.get("status")?),
+    })
+}
+
+pub mod structs {
+    use crate::p2p_connection::structs::ChatMessage;
+    use crate::server_interaction::structs::UserProfile;
+    use serde::{Deserialize, Serialize};
+
+    // content of chatuza.db.key, nothing secret: the key also needs the wallet password
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct DatabaseKeyFile {
+        // the account the database belongs to
+        pub pub_key: String,
+        pub salt: Vec<u8>,
+    }
+
+    // a wallet used on this device, the keypair itself stays in the keystore
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct AccountRecord {
+        pub pub_key: String,
+        // the chatuza user signed in with it, None until the first sign in
+        pub username: Option<String>,
+        pub display_name: Option<String>,
+        pub created_at: u64,   // unix seconds
+        pub last_used_at: u64, // unix seconds
+    }
+
+    // a contact as the user knows it
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct AddressBookEntry {
+        #[serde(flatten)]
+        pub profile: UserProfile,
+        pub nickname: Option<String>,
+        pub notes: Option<String>,
+        // shown instead of the avatar of the profile
+        pub custom_avatar_url: Option<String>,
+        // the address of the profile before it changed, until the user verifies the new one
+        pub previous_wallet_pubkey: Option<String>,
+        pub addresses: Vec<ContactAddress>,
+    }
+
+    impl AddressBookEntry {
+        pub fn name(&self) -> &str {
+            self.nickname
+                .as_deref()
+                .unwrap_or(&self.profile.display_name)
+        }
+    }
+
+    // a wallet address of a contact, from its profile or added by the user
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct ContactAddress {
+        pub id: u64,
+        pub username: String,
+        pub address: String,
+        pub label: String,
+        pub verified_at: Option<u64>, // unix seconds, None until the user checks it
+        pub added_at: u64,            // unix seconds
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct Conversation {
+        pub id: String,
+        pub created_at: u64,
+        pub last_message_id: Option<u64>,
+        pub last_message_at: Option<u64>,
+        pub unread: u64,
+    }
+
+    // a message as far as the retention is concerned
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct StoredMessage {
+        pub id: u64,
+        pub conversation_id: String,
+        pub size: u64, // bytes of the body and the attachments
+    }
+
+    // a file sent with a message, kept next to the database
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct Attachment {
+        pub id: u64,
+        pub message_id: u64,
+        pub file_name: String,
+        pub mime_type: String,
+        pub size: u64, // bytes
+        pub path: String,
+    }
+
+    // a slice of a conversation, oldest first
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct MessagePage {
+        pub messages: Vec<ChatMessage>,
+        // there are more messages past the slice, in the direction it was loaded
+        pub has_more: bool,
+    }
+
+    // a message found by a search, with the matching words marked in an extract of its body
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct SearchHit {
+        pub message: ChatMessage,
+        pub snippet: Vec<SnippetPart>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct SnippetPart {
+        pub text: String,
+        pub highlighted: bool,
+    }
+}
@@ -0,0 +1,47 @@
+// the quiz only confirms a backup when every asked word, and nothing else, is answered right
+use modules::solana_wallet::check_quiz_answers;
+use modules::structs::{BackupAnswer, BackupQuiz, MnemonicInput};
+
+fn mnemonic() -> MnemonicInput {
+    let words: Vec<String> = (1..=12).map(|n| format!("word{}", n)).collect();
+    MnemonicInput {
+        words: words.try_into().unwrap(),
+    }
+}
+
+fn answer(position: usize, word: &str) -> BackupAnswer {
+    BackupAnswer {
+        position,
+        word: word.to_string(),
+    }
+}
+
+#[test]
+fn answers_must_cover_exactly_the_asked_positions() {
+    let quiz = BackupQuiz {
+        positions: vec![1, 4, 9],
+    };
+    let right = [
+        answer(9, "word10"),
+        answer(1, " Word2 "),
+        answer(4, "word5"),
+    ];
+    assert!(check_quiz_answers(&mnemonic(), &quiz, &right).is_ok());
+
+    // nothing answered
+    assert!(check_quiz_answers(&mnemonic(), &quiz, &[]).is_err());
+    // one missing
+    assert!(check_quiz_answers(&mnemonic(), &quiz, &right[..2]).is_err());
+    // positions that weren't asked, even right
+    let other = [answer(0, "word1"), answer(2, "word3"), answer(3, "word4")];
+    assert!(check_quiz_answers(&mnemonic(), &quiz, &other).is_err());
+    // the same position twice
+    let twice = [answer(1, "word2"), answer(1, "word2"), answer(4, "word5")];
+    assert!(check_quiz_answers(&mnemonic(), &quiz, &twice).is_err());
+    // a wrong word
+    let wrong = [answer(1, "word2"), answer(4, "word6"), answer(9, "word10")];
+    assert!(check_quiz_answers(&mnemonic(), &quiz, &wrong).is_err());
+    // an empty quiz confirms nothing
+    let empty = BackupQuiz { positions: vec![] };
+    assert!(check_quiz_answers(&mnemonic(), &empty, &[]).is_err());
+}
@@ -0,0 +1,28 @@
+// helpers shared by the tests of the storage layer, each test file uses some of them
+#![allow(dead_code)]
+use modules::p2p_connection::new_client_id;
+use modules::p2p_connection::structs::{ChatMessage, MessageStatus};
+use modules::sqlite::{self, Database};
+
+// a path nobody else uses in the temporary directory, nothing is created there
+pub fn temporary_path(extension: &str) -> String {
+    let path = std::env::temp_dir().join(format!("chatuza-test-{}.{}", new_client_id(), extension));
+    path.to_str().unwrap().to_string()
+}
+
+pub fn temporary_database() -> Database {
+    sqlite::open_database_at(&temporary_path("db")).unwrap()
+}
+
+// a message received in `conversation_id`, sent `id` seconds after the same moment
+pub fn message(id: u64, conversation_id: &str, sender: &str, body: &str) -> ChatMessage {
+    ChatMessage {
+        id,
+        conversation_id: conversation_id.to_string(),
+        sender: sender.to_string(),
+        body: body.to_string(),
+        sent_at: 1_700_000_000 + id,
+        client_id: None,
+        status: MessageStatus::Delivered,
+    }
+}
@@ -0,0 +1,99 @@
+// the history on disk is only readable with the key of the wallet password
+mod common;
+
+use common::{message, temporary_path};
+use modules::p2p_connection::structs::ChatMessage;
+use modules::sqlite::{self, Database};
+use rusqlite::Connection;
+use std::fs;
+
+const ACCOUNT: &str = "4uQeVj5tqViQh7yWWGStvkEG1Zmhx6uasJtWCJziofM";
+const BODY: &str = "the plaintext nobody else should read";
+
+fn secret() -> ChatMessage {
+    message(1, "alice:bob", "bob", BODY)
+}
+
+fn has_the_message(database: &Database) -> bool {
+    let connection = database.connection().unwrap();
+    sqlite::get_message(&connection, 1)
+        .unwrap()
+        .map(|message| message.body)
+        == Some(BODY.to_string())
+}
+
+// what's on disk once every connection is closed
+fn raw_content(path: &str) -> Vec<u8> {
+    let mut content = fs::read(path).unwrap();
+    if let Ok(wal) = fs::read(format!("{}-wal", path)) {
+        content.extend(wal);
+    }
+    content
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+#[test]
+fn nothing_is_readable_on_disk() {
+    let path = temporary_path("db");
+    let database = sqlite::open_encrypted_database_at(&path, ACCOUNT, "password").unwrap();
+    sqlite::insert_message(&database.connection().unwrap(), &secret()).unwrap();
+    drop(database);
+
+    let content = raw_content(&path);
+    assert!(!content.starts_with(b"SQLite format 3\0"));
+    assert!(!contains(&content, BODY.as_bytes()));
+    // sqlite itself can't read it without the key
+    let plain = Connection::open(&path).unwrap();
+    assert!(plain
+        .query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))
+        .is_err());
+}
+
+#[test]
+fn wrong_password_gets_the_user_facing_error() {
+    let path = temporary_path("db");
+    drop(sqlite::open_encrypted_database_at(&path, ACCOUNT, "password").unwrap());
+
+    let error = match sqlite::open_encrypted_database_at(&path, ACCOUNT, "wrong") {
+        Ok(_) => panic!("the database opened with the wrong password"),
+        Err(e) => e,
+    };
+    assert!(error.contains("can't be decrypted with this password"));
+    assert!(error.contains(&path));
+}
+
+#[test]
+fn changed_password_opens_the_same_history() {
+    let path = temporary_path("db");
+    let database = sqlite::open_encrypted_database_at(&path, ACCOUNT, "old password").unwrap();
+    sqlite::insert_message(&database.connection().unwrap(), &secret()).unwrap();
+    database.change_password("new password").unwrap();
+    // the pool already uses the new key
+    assert!(has_the_message(&database));
+    drop(database);
+
+    assert!(sqlite::open_encrypted_database_at(&path, ACCOUNT, "old password").is_err());
+    let reopened = sqlite::open_encrypted_database_at(&path, ACCOUNT, "new password").unwrap();
+    assert!(has_the_message(&reopened));
+}
+
+#[test]
+fn history_from_before_the_encryption_gets_encrypted() {
+    let path = temporary_path("db");
+    let database = sqlite::open_database_at(&path).unwrap();
+    sqlite::insert_message(&database.connection().unwrap(), &secret()).unwrap();
+    drop(database);
+    assert!(raw_content(&path).starts_with(b"SQLite format 3\0"));
+
+    let database = sqlite::open_encrypted_database_at(&path, ACCOUNT, "password").unwrap();
+    assert!(has_the_message(&database));
+    drop(database);
+    let content = raw_content(&path);
+    assert!(!content.starts_with(b"SQLite format 3\0"));
+    assert!(!contains(&content, BODY.as_bytes()));
+}
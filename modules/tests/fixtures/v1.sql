@@ -0,0 +1,12 @@
+-- the local database as the outbox release left it, before schema_version
+CREATE TABLE IF NOT EXISTS outbox (
+    client_id TEXT PRIMARY KEY,
+    conversation_id TEXT NOT NULL,
+    body TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    state TEXT NOT NULL,
+    last_error TEXT
+);
+INSERT INTO outbox VALUES ('c-1', 'direct:alice:bob', 'still pending', 1700000000, 0, 'pending', NULL);
+INSERT INTO outbox VALUES ('c-2', 'direct:alice:bob', 'gave up', 1700000010, 5, 'failed', 'timed out');
@@ -0,0 +1,29 @@
+-- the local database as the receipts release left it, before schema_version
+CREATE TABLE IF NOT EXISTS outbox (
+    client_id TEXT PRIMARY KEY,
+    conversation_id TEXT NOT NULL,
+    body TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    state TEXT NOT NULL,
+    last_error TEXT
+);
+CREATE TABLE IF NOT EXISTS messages (
+    id INTEGER PRIMARY KEY,
+    conversation_id TEXT NOT NULL,
+    sender TEXT NOT NULL,
+    body TEXT NOT NULL,
+    sent_at INTEGER NOT NULL,
+    client_id TEXT,
+    status INTEGER NOT NULL DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS messages_conversation ON messages (conversation_id, id);
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+INSERT INTO outbox VALUES ('c-1', 'direct:alice:bob', 'still pending', 1700000000, 0, 'pending', NULL);
+INSERT INTO messages VALUES (1, 'direct:alice:bob', 'bob', 'hi alice', 1700000100, NULL, 0);
+INSERT INTO messages VALUES (2, 'direct:alice:bob', 'alice', 'hi bob', 1700000200, 'c-0', 0);
+INSERT INTO messages VALUES (3, 'direct:alice:carol', 'carol', 'hello', 1700000300, NULL, 0);
+INSERT INTO settings VALUES ('send_read_receipts', 'false');
@@ -0,0 +1,84 @@
+-- the local database as the search release left it, versioned and with the full-text index
+CREATE TABLE IF NOT EXISTS accounts (
+    pub_key TEXT PRIMARY KEY,
+    username TEXT,
+    display_name TEXT,
+    created_at INTEGER NOT NULL,
+    last_used_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS contacts (
+    username TEXT PRIMARY KEY,
+    display_name TEXT NOT NULL,
+    wallet_pubkey TEXT NOT NULL,
+    avatar_url TEXT,
+    added_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS conversations (
+    id TEXT PRIMARY KEY,
+    created_at INTEGER NOT NULL,
+    last_message_id INTEGER,
+    last_message_at INTEGER
+);
+CREATE TABLE IF NOT EXISTS outbox (
+    client_id TEXT PRIMARY KEY,
+    conversation_id TEXT NOT NULL,
+    body TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    state TEXT NOT NULL,
+    last_error TEXT
+);
+CREATE TABLE IF NOT EXISTS messages (
+    id INTEGER PRIMARY KEY,
+    conversation_id TEXT NOT NULL,
+    sender TEXT NOT NULL,
+    body TEXT NOT NULL,
+    sent_at INTEGER NOT NULL,
+    client_id TEXT,
+    status INTEGER NOT NULL DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS messages_conversation ON messages (conversation_id, id);
+CREATE TABLE IF NOT EXISTS attachments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    message_id INTEGER NOT NULL REFERENCES messages (id) ON DELETE CASCADE,
+    file_name TEXT NOT NULL,
+    mime_type TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    path TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS attachments_message ON attachments (message_id);
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS schema_version (
+    version INTEGER PRIMARY KEY,
+    applied_at INTEGER NOT NULL
+);
+CREATE VIRTUAL TABLE messages_fts USING fts5 (
+    body,
+    content = 'messages',
+    content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+CREATE TRIGGER messages_fts_insert AFTER INSERT ON messages BEGIN
+    INSERT INTO messages_fts (rowid, body) VALUES (new.id, new.body);
+END;
+CREATE TRIGGER messages_fts_delete AFTER DELETE ON messages BEGIN
+    INSERT INTO messages_fts (messages_fts, rowid, body) VALUES ('delete', old.id, old.body);
+END;
+CREATE TRIGGER messages_fts_update AFTER UPDATE OF body ON messages BEGIN
+    INSERT INTO messages_fts (messages_fts, rowid, body) VALUES ('delete', old.id, old.body);
+    INSERT INTO messages_fts (rowid, body) VALUES (new.id, new.body);
+END;
+INSERT INTO schema_version VALUES (1, 1700000000), (2, 1700000000), (3, 1700000000), (4, 1700000000);
+INSERT INTO accounts VALUES ('4uQeVj5tqViQh7yWWGStvkEG1Zmhx6uasJtWCJziofM', 'alice', 'Alice', 1700000000, 1700000500);
+INSERT INTO contacts VALUES ('bob', 'Bob', '8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR', NULL, 1700000050);
+INSERT INTO conversations VALUES ('direct:alice:bob', 1700000100, 2, 1700000200);
+INSERT INTO conversations VALUES ('direct:alice:carol', 1700000300, 3, 1700000300);
+INSERT INTO outbox VALUES ('c-1', 'direct:alice:bob', 'still pending', 1700000000, 0, 'pending', NULL);
+INSERT INTO messages VALUES (1, 'direct:alice:bob', 'bob', 'hi alice', 1700000100, NULL, 0);
+INSERT INTO messages VALUES (2, 'direct:alice:bob', 'alice', 'hi bob', 1700000200, 'c-0', 0);
+INSERT INTO messages VALUES (3, 'direct:alice:carol', 'carol', 'hello', 1700000300, NULL, 0);
+INSERT INTO attachments (message_id, file_name, mime_type, size, path) VALUES (1, 'cat.png', 'image/png', 2048, 'attachments/1/cat.png');
+INSERT INTO settings VALUES ('send_read_receipts', 'false');
@@ -0,0 +1,173 @@
+// each cluster only offers the funding it can back, and a transfer reports the one it used
+use modules::solana_wallet::plan_funding;
+use modules::structs::{Cluster, FundingPath, FundingPlan, FundingStrategy, NetworkSettings};
+use solana_sdk::pubkey::Pubkey;
+
+const CLUSTERS: [Cluster; 4] = [
+    Cluster::Mainnet,
+    Cluster::Testnet,
+    Cluster::Devnet,
+    Cluster::Localnet,
+];
+
+fn network(cluster: Cluster, funding: FundingStrategy) -> NetworkSettings {
+    NetworkSettings { cluster, funding }
+}
+
+// the plan for a recipient missing the wallet funding, the token account, both or neither
+fn plans(network: &NetworkSettings) -> [Result<FundingPlan, String>; 4] {
+    let mint = Pubkey::new_unique();
+    [
+        plan_funding(network, &mint, false, false),
+        plan_funding(network, &mint, true, false),
+        plan_funding(network, &mint, false, true),
+        plan_funding(network, &mint, true, true),
+    ]
+}
+
+fn plan(
+    path: FundingPath,
+    fund_wallet: bool,
+    backend_creates_account: bool,
+    sender_creates_account: bool,
+) -> FundingPlan {
+    FundingPlan {
+        path,
+        fund_wallet,
+        backend_creates_account,
+        sender_creates_account,
+    }
+}
+
+#[test]
+fn clusters_only_support_the_funding_they_can_back() {
+    for cluster in CLUSTERS {
+        assert!(cluster.supports(cluster.default_funding()), "{:?}", cluster);
+        assert!(cluster.supports(FundingStrategy::SenderPaysRent));
+        assert!(cluster.supports(FundingStrategy::Disabled));
+        assert_eq!(
+            cluster.supports(FundingStrategy::BackendFaucet),
+            cluster == Cluster::Devnet
+        );
+        assert_eq!(
+            cluster.supports(FundingStrategy::Airdrop),
+            cluster != Cluster::Mainnet
+        );
+    }
+    assert_eq!(
+        Cluster::Mainnet.default_funding(),
+        FundingStrategy::Disabled
+    );
+    assert_eq!(
+        Cluster::Devnet.default_funding(),
+        FundingStrategy::BackendFaucet
+    );
+    assert_eq!(Cluster::Testnet.default_funding(), FundingStrategy::Airdrop);
+    assert_eq!(
+        Cluster::Localnet.default_funding(),
+        FundingStrategy::Airdrop
+    );
+}
+
+#[test]
+fn cluster_names_parse() {
+    for cluster in CLUSTERS {
+        assert_eq!(cluster.as_str().parse::<Cluster>().unwrap(), cluster);
+    }
+    assert_eq!("mainnet-beta".parse::<Cluster>().unwrap(), Cluster::Mainnet);
+    assert_eq!("localhost".parse::<Cluster>().unwrap(), Cluster::Localnet);
+    assert_eq!(" DevNet ".parse::<Cluster>().unwrap(), Cluster::Devnet);
+    let error = "solana".parse::<Cluster>().unwrap_err();
+    assert!(error.contains("not a solana cluster"), "{}", error);
+}
+
+#[test]
+fn backend_faucet_funds_and_creates_everything_missing() {
+    let [nothing, wallet, account, both] =
+        plans(&network(Cluster::Devnet, FundingStrategy::BackendFaucet));
+
+    assert_eq!(
+        nothing.unwrap(),
+        plan(FundingPath::NotNeeded, false, false, false)
+    );
+    assert_eq!(
+        wallet.unwrap(),
+        plan(FundingPath::BackendFaucet, true, false, false)
+    );
+    assert_eq!(
+        account.unwrap(),
+        plan(FundingPath::BackendFaucet, false, true, false)
+    );
+    assert_eq!(
+        both.unwrap(),
+        plan(FundingPath::BackendFaucet, true, true, false)
+    );
+}
+
+#[test]
+fn airdrop_funds_the_wallet_and_the_sender_creates_the_account() {
+    let [nothing, wallet, account, both] =
+        plans(&network(Cluster::Testnet, FundingStrategy::Airdrop));
+
+    assert_eq!(
+        nothing.unwrap(),
+        plan(FundingPath::NotNeeded, false, false, false)
+    );
+    assert_eq!(
+        wallet.unwrap(),
+        plan(FundingPath::Airdrop, true, false, false)
+    );
+    // a funded wallet only needs the account, paid by the sender
+    assert_eq!(
+        account.unwrap(),
+        plan(FundingPath::SenderPaidRent, false, false, true)
+    );
+    assert_eq!(both.unwrap(), plan(FundingPath::Airdrop, true, false, true));
+}
+
+#[test]
+fn sender_pays_the_rent_and_never_funds_the_wallet() {
+    let [nothing, wallet, account, both] =
+        plans(&network(Cluster::Mainnet, FundingStrategy::SenderPaysRent));
+
+    assert_eq!(
+        nothing.unwrap(),
+        plan(FundingPath::NotNeeded, false, false, false)
+    );
+    // the token account holds the tokens, the wallet needs no sol for them
+    assert_eq!(
+        wallet.unwrap(),
+        plan(FundingPath::NotNeeded, false, false, false)
+    );
+    assert_eq!(
+        account.unwrap(),
+        plan(FundingPath::SenderPaidRent, false, false, true)
+    );
+    assert_eq!(
+        both.unwrap(),
+        plan(FundingPath::SenderPaidRent, false, false, true)
+    );
+}
+
+#[test]
+fn disabled_funding_refuses_a_missing_token_account() {
+    let [nothing, wallet, account, both] =
+        plans(&network(Cluster::Mainnet, FundingStrategy::Disabled));
+
+    assert_eq!(
+        nothing.unwrap(),
+        plan(FundingPath::NotNeeded, false, false, false)
+    );
+    assert_eq!(
+        wallet.unwrap(),
+        plan(FundingPath::NotNeeded, false, false, false)
+    );
+    for refused in [account, both] {
+        let error = refused.unwrap_err();
+        assert!(
+            error.contains("funding it is disabled on mainnet"),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,355 @@
+// the transcripts show the history as written, and a JSON export imported into another database
+// brings the same history back, once
+mod common;
+
+use common::{message, temporary_database, temporary_path};
+use modules::export::{self, structs::ExportFormat};
+use modules::import;
+use modules::p2p_connection::direct_conversation_id;
+use modules::server_interaction::structs::UserProfile;
+use modules::sqlite::{self, structs::Attachment, Database};
+use rusqlite::Connection;
+use std::fs;
+
+fn alice() -> UserProfile {
+    UserProfile {
+        username: "alice".to_string(),
+        display_name: "Alice".to_string(),
+        wallet_pubkey: "4uQeVj5tqViQh7yWWGStvkEG1Zmhx6uasJtWCJziofM".to_string(),
+        avatar_url: None,
+    }
+}
+
+// the storage layer fixture, with a nickname for bob
+fn exported_history() -> String {
+    let path = temporary_path("db");
+    Connection::open(&path)
+        .unwrap()
+        .execute_batch(include_str!("fixtures/v3.sql"))
+        .unwrap();
+    let database = sqlite::open_database_at(&path).unwrap();
+    let connection = database.connection().unwrap();
+    sqlite::update_contact_details(&connection, "bob", Some("Bobby"), None, None).unwrap();
+    let export_path = temporary_path("json");
+    export::export_history(
+        &connection,
+        &alice(),
+        None,
+        ExportFormat::Json,
+        &export_path,
+        false,
+    )
+    .unwrap();
+    export_path
+}
+
+// a new device where bob is already a contact
+fn new_device() -> Database {
+    let database = temporary_database();
+    let bob = UserProfile {
+        username: "bob".to_string(),
+        display_name: "Bob".to_string(),
+        wallet_pubkey: "8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR".to_string(),
+        avatar_url: None,
+    };
+    sqlite::save_contact(&database.connection().unwrap(), &bob).unwrap();
+    database
+}
+
+#[test]
+fn import_brings_back_the_messages_and_the_contact_details() {
+    let export_path = exported_history();
+    let database = new_device();
+    let mut connection = database.connection().unwrap();
+    let report = import::import_history(&mut connection, "alice", &export_path, false).unwrap();
+    assert_eq!(report.conversations, 2);
+    assert_eq!(report.messages_added, 3);
+    assert_eq!(report.contacts_updated, ["bob"]);
+    // the attachments weren't bundled
+    assert_eq!(report.attachments_missing, ["cat.png"]);
+
+    let messages = sqlite::list_messages(&connection, "direct:alice:bob").unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[1].client_id.as_deref(), Some("c-0"));
+    let bob = sqlite::get_address_book_entry(&connection, "bob")
+        .unwrap()
+        .unwrap();
+    assert_eq!(bob.nickname.as_deref(), Some("Bobby"));
+    assert_eq!(
+        sqlite::search_messages(&connection, "hello", None, 10)
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn importing_twice_adds_nothing() {
+    let export_path = exported_history();
+    let database = new_device();
+    let mut connection = database.connection().unwrap();
+    import::import_history(&mut connection, "alice", &export_path, false).unwrap();
+    let report = import::import_history(&mut connection, "alice", &export_path, false).unwrap();
+    assert_eq!(report.messages_added, 0);
+    assert_eq!(report.messages_already_here, 3);
+    assert!(report.contacts_updated.is_empty());
+}
+
+#[test]
+fn dry_run_reports_without_changing_anything() {
+    let export_path = exported_history();
+    let database = new_device();
+    let mut connection = database.connection().unwrap();
+    let report = import::import_history(&mut connection, "alice", &export_path, true).unwrap();
+    assert!(report.dry_run);
+    assert_eq!(report.messages_added, 3);
+    assert!(sqlite::list_conversations(&connection, "alice")
+        .unwrap()
+        .is_empty());
+    let bob = sqlite::get_address_book_entry(&connection, "bob")
+        .unwrap()
+        .unwrap();
+    assert_eq!(bob.nickname, None);
+}
+
+#[test]
+fn history_of_another_user_is_refused() {
+    let export_path = exported_history();
+    let database = new_device();
+    let mut connection = database.connection().unwrap();
+    let error = import::import_history(&mut connection, "bob", &export_path, true).unwrap_err();
+    assert!(error.contains("belongs to @alice"), "{}", error);
+}
+
+#[test]
+fn imported_addresses_have_to_be_verified_again() {
+    let export_path = exported_history();
+    // the export says bob's cold wallet is verified, this device never checked it
+    let mut export: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&export_path).unwrap()).unwrap();
+    export["contacts"][0]["addresses"]
+        .as_array_mut()
+        .unwrap()
+        .push(serde_json::json!({
+            "id": 99,
+            "username": "bob",
+            "address": "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin",
+            "label": "cold wallet",
+            "verified_at": 1700000000u64,
+            "added_at": 1700000000u64
+        }));
+    fs::write(&export_path, export.to_string()).unwrap();
+
+    let database = new_device();
+    let mut connection = database.connection().unwrap();
+    import::import_history(&mut connection, "alice", &export_path, false).unwrap();
+    let bob = sqlite::get_address_book_entry(&connection, "bob")
+        .unwrap()
+        .unwrap();
+    let cold = bob
+        .addresses
+        .iter()
+        .find(|address| address.label == "cold wallet")
+        .unwrap();
+    assert_eq!(cold.verified_at, None);
+    assert!(sqlite::payment_address(&connection, "bob", None).is_err());
+    assert!(sqlite::payment_address(&connection, "bob", Some(cold.id)).is_err());
+}
+
+// a history with one message from bob, written by someone who knows some html
+fn history_with_markup() -> Database {
+    let database = new_device();
+    let connection = database.connection().unwrap();
+    let message = message(
+        1,
+        &direct_conversation_id("alice", "bob"),
+        "bob",
+        "<script>alert('hi')</script> & \"bye\"",
+    );
+    sqlite::insert_message(&connection, &message).unwrap();
+    sqlite::update_contact_details(&connection, "bob", Some("<b>Bob</b>"), None, None).unwrap();
+    drop(connection);
+    database
+}
+
+#[test]
+fn html_transcript_escapes_what_was_written() {
+    let database = history_with_markup();
+    let connection = database.connection().unwrap();
+    let path = temporary_path("html");
+    export::export_history(
+        &connection,
+        &alice(),
+        None,
+        ExportFormat::Html,
+        &path,
+        false,
+    )
+    .unwrap();
+
+    let html = fs::read_to_string(&path).unwrap();
+    assert!(!html.contains("<script>"));
+    assert!(!html.contains("<b>Bob</b>"));
+    assert!(html.contains("&lt;script&gt;alert(&#39;hi&#39;)&lt;/script&gt; &amp; &quot;bye&quot;"));
+    assert!(html.contains("&lt;b&gt;Bob&lt;/b&gt;"));
+}
+
+#[test]
+fn text_transcript_keeps_the_message_as_written() {
+    let database = history_with_markup();
+    let connection = database.connection().unwrap();
+    let path = temporary_path("txt");
+    export::export_history(
+        &connection,
+        &alice(),
+        None,
+        ExportFormat::Text,
+        &path,
+        false,
+    )
+    .unwrap();
+
+    let text = fs::read_to_string(&path).unwrap();
+    assert!(text.contains("<script>alert('hi')</script> & \"bye\""));
+    assert!(!text.contains("&lt;"));
+}
+
+#[test]
+fn missing_attachments_are_reported() {
+    let database = history_with_markup();
+    let connection = database.connection().unwrap();
+    let kept = temporary_path("png");
+    fs::write(&kept, b"meow").unwrap();
+    let gone = temporary_path("png");
+    for (file_name, path) in [("cat.png", &kept), ("dog.png", &gone)] {
+        let attachment = Attachment {
+            id: 0,
+            message_id: 1,
+            file_name: file_name.to_string(),
+            mime_type: "image/png".to_string(),
+            size: 4,
+            path: path.clone(),
+        };
+        sqlite::insert_attachment(&connection, &attachment).unwrap();
+    }
+
+    let path = temporary_path("json");
+    let report =
+        export::export_history(&connection, &alice(), None, ExportFormat::Json, &path, true)
+            .unwrap();
+    assert_eq!(report.attachments, 1);
+    assert_eq!(report.missing_attachments, [gone]);
+    let archive_path = report.attachments_path.unwrap();
+    assert_eq!(archive_path, export::attachments_archive_path(&path));
+    let mut archive = zip::ZipArchive::new(fs::File::open(&archive_path).unwrap()).unwrap();
+    assert_eq!(archive.len(), 1);
+    assert_eq!(archive.by_index(0).unwrap().name(), "1/cat.png");
+
+    // nothing to bundle, no archive
+    fs::remove_file(&kept).unwrap();
+    let path = temporary_path("json");
+    let report =
+        export::export_history(&connection, &alice(), None, ExportFormat::Json, &path, true)
+            .unwrap();
+    assert_eq!(report.attachments, 0);
+    assert_eq!(report.missing_attachments.len(), 2);
+    assert_eq!(report.attachments_path, None);
+}
+
+// an export of bob's message with the cat.png attachment, and a directory for the attachments
+// of the importing device
+fn exported_with_attachment() -> (String, String) {
+    let database = history_with_markup();
+    let connection = database.connection().unwrap();
+    let cat = temporary_path("png");
+    fs::write(&cat, b"meow").unwrap();
+    let attachment = Attachment {
+        id: 0,
+        message_id: 1,
+        file_name: "cat.png".to_string(),
+        mime_type: "image/png".to_string(),
+        size: 4,
+        path: cat,
+    };
+    sqlite::insert_attachment(&connection, &attachment).unwrap();
+    let export_path = temporary_path("json");
+    export::export_history(
+        &connection,
+        &alice(),
+        None,
+        ExportFormat::Json,
+        &export_path,
+        true,
+    )
+    .unwrap();
+    let attachments_dir = temporary_path("attachments");
+    fs::create_dir(&attachments_dir).unwrap();
+    (export_path, attachments_dir)
+}
+
+fn entries(directory: &str) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort_unstable();
+    names
+}
+
+#[test]
+fn imported_attachments_never_replace_a_file() {
+    let (export_path, attachments_dir) = exported_with_attachment();
+    let message_dir = format!("{}/1", attachments_dir);
+    fs::create_dir(&message_dir).unwrap();
+    fs::write(format!("{}/cat.png", message_dir), b"already here").unwrap();
+
+    let database = new_device();
+    let mut connection = database.connection().unwrap();
+    let report = import::import_history_in(
+        &mut connection,
+        &attachments_dir,
+        "alice",
+        &export_path,
+        false,
+    )
+    .unwrap();
+    assert_eq!(report.attachments_added, 1);
+    let imported = &sqlite::list_attachments(&connection, 1).unwrap()[0];
+    assert_eq!(imported.file_name, "cat.png");
+    assert_eq!(fs::read(&imported.path).unwrap(), b"meow");
+    assert_eq!(
+        fs::read(format!("{}/cat.png", message_dir)).unwrap(),
+        b"already here"
+    );
+    assert_eq!(entries(&message_dir), ["cat (1).png", "cat.png"]);
+    // nothing is left staged
+    assert_eq!(entries(&attachments_dir), ["1"]);
+    fs::remove_dir_all(&attachments_dir).unwrap();
+}
+
+#[test]
+fn failed_import_leaves_no_attachment_behind() {
+    let (export_path, attachments_dir) = exported_with_attachment();
+    let database = new_device();
+    let mut connection = database.connection().unwrap();
+    // the contact details are merged after the attachments are written
+    connection
+        .execute_batch(
+            "CREATE TRIGGER full_disk BEFORE UPDATE ON contacts
+             BEGIN SELECT RAISE(ABORT, 'database or disk is full'); END;",
+        )
+        .unwrap();
+
+    let error = import::import_history_in(
+        &mut connection,
+        &attachments_dir,
+        "alice",
+        &export_path,
+        false,
+    )
+    .unwrap_err();
+    assert!(error.contains("disk is full"), "{}", error);
+    assert!(sqlite::get_message(&connection, 1).unwrap().is_none());
+    assert!(entries(&attachments_dir).is_empty());
+    fs::remove_dir_all(&attachments_dir).unwrap();
+}
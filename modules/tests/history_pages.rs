@@ -0,0 +1,78 @@
+// the chat loads its history a page at a time, has_more says whether to ask for another
+mod common;
+
+use common::{message, temporary_database};
+use modules::sqlite::{self, structs::MessagePage};
+use rusqlite::Connection;
+
+const CONVERSATION: &str = "alice:bob";
+
+// `count` messages with bob, ids 10, 20 and so on, and one in another conversation in between
+fn history(connection: &Connection, count: u64) {
+    for id in 1..=count {
+        let body = format!("message {}", id);
+        sqlite::insert_message(connection, &message(id * 10, CONVERSATION, "bob", &body)).unwrap();
+    }
+    let elsewhere = message(15, "alice:carol", "carol", "not in this conversation");
+    sqlite::insert_message(connection, &elsewhere).unwrap();
+}
+
+fn ids(page: &MessagePage) -> Vec<u64> {
+    page.messages.iter().map(|message| message.id).collect()
+}
+
+#[test]
+fn pages_before_walk_back_to_the_first_message() {
+    let database = temporary_database();
+    let connection = database.connection().unwrap();
+    history(&connection, 5);
+
+    let latest = sqlite::list_messages_before(&connection, CONVERSATION, None, 2).unwrap();
+    assert_eq!(ids(&latest), vec![40, 50]);
+    assert!(latest.has_more);
+    let older = sqlite::list_messages_before(&connection, CONVERSATION, Some(40), 2).unwrap();
+    assert_eq!(ids(&older), vec![20, 30]);
+    assert!(older.has_more);
+    let oldest = sqlite::list_messages_before(&connection, CONVERSATION, Some(20), 2).unwrap();
+    assert_eq!(ids(&oldest), vec![10]);
+    assert!(!oldest.has_more);
+}
+
+#[test]
+fn exactly_limit_messages_left_is_the_last_page() {
+    let database = temporary_database();
+    let connection = database.connection().unwrap();
+    history(&connection, 4);
+
+    let all = sqlite::list_messages_before(&connection, CONVERSATION, None, 4).unwrap();
+    assert_eq!(ids(&all), vec![10, 20, 30, 40]);
+    assert!(!all.has_more);
+    let before = sqlite::list_messages_before(&connection, CONVERSATION, Some(30), 2).unwrap();
+    assert_eq!(ids(&before), vec![10, 20]);
+    assert!(!before.has_more);
+    let after = sqlite::list_messages_after(&connection, CONVERSATION, 20, 2).unwrap();
+    assert_eq!(ids(&after), vec![30, 40]);
+    assert!(!after.has_more);
+    // one more past the limit
+    let after = sqlite::list_messages_after(&connection, CONVERSATION, 10, 2).unwrap();
+    assert_eq!(ids(&after), vec![20, 30]);
+    assert!(after.has_more);
+}
+
+#[test]
+fn pages_after_an_id_skip_it() {
+    let database = temporary_database();
+    let connection = database.connection().unwrap();
+    history(&connection, 3);
+
+    // from a search result in the middle of the history, forward
+    let after = sqlite::list_messages_after(&connection, CONVERSATION, 10, 10).unwrap();
+    assert_eq!(ids(&after), vec![20, 30]);
+    assert!(!after.has_more);
+    let nothing = sqlite::list_messages_after(&connection, CONVERSATION, 30, 10).unwrap();
+    assert!(nothing.messages.is_empty());
+    assert!(!nothing.has_more);
+    let empty = sqlite::list_messages_before(&connection, "alice:dave", None, 10).unwrap();
+    assert!(empty.messages.is_empty());
+    assert!(!empty.has_more);
+}
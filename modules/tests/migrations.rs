@@ -0,0 +1,157 @@
+// every schema a released app left on disk is upgraded to the current one without losing rows
+mod common;
+
+use common::temporary_path;
+use modules::migrations::{backup_path, schema_version, SCHEMA_VERSION};
+use modules::sqlite::{self, Database};
+use rusqlite::Connection;
+use std::path::Path;
+
+// (version, database as that release wrote it)
+const FIXTURES: &[(u32, &str)] = &[
+    (1, include_str!("fixtures/v1.sql")),
+    (2, include_str!("fixtures/v2.sql")),
+    (3, include_str!("fixtures/v3.sql")),
+    (4, include_str!("fixtures/v4.sql")),
+    (5, include_str!("fixtures/v5.sql")),
+];
+
+fn fixture(sql: &str) -> String {
+    let path = temporary_path("db");
+    Connection::open(&path).unwrap().execute_batch(sql).unwrap();
+    path
+}
+
+fn upgraded(version: u32) -> (String, Database) {
+    let (_, sql) = FIXTURES.iter().find(|(v, _)| *v == version).unwrap();
+    let path = fixture(sql);
+    let database = sqlite::open_database_at(&path).unwrap();
+    (path, database)
+}
+
+#[test]
+fn every_fixture_reaches_the_current_version() {
+    for (version, _) in FIXTURES {
+        let (path, database) = upgraded(*version);
+        let connection = database.connection().unwrap();
+        assert_eq!(schema_version(&connection).unwrap(), SCHEMA_VERSION);
+        // the outbox exists since the first version
+        let outbox = sqlite::list_outbox(&connection).unwrap();
+        assert_eq!(outbox[0].body, "still pending", "from version {}", version);
+        let upgraded = *version < SCHEMA_VERSION;
+        assert_eq!(Path::new(&backup_path(&path, *version)).exists(), upgraded);
+    }
+}
+
+#[test]
+fn history_keeps_its_messages_and_gains_conversations() {
+    let (_, database) = upgraded(2);
+    let connection = database.connection().unwrap();
+    let messages = sqlite::list_messages(&connection, "direct:alice:bob").unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[1].client_id.as_deref(), Some("c-0"));
+    assert_eq!(
+        sqlite::get_setting(&connection, "send_read_receipts").unwrap(),
+        Some("false".to_string())
+    );
+
+    // the conversations are made from the messages already there
+    let conversations = sqlite::list_conversations(&connection, "alice").unwrap();
+    let ids: Vec<&str> = conversations.iter().map(|c| c.id.as_str()).collect();
+    assert_eq!(ids, ["direct:alice:carol", "direct:alice:bob"]);
+    assert_eq!(conversations[1].last_message_id, Some(2));
+    assert_eq!(conversations[1].unread, 1);
+
+    // and the history written before the index is searchable
+    let hits = sqlite::search_messages(&connection, "hel", None, 10).unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].message.conversation_id, "direct:alice:carol");
+}
+
+#[test]
+fn storage_layer_schema_keeps_its_rows() {
+    let (_, database) = upgraded(3);
+    let connection = database.connection().unwrap();
+    assert_eq!(sqlite::list_accounts(&connection).unwrap().len(), 1);
+    assert_eq!(
+        sqlite::list_contacts(&connection).unwrap()[0].username,
+        "bob"
+    );
+    assert_eq!(sqlite::list_attachments(&connection, 1).unwrap().len(), 1);
+    assert_eq!(
+        sqlite::list_conversations(&connection, "alice")
+            .unwrap()
+            .len(),
+        2
+    );
+}
+
+#[test]
+fn contacts_gain_their_profile_address_unverified() {
+    let (_, database) = upgraded(4);
+    let connection = database.connection().unwrap();
+    let book = sqlite::list_address_book(&connection).unwrap();
+    assert_eq!(book[0].addresses.len(), 1);
+    assert_eq!(book[0].addresses[0].address, book[0].profile.wallet_pubkey);
+    assert_eq!(book[0].addresses[0].verified_at, None);
+    // nothing is paid before the user checks the address
+    assert!(sqlite::payment_address(&connection, "bob", None).is_err());
+}
+
+#[test]
+fn address_book_keeps_what_the_user_wrote() {
+    let (_, database) = upgraded(5);
+    let connection = database.connection().unwrap();
+    let bob = sqlite::get_address_book_entry(&connection, "bob")
+        .unwrap()
+        .unwrap();
+    assert_eq!(bob.nickname.as_deref(), Some("Bobby"));
+    assert_eq!(bob.addresses[0].verified_at, Some(1700000060));
+    // conversations start without a timer
+    assert_eq!(
+        sqlite::get_retention(&connection, "direct:alice:bob").unwrap(),
+        None
+    );
+}
+
+#[test]
+fn backup_is_the_database_before_the_upgrade() {
+    let (path, _database) = upgraded(1);
+    let backup = Connection::open(backup_path(&path, 1)).unwrap();
+    assert_eq!(schema_version(&backup).unwrap(), 1);
+    let outbox: u32 = backup
+        .query_row("SELECT count(*) FROM outbox", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(outbox, 2);
+}
+
+#[test]
+fn new_database_starts_at_the_current_version_without_backup() {
+    let path = temporary_path("db");
+    let database = sqlite::open_database_at(&path).unwrap();
+    assert_eq!(
+        schema_version(&database.connection().unwrap()).unwrap(),
+        SCHEMA_VERSION
+    );
+    assert!(!Path::new(&backup_path(&path, 0)).exists());
+    drop(database);
+
+    // opening it again changes nothing
+    sqlite::open_database_at(&path).unwrap();
+    assert!(!Path::new(&backup_path(&path, SCHEMA_VERSION)).exists());
+}
+
+#[test]
+fn database_from_a_newer_app_is_refused() {
+    let path = temporary_path("db");
+    drop(sqlite::open_database_at(&path).unwrap());
+    Connection::open(&path)
+        .unwrap()
+        .execute(
+            "INSERT INTO schema_version (version, applied_at) VALUES (?1, 0)",
+            [SCHEMA_VERSION + 1],
+        )
+        .unwrap();
+    let error = sqlite::open_database_at(&path).unwrap_err();
+    assert!(error.contains("newer version of chatuza"), "{}", error);
+}
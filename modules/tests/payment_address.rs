@@ -0,0 +1,108 @@
+// a payment only goes to an address of the contact the user verified
+mod common;
+
+use common::temporary_database;
+use modules::server_interaction::structs::UserProfile;
+use modules::sqlite::{self, structs::ContactAddress};
+use rusqlite::Connection;
+use solana_sdk::signer::{keypair::Keypair, Signer};
+
+fn new_address() -> String {
+    Keypair::new().pubkey().to_string()
+}
+
+fn bob(wallet_pubkey: &str) -> UserProfile {
+    UserProfile {
+        username: "bob".to_string(),
+        display_name: "Bob".to_string(),
+        wallet_pubkey: wallet_pubkey.to_string(),
+        avatar_url: None,
+    }
+}
+
+fn address_of(connection: &Connection, address: &str) -> ContactAddress {
+    sqlite::get_address_book_entry(connection, "bob")
+        .unwrap()
+        .unwrap()
+        .addresses
+        .into_iter()
+        .find(|known| known.address == address)
+        .unwrap()
+}
+
+#[test]
+fn changed_wallet_address_is_refused_until_verified() {
+    let database = temporary_database();
+    let connection = database.connection().unwrap();
+    let (first, second) = (new_address(), new_address());
+    sqlite::save_contact(&connection, &bob(&first)).unwrap();
+    let verified = address_of(&connection, &first);
+    sqlite::verify_contact_address(&connection, verified.id).unwrap();
+    assert_eq!(
+        sqlite::payment_address(&connection, "bob", None).unwrap(),
+        first
+    );
+
+    sqlite::save_contact(&connection, &bob(&second)).unwrap();
+    let error = sqlite::payment_address(&connection, "bob", None).unwrap_err();
+    assert!(error.contains("changed"), "{}", error);
+    // the address checked before can still be picked
+    assert_eq!(
+        sqlite::payment_address(&connection, "bob", Some(verified.id)).unwrap(),
+        first
+    );
+
+    sqlite::verify_contact_address(&connection, address_of(&connection, &second).id).unwrap();
+    assert_eq!(
+        sqlite::payment_address(&connection, "bob", None).unwrap(),
+        second
+    );
+}
+
+#[test]
+fn verified_profile_address_comes_first() {
+    let database = temporary_database();
+    let connection = database.connection().unwrap();
+    let (first, second) = (new_address(), new_address());
+    sqlite::save_contact(&connection, &bob(&first)).unwrap();
+    // given by the user, so verified, and older than the new profile address
+    let manual = new_address();
+    sqlite::add_contact_address(&connection, "bob", &manual, "savings").unwrap();
+    assert_eq!(
+        sqlite::payment_address(&connection, "bob", None).unwrap(),
+        manual
+    );
+
+    sqlite::save_contact(&connection, &bob(&second)).unwrap();
+    sqlite::verify_contact_address(&connection, address_of(&connection, &second).id).unwrap();
+    assert_eq!(
+        sqlite::payment_address(&connection, "bob", None).unwrap(),
+        second
+    );
+}
+
+#[test]
+fn unverified_address_is_refused_even_when_picked() {
+    let database = temporary_database();
+    let connection = database.connection().unwrap();
+    let profile_address = new_address();
+    sqlite::save_contact(&connection, &bob(&profile_address)).unwrap();
+
+    let unverified = address_of(&connection, &profile_address);
+    assert!(unverified.verified_at.is_none());
+    let error = sqlite::payment_address(&connection, "bob", Some(unverified.id)).unwrap_err();
+    assert!(error.contains("isn't verified"), "{}", error);
+    // nor can an address of someone else be picked
+    assert!(sqlite::payment_address(&connection, "bob", Some(unverified.id + 100)).is_err());
+}
+
+#[test]
+fn nothing_verified_nothing_paid() {
+    let database = temporary_database();
+    let connection = database.connection().unwrap();
+    sqlite::save_contact(&connection, &bob(&new_address())).unwrap();
+
+    let error = sqlite::payment_address(&connection, "bob", None).unwrap_err();
+    assert!(error.contains("no address of"), "{}", error);
+    assert!(sqlite::payment_address(&connection, "carol", None).is_err());
+}
@@ -0,0 +1,66 @@
+// each profile keeps its files in its own directory, the default one in the root directory
+use modules::p2p_connection::new_client_id;
+use modules::profiles::{self, DEFAULT_PROFILE};
+use std::path::Path;
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn profiles_keep_their_files_apart() {
+    let root = std::env::temp_dir().join(format!("chatuza-profiles-{}", new_client_id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let root_path = root.to_str().unwrap();
+
+    let listed = profiles::list_profiles_in(root_path).unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].name, DEFAULT_PROFILE);
+    profiles::open_profile_in(root_path, DEFAULT_PROFILE).unwrap();
+    assert_eq!(
+        Path::new(&profiles::data_path("chatuza.db")),
+        root.join("chatuza.db")
+    );
+
+    profiles::open_profile_in(root_path, "alice-test").unwrap();
+    let directory = root.join("profiles").join("alice-test");
+    assert!(directory.is_dir());
+    assert_eq!(
+        Path::new(&profiles::data_path("chatuza.db")),
+        directory.join("chatuza.db")
+    );
+    std::fs::write(profiles::data_path("sec.json"), "{}").unwrap();
+
+    profiles::open_profile_in(root_path, DEFAULT_PROFILE).unwrap();
+    assert_eq!(
+        Path::new(&profiles::data_path("chatuza.db")),
+        root.join("chatuza.db")
+    );
+    let listed = profiles::list_profiles_in(root_path).unwrap();
+    let names: Vec<&str> = listed.iter().map(|profile| profile.name.as_str()).collect();
+    assert_eq!(names, vec![DEFAULT_PROFILE, "alice-test"]);
+    assert!(!listed[0].has_wallet);
+    assert!(listed[1].has_wallet);
+
+    // a name can't reach outside the profiles
+    assert!(profiles::open_profile_in(root_path, "../elsewhere").is_err());
+    assert!(profiles::open_profile_in(root_path, "").is_err());
+    assert!(!root.join("elsewhere").exists());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn profile_flag_is_read_from_the_command_line() {
+    assert_eq!(profiles::profile_from_args(&args(&["chatuza"])), Ok(None));
+    assert_eq!(
+        profiles::profile_from_args(&args(&["chatuza", "--profile", "bob"])),
+        Ok(Some("bob".to_string()))
+    );
+    assert_eq!(
+        profiles::profile_from_args(&args(&["chatuza", "--profile=bob"])),
+        Ok(Some("bob".to_string()))
+    );
+    assert!(profiles::profile_from_args(&args(&["chatuza", "--profile"])).is_err());
+    assert!(profiles::profile_from_args(&args(&["chatuza", "--profile", "a/b"])).is_err());
+}
@@ -0,0 +1,29 @@
+// a new run resumes from the last acknowledged message, whatever else the history holds
+mod common;
+
+use common::{message, temporary_database};
+use modules::p2p_connection::{resume_point, LAST_ACKED_SETTING};
+use modules::sqlite;
+
+#[test]
+fn saved_ack_wins_over_newer_local_messages() {
+    let database = temporary_database();
+    let connection = database.connection().unwrap();
+    // our own message the server accepted, or one brought in by an import
+    sqlite::insert_message(&connection, &message(50, "alice:bob", "alice", "sent")).unwrap();
+    sqlite::set_setting(&connection, LAST_ACKED_SETTING, "30").unwrap();
+
+    assert_eq!(sqlite::last_message_id(&connection).unwrap(), Some(50));
+    // whatever came in between 30 and 50 is asked for again
+    assert_eq!(resume_point(&connection).unwrap(), Some(30));
+}
+
+#[test]
+fn newest_message_without_a_saved_ack() {
+    let database = temporary_database();
+    let connection = database.connection().unwrap();
+    assert_eq!(resume_point(&connection).unwrap(), None);
+
+    sqlite::insert_message(&connection, &message(50, "alice:bob", "bob", "kept")).unwrap();
+    assert_eq!(resume_point(&connection).unwrap(), Some(50));
+}
@@ -0,0 +1,101 @@
+// messages go once their timer runs out, never sooner than the minimum, and the purge keeps
+// going past an attachment it can't erase
+mod common;
+
+use common::{message, temporary_database, temporary_path};
+use modules::p2p_connection::structs::RetentionUpdate;
+use modules::retention::{self, MIN_RETENTION_SECS};
+use modules::sqlite::{self, structs::Attachment};
+use std::fs;
+
+const CONVERSATION: &str = "alice:bob";
+
+fn timer(retention_secs: u64, set_at: u64) -> RetentionUpdate {
+    RetentionUpdate {
+        conversation_id: CONVERSATION.to_string(),
+        retention_secs: Some(retention_secs),
+        set_by: "bob".to_string(),
+        set_at,
+    }
+}
+
+fn attach(connection: &rusqlite::Connection, message_id: u64, path: &str) {
+    let attachment = Attachment {
+        id: 0,
+        message_id,
+        file_name: "cat.png".to_string(),
+        mime_type: "image/png".to_string(),
+        size: 4,
+        path: path.to_string(),
+    };
+    sqlite::insert_attachment(connection, &attachment).unwrap();
+}
+
+#[test]
+fn every_attachment_is_erased_even_after_a_failure() {
+    let database = temporary_database();
+    let mut connection = database.connection().unwrap();
+    // a directory can't be opened for writing, its erasure fails
+    let stuck = temporary_path("dir");
+    fs::create_dir(&stuck).unwrap();
+    let erased = temporary_path("png");
+    fs::write(&erased, b"meow").unwrap();
+    for id in 1..=3 {
+        sqlite::insert_message(
+            &connection,
+            &message(id, "alice:bob", "bob", "disappearing"),
+        )
+        .unwrap();
+    }
+    attach(&connection, 1, &stuck);
+    attach(&connection, 2, &erased);
+
+    // a cap of 0 deletes everything
+    retention::set_storage_cap(&connection, Some(0)).unwrap();
+    let purged = retention::purge(&mut connection, 1_800_000_000).unwrap();
+
+    assert_eq!(purged.expired.len(), 1);
+    assert_eq!(purged.expired[0].ids, vec![1, 2, 3]);
+    assert_eq!(purged.failures.len(), 1);
+    assert!(purged.failures[0].contains(&stuck));
+    assert!(!std::path::Path::new(&erased).exists());
+    for id in 1..=3 {
+        assert!(sqlite::get_message(&connection, id).unwrap().is_none());
+    }
+    fs::remove_dir(&stuck).unwrap();
+}
+
+#[test]
+fn messages_go_when_their_timer_runs_out() {
+    let database = temporary_database();
+    let mut connection = database.connection().unwrap();
+    // sent at 1_700_000_001 and 1_700_000_002
+    for id in 1..=2 {
+        sqlite::insert_message(&connection, &message(id, CONVERSATION, "bob", "bye")).unwrap();
+    }
+    sqlite::apply_retention(&connection, &timer(2 * MIN_RETENTION_SECS, 1)).unwrap();
+    let first_expires = 1_700_000_001 + 2 * MIN_RETENTION_SECS;
+
+    let purged = retention::purge(&mut connection, first_expires - 1).unwrap();
+    assert!(purged.expired.is_empty());
+    let purged = retention::purge(&mut connection, first_expires).unwrap();
+    assert_eq!(purged.expired[0].ids, vec![1]);
+    assert!(sqlite::get_message(&connection, 2).unwrap().is_some());
+}
+
+#[test]
+fn timers_under_the_minimum_are_raised_to_it() {
+    let database = temporary_database();
+    let mut connection = database.connection().unwrap();
+    sqlite::insert_message(&connection, &message(1, CONVERSATION, "bob", "hello")).unwrap();
+
+    // as the server or a peer could send it
+    assert!(sqlite::apply_retention(&connection, &timer(1, 1)).unwrap());
+    let stored = sqlite::get_retention(&connection, CONVERSATION)
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored.retention_secs, Some(MIN_RETENTION_SECS));
+    let purged = retention::purge(&mut connection, 1_700_000_001 + 60).unwrap();
+    assert!(purged.expired.is_empty());
+    assert!(sqlite::get_message(&connection, 1).unwrap().is_some());
+}
@@ -0,0 +1,106 @@
+// the history search matches word prefixes and never reads what's typed as fts syntax
+mod common;
+
+use common::{message, temporary_database};
+use modules::sqlite::{self, structs::SearchHit};
+use rusqlite::Connection;
+
+fn insert(connection: &Connection, id: u64, conversation_id: &str, body: &str) {
+    sqlite::insert_message(connection, &message(id, conversation_id, "bob", body)).unwrap();
+}
+
+fn ids(hits: &[SearchHit]) -> Vec<u64> {
+    let mut ids: Vec<u64> = hits.iter().map(|hit| hit.message.id).collect();
+    ids.sort_unstable();
+    ids
+}
+
+#[test]
+fn words_match_by_prefix() {
+    let database = temporary_database();
+    let connection = database.connection().unwrap();
+    insert(&connection, 1, "alice:bob", "the invoice is attached");
+    insert(&connection, 2, "alice:bob", "invoices are due friday");
+    insert(&connection, 3, "alice:bob", "see you friday");
+
+    let hits = sqlite::search_messages(&connection, "invo", None, 10).unwrap();
+    assert_eq!(ids(&hits), vec![1, 2]);
+    // every word has to be there
+    let hits = sqlite::search_messages(&connection, "invoice fri", None, 10).unwrap();
+    assert_eq!(ids(&hits), vec![2]);
+    assert!(sqlite::search_messages(&connection, "   ", None, 10)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn search_can_stay_in_one_conversation() {
+    let database = temporary_database();
+    let connection = database.connection().unwrap();
+    insert(&connection, 1, "alice:bob", "lunch tomorrow?");
+    insert(&connection, 2, "alice:carol", "lunch was great");
+
+    let everywhere = sqlite::search_messages(&connection, "lunch", None, 10).unwrap();
+    assert_eq!(ids(&everywhere), vec![1, 2]);
+    let with_carol =
+        sqlite::search_messages(&connection, "lunch", Some("alice:carol"), 10).unwrap();
+    assert_eq!(ids(&with_carol), vec![2]);
+    let limited = sqlite::search_messages(&connection, "lunch", None, 1).unwrap();
+    assert_eq!(limited.len(), 1);
+}
+
+#[test]
+fn snippets_mark_the_matched_words() {
+    let database = temporary_database();
+    let connection = database.connection().unwrap();
+    insert(&connection, 1, "alice:bob", "the invoice is attached");
+
+    let hits = sqlite::search_messages(&connection, "invoice", None, 10).unwrap();
+    let highlighted: Vec<&str> = hits[0]
+        .snippet
+        .iter()
+        .filter(|part| part.highlighted)
+        .map(|part| part.text.as_str())
+        .collect();
+    assert_eq!(highlighted, ["invoice"]);
+    let whole: String = hits[0]
+        .snippet
+        .iter()
+        .map(|part| part.text.as_str())
+        .collect();
+    assert_eq!(whole, "the invoice is attached");
+}
+
+#[test]
+fn fts_syntax_is_searched_as_text() {
+    let database = temporary_database();
+    let connection = database.connection().unwrap();
+    insert(&connection, 1, "alice:bob", "he said \"quote\" twice");
+    insert(&connection, 2, "alice:bob", "the store is NEAR the station");
+    insert(&connection, 3, "alice:bob", "rock and roll");
+
+    for query in [
+        "\"quote", "quote\"", "*", "\"", "NEAR(", "rock AND", "OR", "-", "col:rock",
+    ] {
+        assert!(
+            sqlite::search_messages(&connection, query, None, 10).is_ok(),
+            "{}",
+            query
+        );
+    }
+    let quoted = sqlite::search_messages(&connection, "\"quote\"", None, 10).unwrap();
+    assert_eq!(ids(&quoted), vec![1]);
+    // NEAR is a word like any other, not the proximity operator
+    let near = sqlite::search_messages(&connection, "store NEAR station", None, 10).unwrap();
+    assert_eq!(ids(&near), vec![2]);
+    // OR and AND are words the message has to contain, not operators
+    let or = sqlite::search_messages(&connection, "rock OR quote", None, 10).unwrap();
+    assert!(or.is_empty());
+    let and = sqlite::search_messages(&connection, "rock AND", None, 10).unwrap();
+    assert_eq!(ids(&and), vec![3]);
+    let star = sqlite::search_messages(&connection, "ro*", None, 10).unwrap();
+    assert_eq!(ids(&star), vec![3]);
+    assert!(sqlite::search_messages(&connection, "*", None, 10)
+        .unwrap()
+        .is_empty());
+}
@@ -0,0 +1,113 @@
+// any `threshold` shares of a split rebuild the account, fewer or tampered ones don't
+use modules::solana_wallet::{add_existing_account, recover_from_shares, split_mnemonic};
+use modules::structs::{MnemonicInput, MnemonicShare};
+use std::str::FromStr;
+
+const PHRASE: &str = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+
+fn mnemonic() -> MnemonicInput {
+    let words: Vec<String> = PHRASE.split(' ').map(String::from).collect();
+    MnemonicInput {
+        words: words.try_into().unwrap(),
+    }
+}
+
+// every subset of `0..count` with at least `min` members
+fn subsets(count: usize, min: usize) -> Vec<Vec<usize>> {
+    (0u32..1 << count)
+        .filter(|mask| mask.count_ones() as usize >= min)
+        .map(|mask| (0..count).filter(|i| mask & (1 << i) != 0).collect())
+        .collect()
+}
+
+fn pick(shares: &[MnemonicShare], indexes: &[usize]) -> Vec<MnemonicShare> {
+    indexes.iter().map(|i| shares[*i].clone()).collect()
+}
+
+#[test]
+fn every_large_enough_subset_rebuilds_the_account() {
+    let expected = add_existing_account(&mnemonic()).unwrap().pub_key;
+    for (threshold, count) in [(2, 2), (2, 3), (3, 5), (5, 5)] {
+        let shares = split_mnemonic(&mnemonic(), threshold, count).unwrap();
+        assert_eq!(shares.len(), count as usize);
+
+        for subset in subsets(count as usize, threshold as usize) {
+            let recovered = recover_from_shares(&pick(&shares, &subset)).unwrap();
+            assert_eq!(
+                recovered.pub_key, expected,
+                "{}-of-{} {:?}",
+                threshold, count, subset
+            );
+            assert_eq!(recovered.mnemonic.unwrap().words.join(" "), PHRASE);
+        }
+    }
+}
+
+#[test]
+fn a_single_share_is_never_enough() {
+    // each share would be the whole recovery phrase
+    for (threshold, count) in [(0, 3), (1, 1), (1, 3)] {
+        let error = split_mnemonic(&mnemonic(), threshold, count).unwrap_err();
+        assert!(error.contains("at least 2 shares"), "{}", error);
+    }
+    assert!(split_mnemonic(&mnemonic(), 4, 3).is_err());
+}
+
+#[test]
+fn fewer_shares_than_the_threshold_fail() {
+    let shares = split_mnemonic(&mnemonic(), 3, 5).unwrap();
+    for subset in subsets(5, 0).into_iter().filter(|subset| subset.len() < 3) {
+        assert!(
+            recover_from_shares(&pick(&shares, &subset)).is_err(),
+            "{:?}",
+            subset
+        );
+    }
+}
+
+#[test]
+fn shares_survive_being_written_out() {
+    let shares = split_mnemonic(&mnemonic(), 2, 3).unwrap();
+    let typed: Vec<MnemonicShare> = shares
+        .iter()
+        .map(|share| MnemonicShare::from_str(&share.to_string()).unwrap())
+        .collect();
+    let expected = add_existing_account(&mnemonic()).unwrap().pub_key;
+    assert_eq!(recover_from_shares(&typed[1..]).unwrap().pub_key, expected);
+
+    assert!(MnemonicShare::from_str("chatuza-share 12ab-2 legal winner").is_err());
+    assert!(MnemonicShare::from_str(PHRASE).is_err());
+}
+
+#[test]
+fn duplicate_or_corrupted_shares_are_rejected() {
+    let shares = split_mnemonic(&mnemonic(), 2, 3).unwrap();
+
+    // the same share twice doesn't count as two
+    let twice = vec![shares[0].clone(), shares[0].clone()];
+    assert!(recover_from_shares(&twice).is_err());
+
+    // a word that isn't in the word list
+    let mut misspelled = shares[1].clone();
+    misspelled.words = misspelled.words.replacen(' ', "x ", 1);
+    assert!(recover_from_shares(&[shares[0].clone(), misspelled]).is_err());
+
+    // a share of another split of the same phrase
+    let mut other = split_mnemonic(&mnemonic(), 2, 3).unwrap();
+    while other[0].set_id == shares[0].set_id {
+        other = split_mnemonic(&mnemonic(), 2, 3).unwrap();
+    }
+    assert!(recover_from_shares(&[shares[0].clone(), other[1].clone()]).is_err());
+
+    // relabelled to look like this split, it's caught by the shares beyond the threshold
+    let mut relabelled = other[2].clone();
+    relabelled.set_id = shares[0].set_id;
+    assert!(recover_from_shares(&[shares[0].clone(), shares[1].clone(), relabelled]).is_err());
+
+    // a threshold of 0 is never valid
+    let mut no_threshold = shares.clone();
+    for share in no_threshold.iter_mut() {
+        share.threshold = 0;
+    }
+    assert!(recover_from_shares(&no_threshold).is_err());
+}
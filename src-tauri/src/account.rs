@@ -0,0 +1,122 @@
+use crate::chat::ChatState;
+use crate::server::ServerState;
+use crate::storage::StorageState;
+use crate::wallet::WalletState;
+use modules::server_interaction::structs::{SignInRequest, SignUpRequest, UserProfile};
+use modules::solana_wallet;
+use tauri::State;
+
+const SESSION_KEY_PURPOSE: &str = "session";
+
+// the session is sealed with a key of the unlocked wallet, so signing in needs it unlocked
+fn save_session(wallet: &WalletState, server: &ServerState) -> Result<(), String> {
+    let key = wallet.storage_key(SESSION_KEY_PURPOSE)?;
+    server.client.save_session(&key)
+}
+
+// called right after the wallet is unlocked
+pub fn restore_session(wallet: &WalletState, server: &ServerState) -> Result<(), String> {
+    let key = wallet.storage_key(SESSION_KEY_PURPOSE)?;
+    server.client.load_session(&key).map(|_| ())
+}
+
+#[tauri::command]
+pub async fn sign_up(
+    username: String,
+    display_name: String,
+    avatar_url: Option<String>,
+    password: String,
+    wallet: State<'_, WalletState>,
+    server: State<'_, ServerState>,
+    storage: State<'_, StorageState>,
+) -> Result<UserProfile, String> {
+    wallet.storage_key(SESSION_KEY_PURPOSE)?; // fail early when locked
+    let request = SignUpRequest {
+        username: username.trim().to_string(),
+        display_name: display_name.trim().to_string(),
+        wallet_pubkey: solana_wallet::read_account_pubkey()?.to_string(),
+        avatar_url: avatar_url.filter(|url| !url.trim().is_empty()),
+        password,
+    };
+    let profile = server.client.sign_up(&request).await?;
+    save_session(&wallet, &server)?;
+    storage.remember_account(&profile)?;
+    Ok(profile)
+}
+
+#[tauri::command]
+pub async fn sign_in(
+    username: String,
+    password: String,
+    wallet: State<'_, WalletState>,
+    server: State<'_, ServerState>,
+    storage: State<'_, StorageState>,
+) -> Result<UserProfile, String> {
+    wallet.storage_key(SESSION_KEY_PURPOSE)?;
+    let request = SignInRequest {
+        username: username.trim().to_string(),
+        password,
+    };
+    let profile = server.client.sign_in(&request).await?;
+    save_session(&wallet, &server)?;
+    storage.remember_account(&profile)?;
+    Ok(profile)
+}
+
+#[tauri::command]
+pub async fn sign_out(
+    wallet: State<'_, WalletState>,
+    server: State<'_, ServerState>,
+    chat: State<'_, ChatState>,
+) -> Result<(), String> {
+    chat.disconnect();
+    let revoked = server.client.sign_out().await;
+    save_session(&wallet, &server)?; // removes the saved session
+    revoked
+}
+
+// Sign-In With Solana, the wallet key replaces the password
+#[tauri::command]
+pub async fn sign_in_with_wallet(
+    wallet: State<'_, WalletState>,
+    server: State<'_, ServerState>,
+    storage: State<'_, StorageState>,
+) -> Result<UserProfile, String> {
+    let wallet_pubkey = wallet.pubkey()?.to_string();
+    let challenge = server
+        .client
+        .request_sign_in_challenge(&wallet_pubkey)
+        .await?;
+    let message = server
+        .client
+        .wallet_sign_in_message(&challenge, &wallet_pubkey)?;
+    let signature = wallet.sign_message(message.as_bytes())?;
+    let profile = server
+        .client
+        .complete_wallet_sign_in(&wallet_pubkey, &message, &signature.to_string())
+        .await?;
+    save_session(&wallet, &server)?;
+    storage.remember_account(&profile)?;
+    Ok(profile)
+}
+
+// None when signed out, the profile is refreshed from the server when reachable
+#[tauri::command]
+pub async fn current_user(
+    wallet: State<'_, WalletState>,
+    server: State<'_, ServerState>,
+) -> Result<Option<UserProfile>, String> {
+    let cached = match server.client.current_user() {
+        Some(user) => user,
+        None => return Ok(None),
+    };
+    match server.client.fetch_profile().await {
+        Ok(profile) => {
+            // the tokens may have been refreshed on the way
+            save_session(&wallet, &server)?;
+            Ok(Some(profile))
+        }
+        Err(_) if server.client.current_user().is_some() => Ok(Some(cached)), // offline
+        Err(_) => Ok(None),                                                   // the session expired
+    }
+}
@@ -0,0 +1,48 @@
+use crate::wallet::WalletState;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tauri::{AppHandle, GlobalWindowEvent, Manager, WindowEvent};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+// wall clock moving this much further than the monotonic clock means the machine was suspended
+const SLEEP_DETECTION_GAP: Duration = Duration::from_secs(5);
+
+// background watcher locking the wallet on idle timeout or after the machine slept.
+// the monotonic clock stops while suspended, so sleep shows as a jump of the wall clock
+pub fn spawn_watcher(app: AppHandle) {
+    thread::spawn(move || {
+        let mut last_tick = Instant::now();
+        let mut last_wall_tick = SystemTime::now();
+        loop {
+            thread::sleep(CHECK_INTERVAL);
+            let state = app.state::<WalletState>();
+            let policy = *state.policy.lock().unwrap();
+
+            let monotonic_elapsed = last_tick.elapsed();
+            let wall_elapsed = last_wall_tick.elapsed().unwrap_or_default();
+            last_tick = Instant::now();
+            last_wall_tick = SystemTime::now();
+
+            if policy.lock_on_sleep && wall_elapsed > monotonic_elapsed + SLEEP_DETECTION_GAP {
+                state.lock(&app, "sleep");
+                continue;
+            }
+            if let Some(idle) = state.idle_for() {
+                if idle >= Duration::from_secs(policy.idle_timeout_secs) {
+                    state.lock(&app, "idle");
+                }
+            }
+        }
+    });
+}
+
+pub fn handle_window_event(event: &GlobalWindowEvent) {
+    if let WindowEvent::Resized(_) = event.event() {
+        let window = event.window();
+        let state = window.state::<WalletState>();
+        let lock_on_minimize = state.policy.lock().unwrap().lock_on_minimize;
+        if lock_on_minimize && window.is_minimized().unwrap_or(false) {
+            state.lock(&window.app_handle(), "minimized");
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use crate::server::ServerState;
+use crate::storage::StorageState;
+use modules::p2p_connection::structs::{
+    ConnectionEvent, OutboxEntry, PresenceStatus, RetentionUpdate,
+};
+use modules::p2p_connection::{self, ChatConnection, SEND_READ_RECEIPTS_SETTING};
+use modules::retention;
+use modules::sqlite;
+use modules::sqlite::structs::{MessagePage, SearchHit};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::{AppHandle, GlobalWindowEvent, Manager, State, WindowEvent};
+
+pub const MESSAGE_EVENT: &str = "chat-message";
+pub const CONNECTION_EVENT: &str = "chat-connection";
+pub const OUTBOX_EVENT: &str = "chat-outbox";
+pub const RECEIPT_EVENT: &str = "chat-receipt";
+pub const PRESENCE_EVENT: &str = "chat-presence";
+pub const TYPING_EVENT: &str = "chat-typing";
+pub const RETENTION_EVENT: &str = "chat-retention";
+// the sidebar shows the best results only, it doesn't page them
+const SEARCH_LIMIT: u32 = 50;
+// the webview asks for pages of a few dozen messages, more at once would defeat the paging
+const MAX_MESSAGE_PAGE: u32 = 200;
+
+#[derive(Serialize, Deserialize)]
+pub struct PrivacySettings {
+    pub send_read_receipts: bool,
+}
+
+#[derive(Serialize)]
+pub struct MessageSearchResult {
+    #[serde(flatten)]
+    pub hit: SearchHit,
+    // the other user of the conversation, to open it
+    pub contact: Option<String>,
+}
+
+// the live chat connection, opened once signed in
+#[derive(Default)]
+pub struct ChatState {
+    connection: Mutex<Option<ChatConnection>>,
+    // where the next connection resumes from, kept across sign outs of the same run
+    last_acked: Mutex<Option<u64>>,
+    // the contacts may load before the connection is up
+    watched: Mutex<Vec<String>>,
+}
+
+impl ChatState {
+    fn connection(&self) -> Result<ChatConnection, String> {
+        match self.connection.lock().unwrap().as_ref() {
+            Some(connection) => Ok(connection.clone()),
+            None => Err("sign in to start chatting".to_string()),
+        }
+    }
+
+    pub fn disconnect(&self) {
+        self.suspend();
+        self.watched.lock().unwrap().clear();
+    }
+
+    // while the wallet is locked, the same contacts are watched once it's connected again
+    pub fn suspend(&self) {
+        if let Some(connection) = self.connection.lock().unwrap().take() {
+            *self.last_acked.lock().unwrap() = connection.last_acked();
+            let _ = connection.set_presence(PresenceStatus::Offline);
+            connection.shutdown();
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn connect_chat(
+    app: AppHandle,
+    chat: State<'_, ChatState>,
+    server: State<'_, ServerState>,
+    storage: State<'_, StorageState>,
+) -> Result<(), String> {
+    if server.client.current_user().is_none() {
+        return Err("sign in to start chatting".to_string());
+    }
+    let database = storage.database()?;
+    let mut current = chat.connection.lock().unwrap();
+    if current.is_some() {
+        return Ok(());
+    }
+    // after a restart, from what the database kept
+    let last_acked = *chat.last_acked.lock().unwrap();
+    let resume_after = match last_acked {
+        Some(last_acked) => Some(last_acked),
+        None => {
+            let connection = database.connection()?;
+            p2p_connection::resume_point(&connection)?
+        }
+    };
+    let (connection, mut events) =
+        ChatConnection::connect(server.client.clone(), database, resume_after);
+    let watched = chat.watched.lock().unwrap().clone();
+    if !watched.is_empty() {
+        connection.watch_presence(watched)?;
+    }
+    *current = Some(connection);
+
+    tauri::async_runtime::spawn(async move {
+        while let Some(event) = events.recv().await {
+            let _ = match &event {
+                ConnectionEvent::Message(message) => app.emit_all(MESSAGE_EVENT, message),
+                ConnectionEvent::Outbox(entry) => app.emit_all(OUTBOX_EVENT, entry),
+                ConnectionEvent::Receipt { .. } => app.emit_all(RECEIPT_EVENT, &event),
+                ConnectionEvent::Presence(update) => app.emit_all(PRESENCE_EVENT, update),
+                ConnectionEvent::Typing { .. } => app.emit_all(TYPING_EVENT, &event),
+                ConnectionEvent::Retention(update) => app.emit_all(RETENTION_EVENT, update),
+                _ => app.emit_all(CONNECTION_EVENT, &event),
+            };
+        }
+    });
+    Ok(())
+}
+
+// the conversation with `username`, for the signed in user
+#[tauri::command]
+pub fn conversation_with(username: String, server: State<ServerState>) -> Result<String, String> {
+    match server.client.current_user() {
+        Some(user) => Ok(p2p_connection::direct_conversation_id(
+            &user.username,
+            &username,
+        )),
+        None => Err("sign in to start chatting".to_string()),
+    }
+}
+
+#[tauri::command]
+pub fn disconnect_chat(chat: State<ChatState>) {
+    chat.disconnect();
+}
+
+// the message is queued and sent as soon as the server is reachable
+#[tauri::command]
+pub fn send_chat_message(
+    conversation_id: String,
+    body: String,
+    chat: State<ChatState>,
+) -> Result<OutboxEntry, String> {
+    if body.trim().is_empty() {
+        return Err("the message is empty".to_string());
+    }
+    chat.connection()?.send_message(&conversation_id, &body)
+}
+
+#[tauri::command]
+pub fn retry_chat_message(
+    client_id: String,
+    chat: State<ChatState>,
+) -> Result<OutboxEntry, String> {
+    chat.connection()?.retry_message(&client_id)
+}
+
+// messages not accepted by the server yet, kept across restarts
+#[tauri::command]
+pub fn list_outbox(storage: State<StorageState>) -> Result<Vec<OutboxEntry>, String> {
+    let connection = storage.connection()?;
+    sqlite::list_outbox(&connection)
+}
+
+// a page of the history kept on this device, oldest first: the messages after `after`,
+// the ones before `before`, or the latest ones
+#[tauri::command]
+pub fn list_messages(
+    conversation_id: String,
+    before: Option<u64>,
+    after: Option<u64>,
+    limit: u32,
+    storage: State<StorageState>,
+) -> Result<MessagePage, String> {
+    let limit = limit.clamp(1, MAX_MESSAGE_PAGE);
+    let connection = storage.connection()?;
+    match after {
+        Some(after) => sqlite::list_messages_after(&connection, &conversation_id, after, limit),
+        None => sqlite::list_messages_before(&connection, &conversation_id, before, limit),
+    }
+}
+
+// the messages kept on this device matching `query`, only the ones with `contact` when given
+#[tauri::command]
+pub fn search_messages(
+    query: String,
+    contact: Option<String>,
+    server: State<ServerState>,
+    storage: State<StorageState>,
+) -> Result<Vec<MessageSearchResult>, String> {
+    let user = match server.client.current_user() {
+        Some(user) => user,
+        None => return Err("sign in to search your messages".to_string()),
+    };
+    let conversation_id =
+        contact.map(|contact| p2p_connection::direct_conversation_id(&user.username, &contact));
+    let connection = storage.connection()?;
+    let hits = sqlite::search_messages(
+        &connection,
+        &query,
+        conversation_id.as_deref(),
+        SEARCH_LIMIT,
+    )?;
+    Ok(hits
+        .into_iter()
+        .map(|hit| MessageSearchResult {
+            contact: p2p_connection::direct_peer(&hit.message.conversation_id, &user.username),
+            hit,
+        })
+        .collect())
+}
+
+// the user saw the conversation up to `up_to`
+#[tauri::command]
+pub fn mark_read(
+    conversation_id: String,
+    up_to: u64,
+    chat: State<ChatState>,
+) -> Result<(), String> {
+    chat.connection()?.mark_read(&conversation_id, up_to)
+}
+
+#[tauri::command]
+pub fn get_privacy_settings(storage: State<StorageState>) -> Result<PrivacySettings, String> {
+    let connection = storage.connection()?;
+    let send_read_receipts = sqlite::get_setting(&connection, SEND_READ_RECEIPTS_SETTING)?;
+    Ok(PrivacySettings {
+        send_read_receipts: send_read_receipts.as_deref() != Some("false"),
+    })
+}
+
+#[tauri::command]
+pub fn set_privacy_settings(
+    settings: PrivacySettings,
+    storage: State<StorageState>,
+) -> Result<(), String> {
+    let value = if settings.send_read_receipts {
+        "true"
+    } else {
+        "false"
+    };
+    let connection = storage.connection()?;
+    sqlite::set_setting(&connection, SEND_READ_RECEIPTS_SETTING, value)
+}
+
+// follows the presence of the contacts, the updates come through the presence event
+#[tauri::command]
+pub fn watch_presence(usernames: Vec<String>, chat: State<ChatState>) -> Result<(), String> {
+    *chat.watched.lock().unwrap() = usernames.clone();
+    match chat.connection.lock().unwrap().as_ref() {
+        Some(connection) => connection.watch_presence(usernames),
+        None => Ok(()), // followed once connected
+    }
+}
+
+// called on every keystroke of the composer, the connection rate-limits it
+#[tauri::command]
+pub fn send_typing(conversation_id: String, chat: State<ChatState>) -> Result<(), String> {
+    chat.connection()?.typing(&conversation_id)
+}
+
+// the timer of the conversation, None while nobody set one
+#[tauri::command]
+pub fn get_retention(
+    conversation_id: String,
+    storage: State<StorageState>,
+) -> Result<Option<RetentionUpdate>, String> {
+    let connection = storage.connection()?;
+    sqlite::get_retention(&connection, &conversation_id)
+}
+
+// the server applies the timer for both sides, it comes back through the retention event.
+// None turns it off
+#[tauri::command]
+pub fn set_retention(
+    conversation_id: String,
+    retention_secs: Option<u64>,
+    chat: State<ChatState>,
+) -> Result<(), String> {
+    if retention_secs.is_some_and(|secs| secs < retention::MIN_RETENTION_SECS) {
+        return Err("the timer has to be at least an hour".to_string());
+    }
+    chat.connection()?
+        .set_retention(&conversation_id, retention_secs)
+}
+
+// away while the window isn't focused, offline once it's closed
+pub fn handle_window_event(event: &GlobalWindowEvent) {
+    let status = match event.event() {
+        WindowEvent::Focused(true) => PresenceStatus::Online,
+        WindowEvent::Focused(false) => PresenceStatus::Away,
+        WindowEvent::Destroyed => {
+            event.window().state::<ChatState>().disconnect();
+            return;
+        }
+        _ => return,
+    };
+    if let Ok(connection) = event.window().state::<ChatState>().connection() {
+        let _ = connection.set_presence(status);
+    }
+}
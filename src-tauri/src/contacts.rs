@@ -0,0 +1,146 @@
+use crate::server::ServerState;
+use crate::storage::StorageState;
+use crate::wallet::parse_pubkey;
+use modules::server_interaction::structs::{Page, UserProfile};
+use modules::sqlite;
+use modules::sqlite::structs::{AddressBookEntry, ContactAddress};
+use tauri::State;
+
+#[tauri::command]
+pub async fn search_users(
+    query: String,
+    page: u32,
+    server: State<'_, ServerState>,
+) -> Result<Page<UserProfile>, String> {
+    server.client.search_users(&query, page).await
+}
+
+#[tauri::command]
+pub async fn add_contact(
+    username: String,
+    server: State<'_, ServerState>,
+    storage: State<'_, StorageState>,
+) -> Result<UserProfile, String> {
+    let contact = server.client.add_contact(&username).await?;
+    let connection = storage.connection()?;
+    sqlite::save_contact(&connection, &contact)?;
+    Ok(contact)
+}
+
+// the list of the server, the one saved last time while offline
+#[tauri::command]
+pub async fn list_contacts(
+    server: State<'_, ServerState>,
+    storage: State<'_, StorageState>,
+) -> Result<Vec<UserProfile>, String> {
+    match server.client.list_contacts().await {
+        Ok(contacts) => {
+            let mut connection = storage.connection()?;
+            sqlite::replace_contacts(&mut connection, &contacts)?;
+            Ok(contacts)
+        }
+        Err(e) if server.client.current_user().is_some() => {
+            let connection = storage.connection()?;
+            let saved = sqlite::list_contacts(&connection)?;
+            if saved.is_empty() {
+                Err(e)
+            } else {
+                Ok(saved)
+            }
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// Address book
+
+// the contacts as saved by the last list_contacts, with what the user added to them
+#[tauri::command]
+pub fn list_address_book(
+    storage: State<'_, StorageState>,
+) -> Result<Vec<AddressBookEntry>, String> {
+    let connection = storage.connection()?;
+    sqlite::list_address_book(&connection)
+}
+
+// empty fields are cleared
+#[tauri::command]
+pub fn update_contact(
+    username: String,
+    nickname: String,
+    notes: String,
+    custom_avatar_url: String,
+    storage: State<'_, StorageState>,
+) -> Result<AddressBookEntry, String> {
+    let connection = storage.connection()?;
+    sqlite::update_contact_details(
+        &connection,
+        &username,
+        non_empty(&nickname),
+        non_empty(&notes),
+        non_empty(&custom_avatar_url),
+    )?;
+    address_book_entry(&connection, &username)
+}
+
+#[tauri::command]
+pub fn add_contact_address(
+    username: String,
+    address: String,
+    label: String,
+    storage: State<'_, StorageState>,
+) -> Result<ContactAddress, String> {
+    let address = parse_pubkey(&address, "wallet")?.to_string();
+    let label = match non_empty(&label) {
+        Some(label) => label,
+        None => return Err("give the address a label".to_string()),
+    };
+    let connection = storage.connection()?;
+    sqlite::add_contact_address(&connection, &username, &address, label)
+}
+
+#[tauri::command]
+pub fn label_contact_address(
+    id: u64,
+    label: String,
+    storage: State<'_, StorageState>,
+) -> Result<(), String> {
+    let label = match non_empty(&label) {
+        Some(label) => label,
+        None => return Err("give the address a label".to_string()),
+    };
+    let connection = storage.connection()?;
+    sqlite::label_contact_address(&connection, id, label)
+}
+
+// called once the user checked the address with the contact
+#[tauri::command]
+pub fn verify_contact_address(
+    id: u64,
+    username: String,
+    storage: State<'_, StorageState>,
+) -> Result<AddressBookEntry, String> {
+    let connection = storage.connection()?;
+    sqlite::verify_contact_address(&connection, id)?;
+    address_book_entry(&connection, &username)
+}
+
+#[tauri::command]
+pub fn remove_contact_address(id: u64, storage: State<'_, StorageState>) -> Result<(), String> {
+    let connection = storage.connection()?;
+    sqlite::remove_contact_address(&connection, id)
+}
+
+fn address_book_entry(
+    connection: &sqlite::DatabaseConnection,
+    username: &str,
+) -> Result<AddressBookEntry, String> {
+    match sqlite::get_address_book_entry(connection, username)? {
+        Some(entry) => Ok(entry),
+        None => Err(format!("{} isn't in the address book", username)),
+    }
+}
+
+fn non_empty(value: &str) -> Option<&str> {
+    Some(value.trim()).filter(|value| !value.is_empty())
+}
@@ -0,0 +1,55 @@
+use crate::server::ServerState;
+use crate::storage::StorageState;
+use modules::export::{self, structs::ExportFormat, structs::ExportReport};
+use modules::import::{self, structs::ImportReport};
+use modules::p2p_connection;
+use tauri::State;
+
+// the conversation with `contact`, or all of them, written to `path` or to a new file in the
+// profile directory. The attachments go in a zip next to it when asked
+#[tauri::command]
+pub fn export_history(
+    contact: Option<String>,
+    format: ExportFormat,
+    path: Option<String>,
+    with_attachments: bool,
+    server: State<ServerState>,
+    storage: State<StorageState>,
+) -> Result<ExportReport, String> {
+    let user = match server.client.current_user() {
+        Some(user) => user,
+        None => return Err("sign in to export your history".to_string()),
+    };
+    let conversation_id = contact
+        .as_ref()
+        .map(|contact| p2p_connection::direct_conversation_id(&user.username, contact));
+    let path = match path.filter(|path| !path.trim().is_empty()) {
+        Some(path) => path,
+        None => export::default_export_path(contact.as_deref(), format),
+    };
+    let connection = storage.connection()?;
+    export::export_history(
+        &connection,
+        &user,
+        conversation_id.as_deref(),
+        format,
+        &path,
+        with_attachments,
+    )
+}
+
+// merges a JSON export into the history, `dry_run` only reports what would change
+#[tauri::command]
+pub fn import_history(
+    path: String,
+    dry_run: bool,
+    server: State<ServerState>,
+    storage: State<StorageState>,
+) -> Result<ImportReport, String> {
+    let user = match server.client.current_user() {
+        Some(user) => user,
+        None => return Err("sign in to import your history".to_string()),
+    };
+    let mut connection = storage.connection()?;
+    import::import_history(&mut connection, &user.username, path.trim(), dry_run)
+}
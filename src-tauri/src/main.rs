@@ -1,6 +1,17 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod account;
+mod auto_lock;
+mod chat;
+mod contacts;
+mod history;
+mod profiles;
+mod retention;
+mod server;
+mod storage;
+mod wallet;
+
 // Learn more about Tauri commands at https://tauri.app/v1/guides/features/command
 #[tauri::command]
 fn greet(name: &str) -> String {
@@ -9,7 +20,82 @@ fn greet(name: &str) -> String {
 
 fn main() {
     tauri::Builder::default()
-        .invoke_handler(tauri::generate_handler![greet])
+        .manage(wallet::WalletState::default())
+        .manage(chat::ChatState::default())
+        .manage(storage::StorageState::default())
+        .manage(profiles::ProfileState::from_args().expect("invalid chatuza profile"))
+        .manage(server::ServerState::from_env().expect("invalid chatuza server configuration"))
+        .setup(|app| {
+            auto_lock::spawn_watcher(app.handle());
+            retention::spawn_purger(app.handle());
+            Ok(())
+        })
+        .on_window_event(|event| {
+            auto_lock::handle_window_event(&event);
+            chat::handle_window_event(&event);
+        })
+        .invoke_handler(tauri::generate_handler![
+            greet,
+            profiles::list_profiles,
+            profiles::current_profile,
+            profiles::open_profile,
+            wallet::create_account,
+            wallet::replace_account,
+            wallet::delete_account,
+            wallet::list_archived_accounts,
+            wallet::restore_account,
+            wallet::wallet_status,
+            wallet::unlock_wallet,
+            wallet::change_password,
+            wallet::lock_wallet,
+            wallet::report_activity,
+            wallet::get_auto_lock_policy,
+            wallet::set_auto_lock_policy,
+            wallet::get_network_settings,
+            wallet::set_funding_strategy,
+            wallet::transfer,
+            wallet::pay_contact,
+            wallet::reveal_mnemonic,
+            wallet::backup_quiz,
+            wallet::confirm_backup,
+            wallet::confirm_backup_with_phrase,
+            wallet::backup_reminder,
+            wallet::split_recovery_phrase,
+            wallet::recover_from_shares,
+            account::sign_up,
+            account::sign_in,
+            account::sign_in_with_wallet,
+            account::sign_out,
+            account::current_user,
+            contacts::search_users,
+            contacts::add_contact,
+            contacts::list_contacts,
+            contacts::list_address_book,
+            contacts::update_contact,
+            contacts::add_contact_address,
+            contacts::label_contact_address,
+            contacts::verify_contact_address,
+            contacts::remove_contact_address,
+            chat::connect_chat,
+            chat::disconnect_chat,
+            chat::conversation_with,
+            chat::send_chat_message,
+            chat::retry_chat_message,
+            chat::list_outbox,
+            chat::list_messages,
+            chat::search_messages,
+            history::export_history,
+            history::import_history,
+            chat::mark_read,
+            chat::get_privacy_settings,
+            chat::set_privacy_settings,
+            chat::watch_presence,
+            chat::send_typing,
+            chat::get_retention,
+            chat::set_retention,
+            retention::get_storage_cap,
+            retention::set_storage_cap
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
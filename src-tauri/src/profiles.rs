@@ -0,0 +1,52 @@
+use modules::profiles::{self, structs::ProfileInfo};
+use std::sync::Mutex;
+use tauri::State;
+
+// the profile the app runs with, picked once at startup. The wallet, the session and the
+// database are opened from its directory, so switching means starting the app again
+#[derive(Default)]
+pub struct ProfileState {
+    opened: Mutex<Option<String>>,
+}
+
+impl ProfileState {
+    // EH
+    // the profile given with --profile, the switcher is shown without it
+    pub fn from_args() -> Result<Self, String> {
+        let args: Vec<String> = std::env::args().collect();
+        let state = ProfileState::default();
+        if let Some(name) = profiles::profile_from_args(&args)? {
+            state.open(&name)?;
+        }
+        Ok(state)
+    }
+
+    fn open(&self, name: &str) -> Result<(), String> {
+        let mut opened = self.opened.lock().unwrap();
+        match opened.as_deref() {
+            Some(current) if current == name => return Ok(()),
+            Some(_) => return Err("restart chatuza to switch to another profile".to_string()),
+            None => {}
+        }
+        profiles::open_profile(name)?;
+        *opened = Some(name.to_string());
+        Ok(())
+    }
+}
+
+#[tauri::command]
+pub fn list_profiles() -> Result<Vec<ProfileInfo>, String> {
+    profiles::list_profiles()
+}
+
+// None until one is picked in the switcher
+#[tauri::command]
+pub fn current_profile(state: State<ProfileState>) -> Option<String> {
+    state.opened.lock().unwrap().clone()
+}
+
+// opens the profile `name`, a new one is created
+#[tauri::command]
+pub fn open_profile(name: String, state: State<ProfileState>) -> Result<(), String> {
+    state.open(name.trim())
+}
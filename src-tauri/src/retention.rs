@@ -0,0 +1,51 @@
+use crate::chat::CONNECTION_EVENT;
+use crate::storage::StorageState;
+use modules::p2p_connection::structs::ConnectionEvent;
+use modules::retention;
+use modules::solana_wallet::unix_now;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+
+pub const EXPIRED_EVENT: &str = "chat-expired";
+// the timers are at least `retention::MIN_RETENTION_SECS`, a message outliving its timer by this long is fine
+const PURGE_INTERVAL: Duration = Duration::from_secs(30);
+
+// background job deleting the messages whose timer ran out and the oldest ones over the cap.
+// Nothing is deleted while the wallet is locked, the database isn't open then
+pub fn spawn_purger(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(PURGE_INTERVAL);
+        let storage = app.state::<StorageState>();
+        let mut connection = match storage.connection() {
+            Ok(connection) => connection,
+            Err(_) => continue,
+        };
+        // the failures show up like the other local database errors
+        let failures = match retention::purge(&mut connection, unix_now()) {
+            Ok(purged) => {
+                for group in purged.expired {
+                    let _ = app.emit_all(EXPIRED_EVENT, group);
+                }
+                purged.failures
+            }
+            Err(e) => vec![format!("couldn't delete the expired messages \n {}", e)],
+        };
+        for message in failures {
+            let _ = app.emit_all(CONNECTION_EVENT, ConnectionEvent::LocalError { message });
+        }
+    });
+}
+
+// bytes of history kept on this device at most, None when there's no cap
+#[tauri::command]
+pub fn get_storage_cap(storage: State<StorageState>) -> Result<Option<u64>, String> {
+    let connection = storage.connection()?;
+    retention::storage_cap(&connection)
+}
+
+#[tauri::command]
+pub fn set_storage_cap(cap: Option<u64>, storage: State<StorageState>) -> Result<(), String> {
+    let connection = storage.connection()?;
+    retention::set_storage_cap(&connection, cap)
+}
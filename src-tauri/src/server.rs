@@ -0,0 +1,14 @@
+use modules::server_interaction::ChatuzaServerClient;
+
+// the one Chatuza server client of the app, its url comes from CHATUZA_SERVER_URL
+pub struct ServerState {
+    pub client: ChatuzaServerClient,
+}
+
+impl ServerState {
+    pub fn from_env() -> Result<Self, String> {
+        Ok(ServerState {
+            client: ChatuzaServerClient::from_env()?,
+        })
+    }
+}
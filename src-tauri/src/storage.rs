@@ -0,0 +1,84 @@
+use crate::chat::CONNECTION_EVENT;
+use modules::p2p_connection::structs::ConnectionEvent;
+use modules::server_interaction::structs::UserProfile;
+use modules::solana_wallet::unix_now;
+use modules::sqlite::structs::AccountRecord;
+use modules::sqlite::{self, Database, DatabaseConnection};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+const LOCKED: &str = "unlock the wallet to open the local history";
+
+// the local database, the commands share its connection pool.
+// It's encrypted with the wallet password, so it's opened when the wallet is unlocked
+pub struct StorageState {
+    // why it can't be used while it isn't open: locked, or the decryption failed
+    database: Mutex<Result<Database, String>>,
+}
+
+impl Default for StorageState {
+    fn default() -> Self {
+        StorageState {
+            database: Mutex::new(Err(LOCKED.to_string())),
+        }
+    }
+}
+
+impl StorageState {
+    // called with the password that just unlocked the wallet of `account`. A database that
+    // can't be opened isn't an unlock failure, its error is shown like the other local database
+    // errors and returned by the commands using it
+    pub fn open(&self, app: &AppHandle, account: &str, password: &str) {
+        let mut database = self.database.lock().unwrap();
+        if database.is_err() {
+            *database = sqlite::open_database(account, password);
+            if let Err(message) = &*database {
+                let _ = app.emit_all(
+                    CONNECTION_EVENT,
+                    ConnectionEvent::LocalError {
+                        message: message.clone(),
+                    },
+                );
+            }
+        }
+    }
+
+    // the wallet got locked, or the account is gone and the next one opens its own database.
+    // The connections handed out close as they're dropped
+    pub fn close(&self) {
+        *self.database.lock().unwrap() = Err(LOCKED.to_string());
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.database.lock().unwrap().is_ok()
+    }
+
+    pub fn database(&self) -> Result<Database, String> {
+        self.database.lock().unwrap().clone()
+    }
+
+    pub fn connection(&self) -> Result<DatabaseConnection, String> {
+        self.database()?.connection()
+    }
+
+    // EH
+    pub fn change_password(&self, new_password: &str) -> Result<(), String> {
+        self.database()?.change_password(new_password)
+    }
+
+    // links the wallet to the user it signed in as
+    pub fn remember_account(&self, profile: &UserProfile) -> Result<(), String> {
+        let now = unix_now();
+        let connection = self.connection()?;
+        sqlite::save_account(
+            &connection,
+            &AccountRecord {
+                pub_key: profile.wallet_pubkey.clone(),
+                username: Some(profile.username.clone()),
+                display_name: Some(profile.display_name.clone()),
+                created_at: now,
+                last_used_at: now,
+            },
+        )
+    }
+}
@@ -0,0 +1,510 @@
+use crate::account;
+use crate::chat::ChatState;
+use crate::server::ServerState;
+use crate::storage::StorageState;
+use modules::solana_wallet;
+use modules::sqlite;
+use modules::structs::{
+    ArchivedAccount, BackupAnswer, BackupQuiz, FundingStrategy, MnemonicInput, MnemonicShare,
+    NetworkSettings, NewAccountOutput, TransferResponse,
+};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::signer::{keypair::Keypair, Signer};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Instant;
+use tauri::{AppHandle, Manager, State};
+use zeroize::Zeroizing;
+
+pub const LOCK_EVENT: &str = "wallet-locked";
+
+// secrets stay on this side of the IPC bridge, the webview only ever sees public data
+#[derive(Default)]
+pub struct WalletState {
+    // the freshly generated phrase, kept until the backup is confirmed
+    pending_mnemonic: Mutex<Option<MnemonicInput>>,
+    // the positions the last backup quiz asked, the answers must cover exactly these
+    quiz: Mutex<Option<BackupQuiz>>,
+    // decrypted signing key, None while the wallet is locked
+    unlocked: Mutex<Option<UnlockedWallet>>,
+    pub policy: Mutex<AutoLockPolicy>,
+    // the cluster comes from CHATUZA_CLUSTER, the funding can be changed by the user
+    network: Mutex<NetworkSettings>,
+}
+
+pub struct UnlockedWallet {
+    keypair: Keypair,
+    pub last_activity: Instant,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct AutoLockPolicy {
+    pub idle_timeout_secs: u64,
+    pub lock_on_minimize: bool,
+    pub lock_on_sleep: bool,
+}
+
+impl Default for AutoLockPolicy {
+    fn default() -> Self {
+        AutoLockPolicy {
+            idle_timeout_secs: 5 * 60,
+            lock_on_minimize: true,
+            lock_on_sleep: true,
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct LockEvent {
+    pub reason: &'static str, // "manual", "idle", "minimized" or "sleep"
+}
+
+#[derive(Serialize)]
+pub struct AccountInfo {
+    pub pub_key: String,
+    pub backed_up: bool,
+}
+
+#[derive(Serialize)]
+pub struct WalletStatus {
+    pub pub_key: Option<String>, // None when there is no account on this device
+    pub unlocked: bool,
+    pub backed_up: bool,
+}
+
+impl WalletState {
+    // drops the decrypted key and closes the history with its key, the chat connection using
+    // it goes too, and tells the webview. No-op when already locked
+    pub fn lock(&self, app: &AppHandle, reason: &'static str) {
+        if self.unlocked.lock().unwrap().take().is_some() {
+            app.state::<ChatState>().suspend();
+            app.state::<StorageState>().close();
+            let _ = app.emit_all(LOCK_EVENT, LockEvent { reason });
+        }
+    }
+
+    pub fn idle_for(&self) -> Option<std::time::Duration> {
+        self.unlocked
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|wallet| wallet.last_activity.elapsed())
+    }
+
+    // keeps the phrase for the backup quiz and unlocks with the new key
+    fn start_new_account(&self, new_account: &NewAccountOutput) -> AccountInfo {
+        *self.pending_mnemonic.lock().unwrap() = new_account.mnemonic.clone();
+        self.unlock_with(Keypair::from_base58_string(new_account.keypair.expose()));
+        AccountInfo {
+            pub_key: new_account.pub_key.clone(),
+            backed_up: false,
+        }
+    }
+
+    fn forget_account(&self) {
+        *self.pending_mnemonic.lock().unwrap() = None;
+        *self.quiz.lock().unwrap() = None;
+        *self.unlocked.lock().unwrap() = None;
+    }
+
+    fn unlock_with(&self, keypair: Keypair) {
+        *self.unlocked.lock().unwrap() = Some(UnlockedWallet {
+            keypair,
+            last_activity: Instant::now(),
+        });
+    }
+
+    // see `solana_wallet::storage_key`, fails while locked
+    pub fn storage_key(&self, purpose: &str) -> Result<Zeroizing<[u8; 32]>, String> {
+        self.with_signer(|keypair| Ok(solana_wallet::storage_key(keypair, purpose)))
+    }
+
+    // signs with the unlocked key, fails while locked
+    pub fn sign_message(&self, message: &[u8]) -> Result<Signature, String> {
+        self.with_signer(|keypair| Ok(keypair.sign_message(message)))
+    }
+
+    pub fn pubkey(&self) -> Result<Pubkey, String> {
+        self.with_signer(|keypair| Ok(keypair.pubkey()))
+    }
+
+    // a copy of the key for work that can't hold the lock, like a transfer waiting on the
+    // network, the wallet can still be locked meanwhile
+    fn signer(&self) -> Result<Keypair, String> {
+        self.with_signer(|keypair| Ok(keypair.insecure_clone()))
+    }
+
+    fn with_signer<T>(&self, f: impl FnOnce(&Keypair) -> Result<T, String>) -> Result<T, String> {
+        let mut unlocked = self.unlocked.lock().unwrap();
+        match unlocked.as_mut() {
+            Some(wallet) => {
+                wallet.last_activity = Instant::now();
+                f(&wallet.keypair)
+            }
+            None => Err("the wallet is locked, enter your password to continue".to_string()),
+        }
+    }
+}
+
+#[tauri::command]
+pub fn create_account(
+    app: AppHandle,
+    password: String,
+    state: State<WalletState>,
+    storage: State<StorageState>,
+) -> Result<AccountInfo, String> {
+    let new_account = solana_wallet::gen_new_account(&password)?;
+    storage.open(&app, &new_account.pub_key, &password);
+    Ok(state.start_new_account(&new_account))
+}
+
+// `confirmation` is the current address typed by the user
+#[tauri::command]
+pub fn replace_account(
+    app: AppHandle,
+    password: String,
+    confirmation: String,
+    state: State<WalletState>,
+    storage: State<StorageState>,
+) -> Result<AccountInfo, String> {
+    let new_account = solana_wallet::replace_account(&password, &confirmation)?;
+    storage.close();
+    storage.open(&app, &new_account.pub_key, &password);
+    Ok(state.start_new_account(&new_account))
+}
+
+#[tauri::command]
+pub fn delete_account(
+    password: String,
+    confirmation: String,
+    state: State<WalletState>,
+    storage: State<StorageState>,
+) -> Result<ArchivedAccount, String> {
+    let archived = solana_wallet::delete_account(&password, &confirmation)?;
+    state.forget_account();
+    storage.close();
+    Ok(archived)
+}
+
+#[tauri::command]
+pub fn list_archived_accounts() -> Result<Vec<ArchivedAccount>, String> {
+    solana_wallet::list_archived_accounts()
+}
+
+#[tauri::command]
+pub fn restore_account(
+    app: AppHandle,
+    pub_key: String,
+    password: String,
+    state: State<WalletState>,
+    storage: State<StorageState>,
+) -> Result<(), String> {
+    solana_wallet::restore_archived_account(&pub_key, &password)?;
+    state.unlock_with(solana_wallet::unlock_wallet(&password)?);
+    storage.open(&app, &pub_key, &password);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn wallet_status(state: State<WalletState>) -> WalletStatus {
+    WalletStatus {
+        pub_key: solana_wallet::read_account_pubkey()
+            .ok()
+            .map(|pk| pk.to_string()),
+        unlocked: state.unlocked.lock().unwrap().is_some(),
+        backed_up: solana_wallet::is_backed_up().unwrap_or(false),
+    }
+}
+
+#[tauri::command]
+pub fn unlock_wallet(
+    app: AppHandle,
+    password: String,
+    state: State<WalletState>,
+    server: State<ServerState>,
+    storage: State<StorageState>,
+) -> Result<(), String> {
+    let keypair = solana_wallet::unlock_wallet(&password)?;
+    storage.open(&app, &keypair.pubkey().to_string(), &password);
+    state.unlock_with(keypair);
+    // a saved session that can't be read only means signing in again
+    let _ = account::restore_session(&state, &server);
+    Ok(())
+}
+
+// the local history is re-encrypted first, a failure there leaves both on the old password.
+// A history that can't be opened is left as it is, the wallet password still changes
+#[tauri::command]
+pub fn change_password(
+    app: AppHandle,
+    old_password: String,
+    new_password: String,
+    storage: State<StorageState>,
+) -> Result<(), String> {
+    let keypair = solana_wallet::unlock_wallet(&old_password)?;
+    if new_password.is_empty() {
+        return Err("the wallet password can't be empty".to_string());
+    }
+    // closed while the wallet is locked, it's opened just for the rekey
+    let was_open = storage.is_open();
+    storage.open(&app, &keypair.pubkey().to_string(), &old_password);
+    let changed = match storage.is_open() {
+        true => rekey_then_change_password(&storage, &old_password, &new_password),
+        false => solana_wallet::change_wallet_password(&old_password, &new_password),
+    };
+    if !was_open && storage.is_open() {
+        storage.close();
+    }
+    changed
+}
+
+// EH
+fn rekey_then_change_password(
+    storage: &StorageState,
+    old_password: &str,
+    new_password: &str,
+) -> Result<(), String> {
+    storage.change_password(new_password)?;
+    if let Err(e) = solana_wallet::change_wallet_password(old_password, new_password) {
+        // back to the password the keystore still has
+        let _ = storage.change_password(old_password);
+        return Err(e);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn lock_wallet(app: AppHandle, state: State<WalletState>) {
+    state.lock(&app, "manual");
+}
+
+// called by the webview on user input so an active user isn't locked out
+#[tauri::command]
+pub fn report_activity(state: State<WalletState>) {
+    if let Some(wallet) = state.unlocked.lock().unwrap().as_mut() {
+        wallet.last_activity = Instant::now();
+    }
+}
+
+#[tauri::command]
+pub fn get_auto_lock_policy(state: State<WalletState>) -> AutoLockPolicy {
+    *state.policy.lock().unwrap()
+}
+
+#[tauri::command]
+pub fn set_auto_lock_policy(
+    policy: AutoLockPolicy,
+    state: State<WalletState>,
+) -> Result<(), String> {
+    if policy.idle_timeout_secs == 0 {
+        return Err("the idle timeout must be at least one second".to_string());
+    }
+    *state.policy.lock().unwrap() = policy;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_network_settings(state: State<WalletState>) -> NetworkSettings {
+    *state.network.lock().unwrap()
+}
+
+#[tauri::command]
+pub fn set_funding_strategy(
+    funding: FundingStrategy,
+    state: State<WalletState>,
+) -> Result<NetworkSettings, String> {
+    let mut network = state.network.lock().unwrap();
+    if !network.cluster.supports(funding) {
+        return Err(format!(
+            "this funding isn't available on {}",
+            network.cluster.as_str()
+        ));
+    }
+    network.funding = funding;
+    Ok(*network)
+}
+
+#[tauri::command]
+pub async fn transfer(
+    program_id: String,
+    mint: String,
+    recipient: String,
+    amount: u64,
+    decimals: u8,
+    state: State<'_, WalletState>,
+    server: State<'_, ServerState>,
+) -> Result<TransferResponse, String> {
+    send_transfer(
+        &state,
+        &server,
+        &program_id,
+        &mint,
+        &recipient,
+        amount,
+        decimals,
+    )
+    .await
+}
+
+// a transfer to an address of the address book, see sqlite::payment_address for the one picked
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+pub async fn pay_contact(
+    username: String,
+    address_id: Option<u64>,
+    program_id: String,
+    mint: String,
+    amount: u64,
+    decimals: u8,
+    state: State<'_, WalletState>,
+    server: State<'_, ServerState>,
+    storage: State<'_, StorageState>,
+) -> Result<TransferResponse, String> {
+    let recipient = {
+        let connection = storage.connection()?;
+        sqlite::payment_address(&connection, &username, address_id)?
+    };
+    send_transfer(
+        &state,
+        &server,
+        &program_id,
+        &mint,
+        &recipient,
+        amount,
+        decimals,
+    )
+    .await
+}
+
+// the rpc and server calls of a transfer block, they run on the blocking pool so the window
+// stays responsive, with a copy of the key taken before any of them
+async fn send_transfer(
+    state: &WalletState,
+    server: &ServerState,
+    program_id: &str,
+    mint: &str,
+    recipient: &str,
+    amount: u64,
+    decimals: u8,
+) -> Result<TransferResponse, String> {
+    let program_id = parse_pubkey(program_id, "token program")?;
+    let mint = parse_pubkey(mint, "token mint")?;
+    let recipient = parse_pubkey(recipient, "recipient wallet")?;
+    let network = *state.network.lock().unwrap();
+    let keypair = state.signer()?;
+    let client = server.client.clone();
+    let transfer = tauri::async_runtime::spawn_blocking(move || {
+        solana_wallet::transfer_spl(
+            &client,
+            &network,
+            &keypair,
+            &program_id,
+            &mint,
+            &recipient,
+            amount,
+            decimals,
+        )
+    });
+    match transfer.await {
+        Ok(result) => result,
+        Err(e) => Err(format!("the transfer was interrupted \n {}", e)),
+    }
+}
+
+// the only command handing the recovery phrase to the webview, called when the user asks to see it
+#[tauri::command]
+pub fn reveal_mnemonic(state: State<WalletState>) -> Result<Vec<String>, String> {
+    match state.pending_mnemonic.lock().unwrap().as_ref() {
+        Some(mnemonic) => Ok(mnemonic.words.to_vec()),
+        None => Err(
+            "the recovery phrase is only shown once, right after the account creation".to_string(),
+        ),
+    }
+}
+
+// a new quiz replaces the previous one, only its positions are accepted
+#[tauri::command]
+pub fn backup_quiz(count: usize, state: State<WalletState>) -> Result<BackupQuiz, String> {
+    if state.pending_mnemonic.lock().unwrap().is_none() {
+        return Err("no recovery phrase is waiting for a backup confirmation".to_string());
+    }
+    let quiz = solana_wallet::get_backup_quiz(count)?;
+    *state.quiz.lock().unwrap() = Some(quiz.clone());
+    Ok(quiz)
+}
+
+#[tauri::command]
+pub fn confirm_backup(answers: Vec<BackupAnswer>, state: State<WalletState>) -> Result<(), String> {
+    let mut pending = state.pending_mnemonic.lock().unwrap();
+    let mut quiz = state.quiz.lock().unwrap();
+    match (pending.as_ref(), quiz.as_ref()) {
+        (Some(mnemonic), Some(asked)) => solana_wallet::confirm_backup(mnemonic, asked, &answers)?,
+        (Some(_), None) => return Err("take the backup quiz first".to_string()),
+        (None, _) => {
+            return Err("no recovery phrase is waiting for a backup confirmation".to_string())
+        }
+    }
+    // confirmed, the phrase must not stay in memory any longer
+    *pending = None;
+    *quiz = None;
+    Ok(())
+}
+
+// for backups confirmed after a restart, when the generated phrase is gone from memory
+#[tauri::command]
+pub fn confirm_backup_with_phrase(words: Vec<String>) -> Result<(), String> {
+    let mnemonic = match words.try_into() {
+        Ok(words) => MnemonicInput { words },
+        Err(_) => return Err("the recovery phrase must have 12 words".to_string()),
+    };
+    solana_wallet::confirm_backup_with_phrase(&mnemonic)
+}
+
+#[tauri::command]
+pub fn backup_reminder() -> Option<String> {
+    solana_wallet::backup_reminder()
+}
+
+// Social recovery
+
+// the shares come back as the text the user hands out, see `MnemonicShare`'s Display
+#[tauri::command]
+pub fn split_recovery_phrase(
+    words: Vec<String>,
+    threshold: u8,
+    count: u8,
+) -> Result<Vec<String>, String> {
+    let mnemonic = match words.try_into() {
+        Ok(words) => MnemonicInput { words },
+        Err(_) => return Err("the recovery phrase must have 12 words".to_string()),
+    };
+    let shares = solana_wallet::split_account_mnemonic(&mnemonic, threshold, count)?;
+    Ok(shares.iter().map(|share| share.to_string()).collect())
+}
+
+// the rebuilt phrase waits for a backup confirmation like a freshly generated one
+#[tauri::command]
+pub fn recover_from_shares(
+    app: AppHandle,
+    shares: Vec<String>,
+    password: String,
+    state: State<WalletState>,
+    storage: State<StorageState>,
+) -> Result<AccountInfo, String> {
+    let mut parsed: Vec<MnemonicShare> = Vec::with_capacity(shares.len());
+    for share in shares.iter().filter(|share| !share.trim().is_empty()) {
+        parsed.push(MnemonicShare::from_str(share)?);
+    }
+    let recovered = solana_wallet::restore_from_shares(&parsed, &password)?;
+    storage.open(&app, &recovered.pub_key, &password);
+    Ok(state.start_new_account(&recovered))
+}
+
+pub(crate) fn parse_pubkey(value: &str, what: &str) -> Result<Pubkey, String> {
+    match Pubkey::from_str(value.trim()) {
+        Ok(pk) => Ok(pk),
+        Err(_) => Err(format!("{} is not a valid {} address", value, what)),
+    }
+}
@@ -1,20 +1,36 @@
+use crate::auth::AuthScreen;
+use crate::bridge::{self, invoke};
+use crate::chat::{ChatAction, ChatLog, ChatView};
+use crate::lock_screen::LockScreen;
+use crate::presence::{LiveAction, LiveState, TYPING_TIMEOUT_MS};
+use crate::profile_switcher::ProfileSwitcher;
+use crate::sidebar::Sidebar;
+use crate::types::{
+    ChatMessage, ConnectionEvent, ExpiredMessages, OutboxEntry, PresenceUpdate, Receipt,
+    RetentionUpdate, Typing, UserProfile,
+};
+use gloo_timers::callback::Timeout;
 use serde::{Deserialize, Serialize};
 use serde_wasm_bindgen::to_value;
-use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
 
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "tauri"])]
-    async fn invoke(cmd: &str, args: JsValue) -> JsValue;
-}
-
 #[derive(Serialize, Deserialize)]
 struct GreetArgs<'a> {
     name: &'a str,
 }
 
+#[derive(Deserialize)]
+struct WalletStatus {
+    pub_key: Option<String>,
+    unlocked: bool,
+}
+
+#[derive(Deserialize)]
+struct LockEvent {
+    reason: String,
+}
+
 #[function_component(App)]
 pub fn app() -> Html {
     let greet_input_ref = use_node_ref();
@@ -45,6 +61,223 @@ pub fn app() -> Html {
         );
     }
 
+    // the profile the app runs with, None until it's picked in the switcher
+    let profile = use_state(|| None::<String>);
+    // the switcher waits for the backend to tell whether --profile was given
+    let profile_checked = use_state(|| false);
+    {
+        let profile = profile.clone();
+        let profile_checked = profile_checked.clone();
+        use_effect_with_deps(
+            move |_| {
+                spawn_local(async move {
+                    if let Ok(current) =
+                        bridge::call::<_, Option<String>>("current_profile", &()).await
+                    {
+                        profile.set(current);
+                    }
+                    profile_checked.set(true);
+                });
+                || {}
+            },
+            (),
+        );
+    }
+    let on_profile_opened = {
+        let profile = profile.clone();
+        Callback::from(move |name: String| profile.set(Some(name)))
+    };
+
+    // Some(reason) while the wallet is locked
+    let locked = use_state(|| None::<String>);
+    {
+        let locked = locked.clone();
+        use_effect_with_deps(
+            move |_| {
+                bridge::subscribe("wallet-locked", move |event: LockEvent| {
+                    locked.set(Some(event.reason));
+                });
+                || {}
+            },
+            (),
+        );
+    }
+    // the wallet of the profile is read once it's opened
+    {
+        let locked = locked.clone();
+        let opened = profile.is_some();
+        use_effect_with_deps(
+            move |opened| {
+                if *opened {
+                    spawn_local(async move {
+                        if let Ok(status) =
+                            bridge::call::<_, WalletStatus>("wallet_status", &()).await
+                        {
+                            if status.pub_key.is_some() && !status.unlocked {
+                                locked.set(Some("startup".to_string()));
+                            }
+                        }
+                    });
+                }
+                || {}
+            },
+            opened,
+        );
+    }
+    let on_unlocked = {
+        let locked = locked.clone();
+        Callback::from(move |_| locked.set(None))
+    };
+    // the signed in Chatuza user, fetched again every time the wallet gets unlocked
+    let user = use_state(|| None::<UserProfile>);
+    let selected = use_state(|| None::<UserProfile>);
+    {
+        let user = user.clone();
+        let is_locked = locked.is_some();
+        let opened = profile.is_some();
+        use_effect_with_deps(
+            move |(is_locked, opened)| {
+                if *opened && !*is_locked {
+                    spawn_local(async move {
+                        if let Ok(current) =
+                            bridge::call::<_, Option<UserProfile>>("current_user", &()).await
+                        {
+                            user.set(current);
+                        }
+                    });
+                }
+                || {}
+            },
+            (is_locked, opened),
+        );
+    }
+    let on_signed_in = {
+        let user = user.clone();
+        Callback::from(move |profile: UserProfile| user.set(Some(profile)))
+    };
+    let on_signed_out = {
+        let user = user.clone();
+        let selected = selected.clone();
+        Callback::from(move |_| {
+            selected.set(None);
+            user.set(None);
+        })
+    };
+    // the message a search result opened the conversation at
+    let focus = use_state(|| None::<u64>);
+    let on_select = {
+        let selected = selected.clone();
+        let focus = focus.clone();
+        Callback::from(move |contact: UserProfile| {
+            focus.set(None);
+            selected.set(Some(contact))
+        })
+    };
+    let on_jump = {
+        let selected = selected.clone();
+        let focus = focus.clone();
+        Callback::from(move |(contact, message_id): (UserProfile, u64)| {
+            focus.set(Some(message_id));
+            selected.set(Some(contact))
+        })
+    };
+
+    // the chat connection follows the signed in user, the messages arrive as backend events
+    let chat_log = use_reducer(ChatLog::default);
+    // None while connected
+    let connection = use_state(|| Some("connecting...".to_string()));
+    let live = use_reducer(LiveState::default);
+    {
+        let chat_log = chat_log.clone();
+        let connection = connection.clone();
+        let live = live.clone();
+        use_effect_with_deps(
+            move |_| {
+                let on_message = chat_log.clone();
+                let stopped_typing = live.clone();
+                bridge::subscribe("chat-message", move |message: ChatMessage| {
+                    stopped_typing.dispatch(LiveAction::StoppedTyping(
+                        message.conversation_id.clone(),
+                        message.sender.clone(),
+                    ));
+                    on_message.dispatch(ChatAction::Received(message))
+                });
+                let on_presence = live.clone();
+                bridge::subscribe("chat-presence", move |update: PresenceUpdate| {
+                    on_presence.dispatch(LiveAction::Presence(update))
+                });
+                let mut stamp: u32 = 0;
+                bridge::subscribe("chat-typing", move |typing: Typing| {
+                    stamp = stamp.wrapping_add(1);
+                    let conversation_id = typing.conversation_id.clone();
+                    live.dispatch(LiveAction::Typing(typing, stamp));
+                    let live = live.clone();
+                    let stamp = stamp;
+                    Timeout::new(TYPING_TIMEOUT_MS, move || {
+                        live.dispatch(LiveAction::TypingExpired(conversation_id, stamp))
+                    })
+                    .forget();
+                });
+                let on_receipt = chat_log.clone();
+                bridge::subscribe("chat-receipt", move |receipt: Receipt| {
+                    on_receipt.dispatch(ChatAction::Receipt(receipt))
+                });
+                let on_retention = chat_log.clone();
+                bridge::subscribe("chat-retention", move |update: RetentionUpdate| {
+                    on_retention.dispatch(ChatAction::Retention(update))
+                });
+                let on_expired = chat_log.clone();
+                bridge::subscribe("chat-expired", move |expired: ExpiredMessages| {
+                    on_expired.dispatch(ChatAction::Expired(expired))
+                });
+                bridge::subscribe("chat-outbox", move |entry: OutboxEntry| {
+                    chat_log.dispatch(ChatAction::Queued(entry))
+                });
+                bridge::subscribe("chat-connection", move |event: ConnectionEvent| {
+                    connection.set(match event {
+                        ConnectionEvent::Connected => None,
+                        ConnectionEvent::Disconnected { retry_in_secs, .. } => {
+                            Some(format!("offline, reconnecting in {}s", retry_in_secs))
+                        }
+                        ConnectionEvent::ServerError { message }
+                        | ConnectionEvent::LocalError { message } => Some(message),
+                    })
+                });
+                || {}
+            },
+            (),
+        );
+    }
+    // the history and the chat close with the wallet, they're opened again on unlock
+    {
+        let signed_in = user.is_some();
+        let is_locked = locked.is_some();
+        let chat_log = chat_log.clone();
+        use_effect_with_deps(
+            move |(signed_in, is_locked)| {
+                if *signed_in && !*is_locked {
+                    spawn_local(async move {
+                        let _ = bridge::call::<_, ()>("connect_chat", &()).await;
+                        // what was left unsent last time
+                        if let Ok(entries) =
+                            bridge::call::<_, Vec<OutboxEntry>>("list_outbox", &()).await
+                        {
+                            chat_log.dispatch(ChatAction::OutboxLoaded(entries));
+                        }
+                    });
+                }
+                || {}
+            },
+            (signed_in, is_locked),
+        );
+    }
+    // any interaction keeps the unlocked wallet alive
+    let report_activity = Callback::from(|_: MouseEvent| {
+        spawn_local(async {
+            let _ = bridge::call::<_, ()>("report_activity", &()).await;
+        })
+    });
+
     let _greet = {
         let name = name.clone();
         let greet_input_ref = greet_input_ref.clone();
@@ -60,227 +293,50 @@ pub fn app() -> Html {
     };
 
     html! {
-                // <main class="container">
-                //     <div class="row">
-                //         <a href="https://tauri.app" target="_blank">
-                //             <img src="public/tauri.svg" class="logo tauri" alt="Tauri logo"/>
-                //         </a>
-                //         <a href="https://yew.rs" target="_blank">
-                //             <img src="public/yew.png" class="logo yew" alt="Yew logo"/>
-                //         </a>
-                //     </div>
+            // <main class="container">
+            //     <div class="row">
+            //         <a href="https://tauri.app" target="_blank">
+            //             <img src="public/tauri.svg" class="logo tauri" alt="Tauri logo"/>
+            //         </a>
+            //         <a href="https://yew.rs" target="_blank">
+            //             <img src="public/yew.png" class="logo yew" alt="Yew logo"/>
+            //         </a>
+            //     </div>
 
-                //     <p>{"Click on the Tauri and Yew logos to learn more."}</p>
+            //     <p>{"Click on the Tauri and Yew logos to learn more."}</p>
 
-                //     <p>
-                //         {"Recommended IDE setup: "}
-                //         <a href="https://code.visualstudio.com/" target="_blank">{"VS Code"}</a>
-                //         {" + "}
-                //         <a href="https://github.com/tauri-apps/tauri-vscode" target="_blank">{"Tauri"}</a>
-                //         {" + "}
-                //         <a href="https://github.com/rust-lang/rust-analyzer" target="_blank">{"rust-analyzer"}</a>
-                //     </p>
+            //     <p>
+            //         {"Recommended IDE setup: "}
+            //         <a href="https://code.visualstudio.com/" target="_blank">{"VS Code"}</a>
+            //         {" + "}
+            //         <a href="https://github.com/tauri-apps/tauri-vscode" target="_blank">{"Tauri"}</a>
+            //         {" + "}
+            //         <a href="https://github.com/rust-lang/rust-analyzer" target="_blank">{"rust-analyzer"}</a>
+            //     </p>
 
-                //     <form class="row" onsubmit={greet}>
-                //         <input id="greet-input" ref={greet_input_ref} placeholder="Enter a name..." />
-                //         <button type="submit">{"Greet"}</button>
-                //     </form>
+            //     <form class="row" onsubmit={greet}>
+            //         <input id="greet-input" ref={greet_input_ref} placeholder="Enter a name..." />
+            //         <button type="submit">{"Greet"}</button>
+            //     </form>
 
-                //     <p><b>{ &*greet_msg }</b></p>
-                // </main>
-                <div id="container">
-            <aside>
-                <header>
-                    <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/ico_search.png"/>
-                    <input type="text" placeholder="search"/>
-                </header>
-                <ul>
-                    <li>
-                        <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/chat_avatar_01.jpg" alt=""/>
-                        <div>
-                            <h2>{"Prénom Nom"}</h2>
-                            <h3>
-                                <span class="status orange"></span>
-                                {"offline"}
-                            </h3>
-                        </div>
-                    </li>
-                    <li>
-                        <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/chat_avatar_02.jpg" alt=""/>
-                        <div>
-                            <h2>{"Prénom Nom"}</h2>
-                            <h3>
-                                <span class="status green"></span>
-                                {"online"}
-                            </h3>
-                        </div>
-                    </li>
-                    <li>
-                        <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/chat_avatar_03.jpg" alt=""/>
-                        <div>
-                            <h2>{"Prénom Nom"}</h2>
-                            <h3>
-                                <span class="status orange"></span>
-                                {"offline"}
-                            </h3>
-                        </div>
-                    </li>
-                    <li>
-                        <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/chat_avatar_04.jpg" alt=""/>
-                        <div>
-                            <h2>{"Prénom Nom"}</h2>
-                            <h3>
-                                <span class="status green"></span>
-                                {"online"}
-                            </h3>
-                        </div>
-                    </li>
-                    <li>
-                        <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/chat_avatar_05.jpg" alt=""/>
-                        <div>
-                            <h2>{"Prénom Nom"}</h2>
-                            <h3>
-                                <span class="status orange"></span>
-                                {"offline"}
-                            </h3>
-                        </div>
-                    </li>
-                    <li>
-                        <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/chat_avatar_06.jpg" alt=""/>
-                        <div>
-                            <h2>{"Prénom Nom"}</h2>
-                            <h3>
-                                <span class="status green"></span>
-                                {"online"}
-                            </h3>
-                        </div>
-                    </li>
-                    <li>
-                        <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/chat_avatar_07.jpg" alt=""/>
-                        <div>
-                            <h2>{"Prénom Nom"}</h2>
-                            <h3>
-                                <span class="status green"></span>
-                                {"online"}
-                            </h3>
-                        </div>
-                    </li>
-                    <li>
-                        <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/chat_avatar_08.jpg" alt=""/>
-                        <div>
-                            <h2>{"Prénom Nom"}</h2>
-                            <h3>
-                                <span class="status green"></span>
-                                {"online"}
-                            </h3>
-                        </div>
-                    </li>
-                    <li>
-                        <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/chat_avatar_09.jpg" alt=""/>
-                        <div>
-                            <h2>{"Prénom Nom"}</h2>
-                            <h3>
-                                <span class="status green"></span>
-                                {"online"}
-                            </h3>
-                        </div>
-                    </li>
-                    <li>
-                        <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/chat_avatar_10.jpg" alt=""/>
-                        <div>
-                            <h2>{"Prénom Nom"}</h2>
-                            <h3>
-                                <span class="status orange"></span>
-                                {"offline"}
-                            </h3>
-                        </div>
-                    </li>
-                </ul>
-            </aside>
-            <main>
-                <header>
-                    <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/chat_avatar_01.jpg" alt=""/>
-                    <div>
-                        <h2>{"Chat with Vincent Porter"}</h2>
-                        <h3>{"already 1902 messages"}</h3>
-                    </div>
-                    <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/ico_star.png" alt=""/>
-                </header>
-                <ul id="chat">
-                    <li class="you">
-                        <div class="entete">
-                            <span class="status green"></span>
-                            <h2>{"Vincent"}</h2>
-                            <h3>{"10:12AM, Today"}</h3>
-                        </div>
-                        <div class="triangle"></div>
-                        <div class="message">
-    {"                        Lorem ipsum dolor sit amet, consectetuer adipiscing elit. Aenean commodo ligula eget dolor.
-    "}                    </div>
-                    </li>
-                    <li class="me">
-                        <div class="entete">
-                            <h3>{"10:12AM, Today"}</h3>
-                            <h2>{"Vincent"}</h2>
-                            <span class="status blue"></span>
-                        </div>
-                        <div class="triangle"></div>
-                        <div class="message">
-    {"                        Lorem ipsum dolor sit amet, consectetuer adipiscing elit. Aenean commodo ligula eget dolor.
-    "}                    </div>
-                    </li>
-                    <li class="me">
-                        <div class="entete">
-                            <h3>{"10:12AM, Today"}</h3>
-                            <h2>{"Vincent"}</h2>
-                            <span class="status blue"></span>
-                        </div>
-                        <div class="triangle"></div>
-                        <div class="message">
-                            {"OK"}
-                        </div>
-                    </li>
-                    <li class="you">
-                        <div class="entete">
-                            <span class="status green"></span>
-                            <h2>{"Vincent"}</h2>
-                            <h3>{"10:12AM, Today"}</h3>
-                        </div>
-                        <div class="triangle"></div>
-                        <div class="message">
-    {"                        Lorem ipsum dolor sit amet, consectetuer adipiscing elit. Aenean commodo ligula eget dolor.
-    "}                    </div>
-                    </li>
-                    <li class="me">
-                        <div class="entete">
-                            <h3>{"10:12AM, Today"}</h3>
-                            <h2>{"Vincent"}</h2>
-                            <span class="status blue"></span>
-                        </div>
-                        <div class="triangle"></div>
-                        <div class="message">
-    {"                        Lorem ipsum dolor sit amet, consectetuer adipiscing elit. Aenean commodo ligula eget dolor.
-    "}                    </div>
-                    </li>
-                    <li class="me">
-                        <div class="entete">
-                            <h3>{"10:12AM, Today"}</h3>
-                            <h2>{"Vincent"}</h2>
-                            <span class="status blue"></span>
-                        </div>
-                        <div class="triangle"></div>
-                        <div class="message">
-                            {"OK"}
-                        </div>
-                    </li>
-                </ul>
-                <footer>
-                    <textarea placeholder="Type your message"></textarea>
-                    <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/ico_picture.png" alt=""/>
-                    <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/ico_file.png" alt=""/>
-                    <a href="#">{"Send"}</a>
-                </footer>
-            </main>
-        </div>
+            //     <p><b>{ &*greet_msg }</b></p>
+            // </main>
+            <div id="container" onclick={report_activity}>
+        if profile.is_none() {
+            if *profile_checked {
+                <ProfileSwitcher on_opened={on_profile_opened}/>
             }
+        } else if let Some(reason) = &*locked {
+            <LockScreen reason={reason.clone()} on_unlocked={on_unlocked}/>
+        } else if user.is_none() {
+            <AuthScreen on_signed_in={on_signed_in}/>
+        }
+        if let Some(user) = &*user {
+            <Sidebar user={user.clone()} live={live.clone()} selected={selected.as_ref().map(|contact| contact.username.clone())} on_select={on_select} on_jump={on_jump} on_signed_out={on_signed_out}/>
+        }
+        if let Some(user) = &*user {
+            <ChatView user={user.clone()} live={live.clone()} contact={(*selected).clone()} focus={*focus} log={chat_log.clone()} connection={(*connection).clone()}/>
+        }
+    </div>
+        }
 }
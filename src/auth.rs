@@ -0,0 +1,162 @@
+use crate::bridge;
+use crate::shares::RecoverScreen;
+use crate::types::UserProfile;
+use serde::Serialize;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SignUpArgs {
+    username: String,
+    display_name: String,
+    avatar_url: Option<String>,
+    password: String,
+}
+
+#[derive(Serialize)]
+struct SignInArgs {
+    username: String,
+    password: String,
+}
+
+#[derive(Properties, PartialEq)]
+pub struct AuthScreenProps {
+    pub on_signed_in: Callback<UserProfile>,
+}
+
+#[function_component(AuthScreen)]
+pub fn auth_screen(props: &AuthScreenProps) -> Html {
+    let signing_up = use_state(|| false);
+    let recovering = use_state(|| false);
+    // the address of a wallet just rebuilt from shares
+    let recovered = use_state(|| None::<String>);
+    let error = use_state(|| None::<String>);
+    let username_ref = use_node_ref();
+    let display_name_ref = use_node_ref();
+    let avatar_ref = use_node_ref();
+    let password_ref = use_node_ref();
+
+    let submit = {
+        let signing_up = signing_up.clone();
+        let error = error.clone();
+        let refs = (
+            username_ref.clone(),
+            display_name_ref.clone(),
+            avatar_ref.clone(),
+            password_ref.clone(),
+        );
+        let on_signed_in = props.on_signed_in.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            let value = |node: &NodeRef| {
+                node.cast::<HtmlInputElement>()
+                    .map(|input| input.value())
+                    .unwrap_or_default()
+            };
+            let username = value(&refs.0);
+            let password = value(&refs.3);
+            let sign_up = (*signing_up).then(|| SignUpArgs {
+                username: username.clone(),
+                display_name: value(&refs.1),
+                avatar_url: Some(value(&refs.2)).filter(|url| !url.is_empty()),
+                password: password.clone(),
+            });
+            let error = error.clone();
+            let on_signed_in = on_signed_in.clone();
+            spawn_local(async move {
+                let result = match sign_up {
+                    Some(args) => bridge::call::<_, UserProfile>("sign_up", &args).await,
+                    None => {
+                        let args = SignInArgs { username, password };
+                        bridge::call::<_, UserProfile>("sign_in", &args).await
+                    }
+                };
+                match result {
+                    Ok(profile) => on_signed_in.emit(profile),
+                    Err(e) => error.set(Some(e)),
+                }
+            });
+        })
+    };
+
+    let wallet_sign_in = {
+        let error = error.clone();
+        let on_signed_in = props.on_signed_in.clone();
+        Callback::from(move |_: MouseEvent| {
+            let error = error.clone();
+            let on_signed_in = on_signed_in.clone();
+            spawn_local(async move {
+                match bridge::call::<_, UserProfile>("sign_in_with_wallet", &()).await {
+                    Ok(profile) => on_signed_in.emit(profile),
+                    Err(e) => error.set(Some(e)),
+                }
+            });
+        })
+    };
+
+    let toggle = {
+        let signing_up = signing_up.clone();
+        let error = error.clone();
+        Callback::from(move |_: MouseEvent| {
+            error.set(None);
+            signing_up.set(!*signing_up);
+        })
+    };
+
+    let open_recovery = {
+        let recovering = recovering.clone();
+        Callback::from(move |_: MouseEvent| recovering.set(true))
+    };
+    let on_recovered = {
+        let recovering = recovering.clone();
+        let recovered = recovered.clone();
+        let error = error.clone();
+        Callback::from(move |pub_key: String| {
+            error.set(None);
+            recovered.set(Some(pub_key));
+            recovering.set(false);
+        })
+    };
+    let on_recovery_cancel = {
+        let recovering = recovering.clone();
+        Callback::from(move |_| recovering.set(false))
+    };
+
+    if *recovering {
+        return html! {
+            <RecoverScreen on_recovered={on_recovered} on_cancel={on_recovery_cancel}/>
+        };
+    }
+
+    html! {
+        <div class="overlay-screen">
+            <form onsubmit={submit}>
+                <h2>{ if *signing_up { "Create your Chatuza account" } else { "Sign in to Chatuza" } }</h2>
+                if let Some(pub_key) = &*recovered {
+                    <h3>{format!("wallet {} recovered, sign in with it", pub_key)}</h3>
+                }
+                <input type="text" ref={username_ref} placeholder="username"/>
+                if *signing_up {
+                    <input type="text" ref={display_name_ref} placeholder="display name"/>
+                    <input type="url" ref={avatar_ref} placeholder="avatar url (optional)"/>
+                }
+                <input type="password" ref={password_ref} placeholder="password"/>
+                <button type="submit">{ if *signing_up { "Sign up" } else { "Sign in" } }</button>
+                if !*signing_up {
+                    <button type="button" class="secondary" onclick={wallet_sign_in}>
+                        {"Sign in with my wallet"}
+                    </button>
+                    <a class="switch" onclick={open_recovery}>{"Recover my wallet from shares"}</a>
+                }
+                <a class="switch" onclick={toggle}>
+                    { if *signing_up { "I already have an account" } else { "Create an account" } }
+                </a>
+                if let Some(error) = &*error {
+                    <p class="error">{error}</p>
+                }
+            </form>
+        </div>
+    }
+}
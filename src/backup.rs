@@ -0,0 +1,192 @@
+use crate::bridge;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{HtmlInputElement, HtmlTextAreaElement};
+use yew::prelude::*;
+
+// words asked in the quiz
+const QUIZ_WORDS: usize = 3;
+
+#[derive(Serialize)]
+struct QuizArgs {
+    count: usize,
+}
+
+#[derive(Deserialize)]
+struct BackupQuiz {
+    positions: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct BackupAnswer {
+    position: usize,
+    word: String,
+}
+
+#[derive(Serialize)]
+struct ConfirmArgs {
+    answers: Vec<BackupAnswer>,
+}
+
+#[derive(Serialize)]
+struct PhraseArgs {
+    words: Vec<String>,
+}
+
+#[derive(Clone, PartialEq)]
+enum Step {
+    Loading,
+    // the phrase of the account just created, shown once
+    Words(Vec<String>),
+    // the positions to fill in from what the user wrote down
+    Quiz(Vec<usize>),
+    // the phrase is gone from memory after a restart, the whole of it is typed back
+    Phrase,
+}
+
+#[derive(Properties, PartialEq)]
+pub struct BackupScreenProps {
+    pub on_confirmed: Callback<()>,
+    pub on_cancel: Callback<()>,
+}
+
+// the quiz proving the recovery phrase was written down, large transfers wait for it
+#[function_component(BackupScreen)]
+pub fn backup_screen(props: &BackupScreenProps) -> Html {
+    let step = use_state(|| Step::Loading);
+    let error = use_state(|| None::<String>);
+    let answer_refs = use_state(Vec::<NodeRef>::new);
+    let phrase_ref = use_node_ref();
+    {
+        let step = step.clone();
+        use_effect_with_deps(
+            move |_| {
+                spawn_local(async move {
+                    match bridge::call::<_, Vec<String>>("reveal_mnemonic", &()).await {
+                        Ok(words) => step.set(Step::Words(words)),
+                        Err(_) => step.set(Step::Phrase),
+                    }
+                });
+                || {}
+            },
+            (),
+        );
+    }
+
+    let start_quiz = {
+        let step = step.clone();
+        let error = error.clone();
+        let answer_refs = answer_refs.clone();
+        Callback::from(move |_: MouseEvent| {
+            let step = step.clone();
+            let error = error.clone();
+            let answer_refs = answer_refs.clone();
+            spawn_local(async move {
+                let args = QuizArgs { count: QUIZ_WORDS };
+                match bridge::call::<_, BackupQuiz>("backup_quiz", &args).await {
+                    Ok(quiz) => {
+                        answer_refs
+                            .set(quiz.positions.iter().map(|_| NodeRef::default()).collect());
+                        error.set(None);
+                        step.set(Step::Quiz(quiz.positions));
+                    }
+                    Err(e) => error.set(Some(e)),
+                }
+            });
+        })
+    };
+    let submit = {
+        let step = step.clone();
+        let error = error.clone();
+        let answer_refs = answer_refs.clone();
+        let phrase_ref = phrase_ref.clone();
+        let on_confirmed = props.on_confirmed.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            let error = error.clone();
+            let on_confirmed = on_confirmed.clone();
+            match &*step {
+                Step::Quiz(positions) => {
+                    let answers = positions
+                        .iter()
+                        .zip(answer_refs.iter())
+                        .map(|(position, node)| BackupAnswer {
+                            position: *position,
+                            word: node
+                                .cast::<HtmlInputElement>()
+                                .map(|input| input.value())
+                                .unwrap_or_default(),
+                        })
+                        .collect();
+                    let args = ConfirmArgs { answers };
+                    spawn_local(async move {
+                        match bridge::call::<_, ()>("confirm_backup", &args).await {
+                            Ok(()) => on_confirmed.emit(()),
+                            Err(e) => error.set(Some(e)),
+                        }
+                    });
+                }
+                Step::Phrase => {
+                    let phrase = phrase_ref
+                        .cast::<HtmlTextAreaElement>()
+                        .map(|textarea| textarea.value())
+                        .unwrap_or_default();
+                    let args = PhraseArgs {
+                        words: phrase.split_whitespace().map(str::to_lowercase).collect(),
+                    };
+                    spawn_local(async move {
+                        match bridge::call::<_, ()>("confirm_backup_with_phrase", &args).await {
+                            Ok(()) => on_confirmed.emit(()),
+                            Err(e) => error.set(Some(e)),
+                        }
+                    });
+                }
+                Step::Loading | Step::Words(_) => {}
+            }
+        })
+    };
+    let cancel = {
+        let on_cancel = props.on_cancel.clone();
+        Callback::from(move |_: MouseEvent| on_cancel.emit(()))
+    };
+
+    html! {
+        <div class="overlay-screen">
+            <form onsubmit={submit}>
+                <h2>{"Confirm your recovery phrase"}</h2>
+                { match &*step {
+                    Step::Loading => html! {},
+                    Step::Words(words) => html! {
+                        <>
+                            <h3>{"write these words down in order and keep them offline, they're shown only once"}</h3>
+                            <ol class="phrase">
+                                { for words.iter().map(|word| html! { <li>{word}</li> }) }
+                            </ol>
+                            <button type="button" onclick={start_quiz}>{"I wrote them down"}</button>
+                        </>
+                    },
+                    Step::Quiz(positions) => html! {
+                        <>
+                            <h3>{"fill in these words from what you wrote down"}</h3>
+                            { for positions.iter().zip(answer_refs.iter()).map(|(position, node)| html! {
+                                <input type="text" ref={node.clone()} placeholder={format!("word #{}", position + 1)}/>
+                            }) }
+                            <button type="submit">{"Confirm"}</button>
+                        </>
+                    },
+                    Step::Phrase => html! {
+                        <>
+                            <h3>{"type the 12 words of your recovery phrase, separated by spaces"}</h3>
+                            <textarea ref={phrase_ref.clone()} placeholder="recovery phrase"></textarea>
+                            <button type="submit">{"Confirm"}</button>
+                        </>
+                    },
+                } }
+                <a class="switch" onclick={cancel}>{"Later"}</a>
+                if let Some(error) = &*error {
+                    <p class="error">{error}</p>
+                }
+            </form>
+        </div>
+    }
+}
@@ -0,0 +1,45 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_wasm_bindgen::{from_value, to_value};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "tauri"])]
+    pub async fn invoke(cmd: &str, args: JsValue) -> JsValue;
+
+    // same command, but a rejected promise (a command returning Err) comes back as Err
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "tauri"], js_name = invoke, catch)]
+    async fn invoke_catch(cmd: &str, args: JsValue) -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "event"])]
+    async fn listen(event: &str, handler: &Closure<dyn FnMut(JsValue)>) -> JsValue;
+}
+
+// calls a fallible Tauri command, the error is the displayable message returned by the backend
+pub async fn call<A: Serialize, R: DeserializeOwned>(cmd: &str, args: &A) -> Result<R, String> {
+    let args = to_value(args).map_err(|e| e.to_string())?;
+    match invoke_catch(cmd, args).await {
+        Ok(value) => from_value(value).map_err(|e| e.to_string()),
+        Err(e) => Err(e.as_string().unwrap_or_else(|| format!("{} failed", cmd))),
+    }
+}
+
+// subscribes to a backend event for the whole lifetime of the app
+pub fn subscribe<T: DeserializeOwned + 'static>(
+    event: &'static str,
+    mut handler: impl FnMut(T) + 'static,
+) {
+    let closure = Closure::<dyn FnMut(JsValue)>::new(move |raw: JsValue| {
+        let payload = js_sys::Reflect::get(&raw, &JsValue::from_str("payload"))
+            .ok()
+            .and_then(|payload| from_value::<T>(payload).ok());
+        if let Some(payload) = payload {
+            handler(payload);
+        }
+    });
+    wasm_bindgen_futures::spawn_local(async move {
+        listen(event, &closure).await;
+        closure.forget(); // the listener is never removed
+    });
+}
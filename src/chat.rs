@@ -0,0 +1,749 @@
+use crate::bridge;
+use crate::presence::{self, LiveState};
+use crate::types::{
+    ChatMessage, ExpiredMessages, MessagePage, MessageStatus, OutboxEntry, OutboxState, Receipt,
+    RetentionUpdate, UserProfile, DEFAULT_AVATAR,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{Element, HtmlTextAreaElement};
+use yew::prelude::*;
+
+// messages loaded at once, when a conversation opens and when the scroll nears an end
+const PAGE_SIZE: u32 = 50;
+// the height of a message that isn't rendered, in pixels. The list only renders what's
+// around the visible part and stands the rest in with space of this height per message
+const ROW_HEIGHT: i32 = 105;
+// messages rendered above and below the visible ones
+const OVERSCAN: usize = 8;
+// the next page is loaded once the scroll is this close to an end of the list
+const LOAD_MARGIN: i32 = 400;
+// the disappearing timers offered in the header
+const RETENTION_CHOICES: [(Option<u64>, &str); 5] = [
+    (None, "off"),
+    (Some(60 * 60), "1 hour"),
+    (Some(24 * 60 * 60), "1 day"),
+    (Some(7 * 24 * 60 * 60), "1 week"),
+    (Some(28 * 24 * 60 * 60), "4 weeks"),
+];
+
+#[derive(Serialize)]
+struct ConversationArgs {
+    username: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ConversationIdArgs {
+    conversation_id: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PageArgs {
+    conversation_id: String,
+    before: Option<u64>,
+    after: Option<u64>,
+    limit: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MarkReadArgs {
+    conversation_id: String,
+    up_to: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RetryArgs {
+    client_id: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RetentionArgs {
+    conversation_id: String,
+    retention_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SendArgs {
+    conversation_id: String,
+    body: String,
+}
+
+// the loaded part of every conversation in server order,
+// plus our messages the server didn't accept yet and the disappearing timers
+#[derive(Default, PartialEq)]
+pub struct ChatLog {
+    conversations: HashMap<String, History>,
+    outbox: Vec<OutboxEntry>,
+    retention: HashMap<String, RetentionUpdate>,
+}
+
+// the part of a conversation loaded from the history kept on this device
+#[derive(Default, Clone, PartialEq)]
+pub struct History {
+    pub messages: Vec<ChatMessage>,
+    // older messages are left on disk until scrolled to
+    pub has_older: bool,
+    // the newer ones too after a jump to an old message, what arrives meanwhile waits with them
+    pub has_newer: bool,
+}
+
+pub enum ChatAction {
+    // the latest page, when the conversation is opened
+    Opened(String, MessagePage),
+    // the pages before and after a message jumped to
+    Around(String, MessagePage, MessagePage),
+    Older(String, MessagePage),
+    Newer(String, MessagePage),
+    Received(ChatMessage),
+    Receipt(Receipt),
+    Queued(OutboxEntry),
+    OutboxLoaded(Vec<OutboxEntry>),
+    Retention(RetentionUpdate),
+    // deleted by the backend, the timer ran out or the history went over the cap
+    Expired(ExpiredMessages),
+}
+
+impl ChatLog {
+    pub fn history(&self, conversation_id: &str) -> History {
+        self.conversations
+            .get(conversation_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn outbox(&self, conversation_id: &str) -> Vec<OutboxEntry> {
+        self.outbox
+            .iter()
+            .filter(|entry| entry.conversation_id == conversation_id)
+            .cloned()
+            .collect()
+    }
+
+    pub fn retention(&self, conversation_id: &str) -> Option<u64> {
+        self.retention
+            .get(conversation_id)
+            .and_then(|update| update.retention_secs)
+    }
+}
+
+impl Reducible for ChatLog {
+    type Action = ChatAction;
+
+    fn reduce(self: Rc<Self>, action: ChatAction) -> Rc<Self> {
+        let mut conversations = self.conversations.clone();
+        let mut outbox = self.outbox.clone();
+        let mut retention = self.retention.clone();
+        match action {
+            ChatAction::Opened(conversation_id, page) => {
+                // what arrived while the page was loading is kept
+                let newest = page.messages.last().map(|message| message.id);
+                let arrived: Vec<ChatMessage> = conversations
+                    .get(&conversation_id)
+                    .map(|history| history.messages.clone())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|message| Some(message.id) > newest)
+                    .collect();
+                let mut history = History {
+                    messages: page.messages,
+                    has_older: page.has_more,
+                    has_newer: false,
+                };
+                for message in arrived {
+                    insert_message(&mut history.messages, message);
+                }
+                conversations.insert(conversation_id, history);
+            }
+            ChatAction::Around(conversation_id, older, newer) => {
+                let mut messages = older.messages;
+                messages.extend(newer.messages);
+                let history = History {
+                    messages,
+                    has_older: older.has_more,
+                    has_newer: newer.has_more,
+                };
+                conversations.insert(conversation_id, history);
+            }
+            ChatAction::Older(conversation_id, page) => {
+                let history = conversations.entry(conversation_id).or_default();
+                for message in page.messages {
+                    insert_message(&mut history.messages, message);
+                }
+                history.has_older = page.has_more;
+            }
+            ChatAction::Newer(conversation_id, page) => {
+                let history = conversations.entry(conversation_id).or_default();
+                for message in page.messages {
+                    insert_message(&mut history.messages, message);
+                }
+                history.has_newer = page.has_more;
+            }
+            ChatAction::Received(message) => {
+                if let Some(client_id) = &message.client_id {
+                    outbox.retain(|entry| &entry.client_id != client_id);
+                }
+                let history = conversations
+                    .entry(message.conversation_id.clone())
+                    .or_default();
+                if !history.has_newer {
+                    insert_message(&mut history.messages, message);
+                }
+            }
+            ChatAction::Receipt(receipt) => {
+                if let Some(history) = conversations.get_mut(&receipt.conversation_id) {
+                    for message in history.messages.iter_mut() {
+                        if message.id <= receipt.up_to && message.status < receipt.status {
+                            message.status = receipt.status;
+                        }
+                    }
+                }
+            }
+            ChatAction::Queued(entry) => {
+                // the server may have accepted it before the command returned
+                let accepted = conversations
+                    .values()
+                    .flat_map(|history| history.messages.iter())
+                    .any(|message| message.client_id.as_deref() == Some(entry.client_id.as_str()));
+                if !accepted {
+                    match outbox
+                        .iter_mut()
+                        .find(|known| known.client_id == entry.client_id)
+                    {
+                        Some(known) => *known = entry,
+                        None => outbox.push(entry),
+                    }
+                }
+            }
+            ChatAction::OutboxLoaded(entries) => outbox = entries,
+            ChatAction::Retention(update) => {
+                // the event and the loaded timer may arrive in any order, the latest change wins
+                let newer = retention
+                    .get(&update.conversation_id)
+                    .is_none_or(|known| known.set_at <= update.set_at);
+                if newer {
+                    retention.insert(update.conversation_id.clone(), update);
+                }
+            }
+            ChatAction::Expired(expired) => {
+                if let Some(history) = conversations.get_mut(&expired.conversation_id) {
+                    history
+                        .messages
+                        .retain(|message| !expired.ids.contains(&message.id));
+                }
+            }
+        }
+        Rc::new(ChatLog {
+            conversations,
+            outbox,
+            retention,
+        })
+    }
+}
+
+// keeps the conversation in server order, a message seen twice keeps its furthest status
+fn insert_message(messages: &mut Vec<ChatMessage>, message: ChatMessage) {
+    match messages.binary_search_by_key(&message.id, |known| known.id) {
+        Ok(position) => {
+            let known = &mut messages[position];
+            known.status = known.status.max(message.status);
+        }
+        Err(position) => messages.insert(position, message),
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ChatViewProps {
+    pub user: UserProfile,
+    pub contact: Option<UserProfile>,
+    // the message to scroll to, picked in the search results
+    pub focus: Option<u64>,
+    pub log: UseReducerHandle<ChatLog>,
+    pub live: UseReducerHandle<LiveState>,
+    // what to show under the contact name while the connection is down
+    pub connection: Option<String>,
+}
+
+#[function_component(ChatView)]
+pub fn chat_view(props: &ChatViewProps) -> Html {
+    let conversation_id = use_state(|| None::<String>);
+    let error = use_state(|| None::<String>);
+    let composer = use_node_ref();
+    let focused = use_node_ref();
+    let list = use_node_ref();
+    // (scroll top, visible height) of the list, the rendered messages follow it
+    let viewport = use_state(|| (0, 0));
+    let loading = use_mut_ref(|| false);
+    // the list stays at the bottom while the user is there
+    let at_bottom = use_mut_ref(|| true);
+    // what the list has to scroll down by once the older messages it loaded are rendered
+    let pending_shift = use_mut_ref(|| 0);
+    let scrolled_to = use_mut_ref(|| None::<u64>);
+
+    let (history, outbox) = match &*conversation_id {
+        Some(id) => (props.log.history(id), props.log.outbox(id)),
+        None => (History::default(), vec![]),
+    };
+    let messages = &history.messages;
+    let (top, height) = *viewport;
+    let first = ((top / ROW_HEIGHT).max(0) as usize)
+        .saturating_sub(OVERSCAN)
+        .min(messages.len());
+    let last = (((top + height) / ROW_HEIGHT).max(0) as usize + OVERSCAN + 1)
+        .max(first)
+        .min(messages.len());
+    {
+        let conversation_id = conversation_id.clone();
+        let log = props.log.clone();
+        let at_bottom = at_bottom.clone();
+        let username = props
+            .contact
+            .as_ref()
+            .map(|contact| contact.username.clone());
+        use_effect_with_deps(
+            move |(username, focus)| {
+                conversation_id.set(None);
+                if let Some(username) = username.clone() {
+                    let log = log.clone();
+                    let focus = *focus;
+                    spawn_local(async move {
+                        let args = ConversationArgs { username };
+                        let id = match bridge::call::<_, String>("conversation_with", &args).await {
+                            Ok(id) => id,
+                            Err(_) => return,
+                        };
+                        let args = ConversationIdArgs {
+                            conversation_id: id.clone(),
+                        };
+                        if let Ok(Some(update)) =
+                            bridge::call::<_, Option<RetentionUpdate>>("get_retention", &args).await
+                        {
+                            log.dispatch(ChatAction::Retention(update));
+                        }
+                        match focus {
+                            Some(message_id) => {
+                                let half = PAGE_SIZE / 2;
+                                let older = load_page(&id, Some(message_id + 1), None, half).await;
+                                let newer = load_page(&id, None, Some(message_id), half).await;
+                                if let (Ok(older), Ok(newer)) = (older, newer) {
+                                    log.dispatch(ChatAction::Around(id.clone(), older, newer));
+                                }
+                            }
+                            None => {
+                                if let Ok(page) = load_page(&id, None, None, PAGE_SIZE).await {
+                                    log.dispatch(ChatAction::Opened(id.clone(), page));
+                                }
+                            }
+                        }
+                        *at_bottom.borrow_mut() = focus.is_none();
+                        conversation_id.set(Some(id));
+                    });
+                }
+                || {}
+            },
+            (username, props.focus),
+        );
+    }
+
+    let on_scroll = {
+        let list = list.clone();
+        let viewport = viewport.clone();
+        let conversation_id = conversation_id.clone();
+        let log = props.log.clone();
+        let loading = loading.clone();
+        let at_bottom = at_bottom.clone();
+        let pending_shift = pending_shift.clone();
+        let history = history.clone();
+        Callback::from(move |_: Event| {
+            let element = match list.cast::<Element>() {
+                Some(element) => element,
+                None => return,
+            };
+            let (top, height) = (element.scroll_top(), element.client_height());
+            viewport.set((top, height));
+            let near_bottom = top + height >= element.scroll_height() - LOAD_MARGIN;
+            *at_bottom.borrow_mut() =
+                !history.has_newer && top + height >= element.scroll_height() - ROW_HEIGHT / 2;
+
+            let older = top < LOAD_MARGIN && history.has_older;
+            let newer = near_bottom && history.has_newer;
+            let conversation_id = match (*conversation_id).clone() {
+                Some(id) if (older || newer) && !*loading.borrow() => id,
+                _ => return,
+            };
+            *loading.borrow_mut() = true;
+            let first_id = history.messages.first().map(|message| message.id);
+            let last_id = history.messages.last().map(|message| message.id);
+            let log = log.clone();
+            let loading = loading.clone();
+            let pending_shift = pending_shift.clone();
+            spawn_local(async move {
+                if older {
+                    if let Ok(page) = load_page(&conversation_id, first_id, None, PAGE_SIZE).await {
+                        // the messages added above push down what the user is reading
+                        *pending_shift.borrow_mut() += page.messages.len() as i32 * ROW_HEIGHT;
+                        log.dispatch(ChatAction::Older(conversation_id, page));
+                    }
+                } else if let Ok(page) = load_page(&conversation_id, None, last_id, PAGE_SIZE).await
+                {
+                    log.dispatch(ChatAction::Newer(conversation_id, page));
+                }
+                *loading.borrow_mut() = false;
+            });
+        })
+    };
+
+    // keeps the view on the same messages when older ones are added above, and at the bottom
+    // when new ones arrive while the user is there
+    {
+        let list = list.clone();
+        let viewport = viewport.clone();
+        let at_bottom = at_bottom.clone();
+        let pending_shift = pending_shift.clone();
+        use_effect_with_deps(
+            move |_| {
+                if let Some(element) = list.cast::<Element>() {
+                    let shift = std::mem::take(&mut *pending_shift.borrow_mut());
+                    if shift != 0 {
+                        element.set_scroll_top(element.scroll_top() + shift);
+                    } else if *at_bottom.borrow() {
+                        element.set_scroll_top(element.scroll_height());
+                    }
+                    let current = (element.scroll_top(), element.client_height());
+                    if current != *viewport {
+                        viewport.set(current);
+                    }
+                }
+                || {}
+            },
+            (messages.len(), outbox.len(), first, last),
+        );
+    }
+
+    // the focused message is brought into the rendered messages, then scrolled to
+    {
+        let focused = focused.clone();
+        let list = list.clone();
+        let viewport = viewport.clone();
+        let focus_index = props
+            .focus
+            .and_then(|focus| messages.iter().position(|message| message.id == focus));
+        use_effect_with_deps(
+            move |(focus, focus_index, _)| {
+                if focus.is_some() && *scrolled_to.borrow() != *focus {
+                    if let Some(element) = focused.cast::<Element>() {
+                        element.scroll_into_view();
+                        *scrolled_to.borrow_mut() = *focus;
+                    } else if let (Some(index), Some(element)) =
+                        (focus_index, list.cast::<Element>())
+                    {
+                        element.set_scroll_top(*index as i32 * ROW_HEIGHT);
+                        viewport.set((element.scroll_top(), element.client_height()));
+                    }
+                }
+                || {}
+            },
+            (props.focus, focus_index, first),
+        );
+    }
+
+    // what the contact sent is read as soon as it's on screen
+    {
+        let last_received = messages
+            .iter()
+            .filter(|message| message.sender != props.user.username)
+            .filter(|message| message.status != MessageStatus::Read)
+            .map(|message| message.id)
+            .max();
+        let conversation_id = (*conversation_id).clone();
+        use_effect_with_deps(
+            move |(conversation_id, last_received)| {
+                if let (Some(conversation_id), Some(up_to)) =
+                    (conversation_id.clone(), *last_received)
+                {
+                    spawn_local(async move {
+                        let args = MarkReadArgs {
+                            conversation_id,
+                            up_to,
+                        };
+                        let _ = bridge::call::<_, ()>("mark_read", &args).await;
+                    });
+                }
+                || {}
+            },
+            (conversation_id, last_received),
+        );
+    }
+
+    let send = {
+        let conversation_id = conversation_id.clone();
+        let error = error.clone();
+        let composer = composer.clone();
+        let log = props.log.clone();
+        Callback::from(move |_: ()| {
+            let (conversation_id, textarea) = match (
+                (*conversation_id).clone(),
+                composer.cast::<HtmlTextAreaElement>(),
+            ) {
+                (Some(id), Some(textarea)) => (id, textarea),
+                _ => return,
+            };
+            let body = textarea.value();
+            if body.trim().is_empty() {
+                return;
+            }
+            let error = error.clone();
+            let log = log.clone();
+            spawn_local(async move {
+                let args = SendArgs {
+                    conversation_id,
+                    body,
+                };
+                match bridge::call::<_, OutboxEntry>("send_chat_message", &args).await {
+                    Ok(entry) => {
+                        textarea.set_value("");
+                        error.set(None);
+                        log.dispatch(ChatAction::Queued(entry));
+                    }
+                    Err(e) => error.set(Some(e)),
+                }
+            });
+        })
+    };
+    let on_send_click = {
+        let send = send.clone();
+        Callback::from(move |e: MouseEvent| {
+            e.prevent_default();
+            send.emit(());
+        })
+    };
+    // the contact sees we're typing, the backend decides how often it's worth telling
+    let on_input = {
+        let conversation_id = conversation_id.clone();
+        Callback::from(move |_: InputEvent| {
+            if let Some(conversation_id) = (*conversation_id).clone() {
+                spawn_local(async move {
+                    let args = ConversationIdArgs { conversation_id };
+                    let _ = bridge::call::<_, ()>("send_typing", &args).await;
+                });
+            }
+        })
+    };
+    // applied once the server sends the timer back, to both sides of the conversation
+    let set_retention = {
+        let conversation_id = conversation_id.clone();
+        let error = error.clone();
+        move |retention_secs: Option<u64>| {
+            let conversation_id = conversation_id.clone();
+            let error = error.clone();
+            Callback::from(move |e: MouseEvent| {
+                e.prevent_default();
+                let error = error.clone();
+                let conversation_id = match (*conversation_id).clone() {
+                    Some(id) => id,
+                    None => return,
+                };
+                let args = RetentionArgs {
+                    conversation_id,
+                    retention_secs,
+                };
+                spawn_local(async move {
+                    if let Err(e) = bridge::call::<_, ()>("set_retention", &args).await {
+                        error.set(Some(e));
+                    }
+                });
+            })
+        }
+    };
+    // enter sends, shift+enter starts a new line
+    let on_keydown = Callback::from(move |e: KeyboardEvent| {
+        if e.key() == "Enter" && !e.shift_key() {
+            e.prevent_default();
+            send.emit(());
+        }
+    });
+
+    let contact = match &props.contact {
+        Some(contact) => contact,
+        None => {
+            return html! {
+                <main>
+                    <ul id="chat">
+                        <li class="empty">{"Pick a contact to start chatting"}</li>
+                    </ul>
+                </main>
+            }
+        }
+    };
+    let retry = {
+        let log = props.log.clone();
+        let error = error.clone();
+        move |client_id: String| {
+            let log = log.clone();
+            let error = error.clone();
+            Callback::from(move |e: MouseEvent| {
+                e.prevent_default();
+                let log = log.clone();
+                let error = error.clone();
+                let args = RetryArgs {
+                    client_id: client_id.clone(),
+                };
+                spawn_local(async move {
+                    match bridge::call::<_, OutboxEntry>("retry_chat_message", &args).await {
+                        Ok(entry) => log.dispatch(ChatAction::Queued(entry)),
+                        Err(e) => error.set(Some(e)),
+                    }
+                });
+            })
+        }
+    };
+    let avatar = contact
+        .avatar_url
+        .clone()
+        .unwrap_or_else(|| DEFAULT_AVATAR.to_string());
+    let typing = match &*conversation_id {
+        Some(id) => props.live.typing(id).is_some(),
+        None => false,
+    };
+    let subtitle = match &props.connection {
+        Some(status) => html! { {status} },
+        None if typing => html! { {"typing..."} },
+        None => presence::status_line(props.live.presence(&contact.username)),
+    };
+    let timer = conversation_id
+        .as_ref()
+        .and_then(|id| props.log.retention(id));
+
+    html! {
+        <main>
+            <header>
+                <img src={avatar} alt=""/>
+                <div>
+                    <h2>{format!("Chat with {}", contact.display_name)}</h2>
+                    <h3>{subtitle}</h3>
+                    <p class="retention" title="messages of both sides are deleted this long after they're sent">
+                        {"Disappearing messages: "}
+                        { for RETENTION_CHOICES.iter().enumerate().map(|(index, (secs, label))| html! {
+                            <>
+                                if index > 0 { {" · "} }
+                                <a href="#" class={classes!((timer == *secs).then_some("current"))} onclick={set_retention(*secs)}>{*label}</a>
+                            </>
+                        }) }
+                    </p>
+                </div>
+                <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/ico_star.png" alt=""/>
+            </header>
+            <ul id="chat" ref={list} onscroll={on_scroll}>
+                if history.has_older || first > 0 {
+                    <li class="spacer" style={format!("height:{}px", first as i32 * ROW_HEIGHT)}></li>
+                }
+                { for messages[first..last].iter().map(|message| {
+                    let mine = message.sender == props.user.username;
+                    let name = if mine { &props.user.display_name } else { &contact.display_name };
+                    let is_focused = props.focus == Some(message.id);
+                    html! {
+                        <li class={classes!(if mine { "me" } else { "you" }, is_focused.then_some("focused"))}
+                            ref={if is_focused { focused.clone() } else { NodeRef::default() }}>
+                            <div class="entete">
+                                if mine {
+                                    { ticks(message.status) }
+                                    <h3>{format_time(message.sent_at)}</h3>
+                                    <h2>{name}</h2>
+                                } else {
+                                    <h2>{name}</h2>
+                                    <h3>{format_time(message.sent_at)}</h3>
+                                }
+                            </div>
+                            <div class="triangle"></div>
+                            <div class="message">{&message.body}</div>
+                        </li>
+                    }
+                }) }
+                if last < messages.len() {
+                    <li class="spacer" style={format!("height:{}px", (messages.len() - last) as i32 * ROW_HEIGHT)}></li>
+                }
+                if history.has_newer {
+                    <li class="empty">{"Loading newer messages..."}</li>
+                }
+                { for outbox.iter().filter(|_| !history.has_newer).map(|entry| {
+                    let (class, state) = match entry.state {
+                        OutboxState::Pending => ("me pending", "sending...".to_string()),
+                        OutboxState::Sent => ("me pending", "sent".to_string()),
+                        OutboxState::Failed => (
+                            "me failed",
+                            entry.last_error.clone().unwrap_or_else(|| "not sent".to_string()),
+                        ),
+                    };
+                    html! {
+                        <li class={class} title={state.clone()}>
+                            <div class="entete">
+                                if entry.state == OutboxState::Failed {
+                                    <a href="#" onclick={retry(entry.client_id.clone())}>{"Retry"}</a>
+                                }
+                                if entry.state == OutboxState::Sent {
+                                    <span class="ticks faint" title="sent, waiting for the server">{"✓"}</span>
+                                }
+                                <h3>{format!("{} · {}", format_time(entry.created_at), state)}</h3>
+                                <h2>{&props.user.display_name}</h2>
+                            </div>
+                            <div class="triangle"></div>
+                            <div class="message">{&entry.body}</div>
+                        </li>
+                    }
+                }) }
+            </ul>
+            <footer>
+                if let Some(error) = &*error {
+                    <p class="error">{error}</p>
+                }
+                <textarea ref={composer} placeholder="Type your message" oninput={on_input} onkeydown={on_keydown}></textarea>
+                <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/ico_picture.png" alt=""/>
+                <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/ico_file.png" alt=""/>
+                <a href="#" onclick={on_send_click}>{"Send"}</a>
+            </footer>
+        </main>
+    }
+}
+
+async fn load_page(
+    conversation_id: &str,
+    before: Option<u64>,
+    after: Option<u64>,
+    limit: u32,
+) -> Result<MessagePage, String> {
+    let args = PageArgs {
+        conversation_id: conversation_id.to_string(),
+        before,
+        after,
+        limit,
+    };
+    bridge::call::<_, MessagePage>("list_messages", &args).await
+}
+
+// a faint tick while sending, one once on the server, two on the recipient's device, blue once read
+fn ticks(status: MessageStatus) -> Html {
+    let (class, title, marks) = match status {
+        MessageStatus::Stored => ("ticks", "delivered to the server", "✓"),
+        MessageStatus::Delivered => ("ticks", "delivered", "✓✓"),
+        MessageStatus::Read => ("ticks read", "read", "✓✓"),
+    };
+    html! { <span class={class} title={title}>{marks}</span> }
+}
+
+// "10:12 AM, 3/14/2024" in the local time zone
+pub fn format_time(unix_secs: u64) -> String {
+    let date = js_sys::Date::new(&JsValue::from_f64(unix_secs as f64 * 1000.0));
+    format!(
+        "{}, {}",
+        String::from(date.to_locale_time_string("default")),
+        String::from(date.to_locale_date_string("default", &JsValue::UNDEFINED))
+    )
+}
@@ -0,0 +1,67 @@
+use crate::bridge;
+use serde::Serialize;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+
+#[derive(Serialize)]
+struct UnlockArgs {
+    password: String,
+}
+
+#[derive(Properties, PartialEq)]
+pub struct LockScreenProps {
+    pub reason: Option<String>,
+    pub on_unlocked: Callback<()>,
+}
+
+#[function_component(LockScreen)]
+pub fn lock_screen(props: &LockScreenProps) -> Html {
+    let password_ref = use_node_ref();
+    let error = use_state(|| None::<String>);
+
+    let unlock = {
+        let password_ref = password_ref.clone();
+        let error = error.clone();
+        let on_unlocked = props.on_unlocked.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            let input = password_ref.cast::<web_sys::HtmlInputElement>().unwrap();
+            let args = UnlockArgs {
+                password: input.value(),
+            };
+            input.set_value("");
+            let error = error.clone();
+            let on_unlocked = on_unlocked.clone();
+            spawn_local(async move {
+                match bridge::call::<_, ()>("unlock_wallet", &args).await {
+                    Ok(()) => {
+                        error.set(None);
+                        on_unlocked.emit(());
+                    }
+                    Err(e) => error.set(Some(e)),
+                }
+            });
+        })
+    };
+
+    let reason = match props.reason.as_deref() {
+        Some("idle") => "locked after a period of inactivity",
+        Some("minimized") => "locked because the window was minimized",
+        Some("sleep") => "locked because the computer went to sleep",
+        _ => "your wallet is locked",
+    };
+
+    html! {
+        <div class="overlay-screen">
+            <form onsubmit={unlock}>
+                <h2>{"Wallet locked"}</h2>
+                <h3>{reason}</h3>
+                <input type="password" ref={password_ref} placeholder="password"/>
+                <button type="submit">{"Unlock"}</button>
+                if let Some(error) = &*error {
+                    <p class="error">{error}</p>
+                }
+            </form>
+        </div>
+    }
+}
@@ -1,6 +1,15 @@
 mod app;
+mod auth;
+mod backup;
+mod bridge;
+mod chat;
+mod lock_screen;
+mod presence;
+mod profile_switcher;
+mod shares;
+mod sidebar;
+mod types;
 use app::App;
-use modules::solana_wallet;
 
 fn main() {
     yew::Renderer::<App>::new().render();
@@ -0,0 +1,92 @@
+use crate::chat::format_time;
+use crate::types::{PresenceStatus, PresenceUpdate, Typing};
+use std::collections::HashMap;
+use std::rc::Rc;
+use yew::prelude::*;
+
+// a typing notification is shown this long unless another one follows,
+// the sender repeats them every few seconds while typing
+pub const TYPING_TIMEOUT_MS: u32 = 5000;
+
+// presence of the followed users and who is typing where
+#[derive(Default, PartialEq)]
+pub struct LiveState {
+    presence: HashMap<String, PresenceUpdate>,
+    // conversation id -> (username, stamp of the notification)
+    typing: HashMap<String, (String, u32)>,
+}
+
+pub enum LiveAction {
+    Presence(PresenceUpdate),
+    Typing(Typing, u32),
+    // the notification `stamp` timed out, a newer one keeps the indicator
+    TypingExpired(String, u32),
+    // a message of the user arrived in the conversation
+    StoppedTyping(String, String),
+}
+
+impl LiveState {
+    pub fn presence(&self, username: &str) -> Option<&PresenceUpdate> {
+        self.presence.get(username)
+    }
+
+    pub fn typing(&self, conversation_id: &str) -> Option<&str> {
+        self.typing
+            .get(conversation_id)
+            .map(|(username, _)| username.as_str())
+    }
+}
+
+impl Reducible for LiveState {
+    type Action = LiveAction;
+
+    fn reduce(self: Rc<Self>, action: LiveAction) -> Rc<Self> {
+        let mut presence = self.presence.clone();
+        let mut typing = self.typing.clone();
+        match action {
+            LiveAction::Presence(update) => {
+                if update.status != PresenceStatus::Online {
+                    typing.retain(|_, (username, _)| *username != update.username);
+                }
+                presence.insert(update.username.clone(), update);
+            }
+            LiveAction::Typing(event, stamp) => {
+                typing.insert(event.conversation_id, (event.username, stamp));
+            }
+            LiveAction::TypingExpired(conversation_id, stamp) => {
+                if typing.get(&conversation_id).map(|(_, known)| *known) == Some(stamp) {
+                    typing.remove(&conversation_id);
+                }
+            }
+            LiveAction::StoppedTyping(conversation_id, sender) => {
+                if typing.get(&conversation_id).map(|(username, _)| username) == Some(&sender) {
+                    typing.remove(&conversation_id);
+                }
+            }
+        }
+        Rc::new(LiveState { presence, typing })
+    }
+}
+
+// the status dot and text of a user, "offline" when nothing was heard of them yet
+pub fn status_line(update: Option<&PresenceUpdate>) -> Html {
+    let (class, text) = describe(update);
+    html! {
+        <>
+            <span class={classes!("status", class)}></span>
+            {text}
+        </>
+    }
+}
+
+pub fn describe(update: Option<&PresenceUpdate>) -> (&'static str, String) {
+    match update {
+        Some(update) if update.status == PresenceStatus::Online => ("green", "online".to_string()),
+        Some(update) if update.status == PresenceStatus::Away => ("yellow", "away".to_string()),
+        Some(PresenceUpdate {
+            last_seen: Some(last_seen),
+            ..
+        }) => ("orange", format!("last seen {}", format_time(*last_seen))),
+        _ => ("orange", "offline".to_string()),
+    }
+}
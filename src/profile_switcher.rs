@@ -0,0 +1,99 @@
+use crate::bridge;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+
+#[derive(Serialize)]
+struct OpenProfileArgs {
+    name: String,
+}
+
+#[derive(Deserialize, Clone, PartialEq)]
+struct ProfileInfo {
+    name: String,
+    has_wallet: bool,
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ProfileSwitcherProps {
+    pub on_opened: Callback<String>,
+}
+
+// shown at startup unless the app was launched with --profile, every profile has its own
+// wallet, session and history
+#[function_component(ProfileSwitcher)]
+pub fn profile_switcher(props: &ProfileSwitcherProps) -> Html {
+    let profiles = use_state(Vec::<ProfileInfo>::new);
+    let name_ref = use_node_ref();
+    let error = use_state(|| None::<String>);
+    {
+        let profiles = profiles.clone();
+        let error = error.clone();
+        use_effect_with_deps(
+            move |_| {
+                spawn_local(async move {
+                    match bridge::call::<_, Vec<ProfileInfo>>("list_profiles", &()).await {
+                        Ok(list) => profiles.set(list),
+                        Err(e) => error.set(Some(e)),
+                    }
+                });
+                || {}
+            },
+            (),
+        );
+    }
+
+    let open = {
+        let error = error.clone();
+        let on_opened = props.on_opened.clone();
+        Callback::from(move |name: String| {
+            let error = error.clone();
+            let on_opened = on_opened.clone();
+            spawn_local(async move {
+                let args = OpenProfileArgs { name: name.clone() };
+                match bridge::call::<_, ()>("open_profile", &args).await {
+                    Ok(()) => on_opened.emit(name),
+                    Err(e) => error.set(Some(e)),
+                }
+            });
+        })
+    };
+    let create = {
+        let name_ref = name_ref.clone();
+        let open = open.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            let input = name_ref.cast::<web_sys::HtmlInputElement>().unwrap();
+            let name = input.value().trim().to_string();
+            if !name.is_empty() {
+                open.emit(name);
+            }
+        })
+    };
+
+    html! {
+        <div class="overlay-screen">
+            <form onsubmit={create}>
+                <h2>{"Choose a profile"}</h2>
+                <h3>{"each profile has its own wallet and chats"}</h3>
+                { for profiles.iter().map(|profile| {
+                    let name = profile.name.clone();
+                    let onclick = open.reform(move |_: MouseEvent| name.clone());
+                    html! {
+                        <button type="button" class="secondary" {onclick}>
+                            { &profile.name }
+                            if !profile.has_wallet {
+                                <span class="faint">{" · no wallet yet"}</span>
+                            }
+                        </button>
+                    }
+                }) }
+                <input type="text" ref={name_ref} placeholder="new profile name"/>
+                <button type="submit">{"Create profile"}</button>
+                if let Some(error) = &*error {
+                    <p class="error">{error}</p>
+                }
+            </form>
+        </div>
+    }
+}
@@ -0,0 +1,184 @@
+use crate::bridge;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{HtmlInputElement, HtmlTextAreaElement};
+use yew::prelude::*;
+
+// what the split form starts with, any 2 of 3 shares rebuild the account
+const DEFAULT_THRESHOLD: u8 = 2;
+const DEFAULT_COUNT: u8 = 3;
+
+#[derive(Serialize)]
+struct SplitArgs {
+    words: Vec<String>,
+    threshold: u8,
+    count: u8,
+}
+
+#[derive(Serialize)]
+struct RecoverArgs {
+    shares: Vec<String>,
+    password: String,
+}
+
+#[derive(Deserialize)]
+struct AccountInfo {
+    pub_key: String,
+}
+
+fn textarea_value(node: &NodeRef) -> String {
+    node.cast::<HtmlTextAreaElement>()
+        .map(|textarea| textarea.value())
+        .unwrap_or_default()
+}
+
+fn input_value(node: &NodeRef) -> String {
+    node.cast::<HtmlInputElement>()
+        .map(|input| input.value())
+        .unwrap_or_default()
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ShareScreenProps {
+    pub on_close: Callback<()>,
+}
+
+// splits the recovery phrase into shares handed to trusted people, none of them alone can rebuild it
+#[function_component(ShareScreen)]
+pub fn share_screen(props: &ShareScreenProps) -> Html {
+    let shares = use_state(|| None::<Vec<String>>);
+    let error = use_state(|| None::<String>);
+    let phrase_ref = use_node_ref();
+    let threshold_ref = use_node_ref();
+    let count_ref = use_node_ref();
+
+    let submit = {
+        let shares = shares.clone();
+        let error = error.clone();
+        let refs = (phrase_ref.clone(), threshold_ref.clone(), count_ref.clone());
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            let number = |node: &NodeRef| input_value(node).trim().parse::<u8>();
+            let (threshold, count) = match (number(&refs.1), number(&refs.2)) {
+                (Ok(threshold), Ok(count)) => (threshold, count),
+                _ => {
+                    error.set(Some(
+                        "the number of shares must be between 1 and 255".to_string(),
+                    ));
+                    return;
+                }
+            };
+            let args = SplitArgs {
+                words: textarea_value(&refs.0)
+                    .split_whitespace()
+                    .map(str::to_lowercase)
+                    .collect(),
+                threshold,
+                count,
+            };
+            let shares = shares.clone();
+            let error = error.clone();
+            spawn_local(async move {
+                match bridge::call::<_, Vec<String>>("split_recovery_phrase", &args).await {
+                    Ok(split) => {
+                        error.set(None);
+                        shares.set(Some(split));
+                    }
+                    Err(e) => error.set(Some(e)),
+                }
+            });
+        })
+    };
+    let close = {
+        let on_close = props.on_close.clone();
+        Callback::from(move |_: MouseEvent| on_close.emit(()))
+    };
+
+    html! {
+        <div class="overlay-screen">
+            <form onsubmit={submit}>
+                <h2>{"Recovery shares"}</h2>
+                if let Some(shares) = &*shares {
+                    <h3>{"give each share to a different person, write them down and don't keep them together"}</h3>
+                    <ol class="shares">
+                        { for shares.iter().map(|share| html! { <li>{share}</li> }) }
+                    </ol>
+                    <button type="button" onclick={close.clone()}>{"Done"}</button>
+                } else {
+                    <h3>{"type the 12 words of your recovery phrase, separated by spaces"}</h3>
+                    <textarea ref={phrase_ref} placeholder="recovery phrase"></textarea>
+                    <label>
+                        {"shares needed "}
+                        <input type="number" min="2" max="255" ref={threshold_ref} value={DEFAULT_THRESHOLD.to_string()}/>
+                        {" out of "}
+                        <input type="number" min="2" max="255" ref={count_ref} value={DEFAULT_COUNT.to_string()}/>
+                    </label>
+                    <button type="submit">{"Split"}</button>
+                    <a class="switch" onclick={close}>{"Cancel"}</a>
+                }
+                if let Some(error) = &*error {
+                    <p class="error">{error}</p>
+                }
+            </form>
+        </div>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct RecoverScreenProps {
+    pub on_recovered: Callback<String>,
+    pub on_cancel: Callback<()>,
+}
+
+// rebuilds the wallet on a device without one from enough shares, one per line
+#[function_component(RecoverScreen)]
+pub fn recover_screen(props: &RecoverScreenProps) -> Html {
+    let error = use_state(|| None::<String>);
+    let shares_ref = use_node_ref();
+    let password_ref = use_node_ref();
+
+    let submit = {
+        let error = error.clone();
+        let shares_ref = shares_ref.clone();
+        let password_ref = password_ref.clone();
+        let on_recovered = props.on_recovered.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            let args = RecoverArgs {
+                shares: textarea_value(&shares_ref)
+                    .lines()
+                    .map(str::to_string)
+                    .collect(),
+                password: input_value(&password_ref),
+            };
+            let error = error.clone();
+            let on_recovered = on_recovered.clone();
+            spawn_local(async move {
+                match bridge::call::<_, AccountInfo>("recover_from_shares", &args).await {
+                    Ok(account) => on_recovered.emit(account.pub_key),
+                    Err(e) => error.set(Some(e)),
+                }
+            });
+        })
+    };
+    let cancel = {
+        let on_cancel = props.on_cancel.clone();
+        Callback::from(move |_: MouseEvent| on_cancel.emit(()))
+    };
+
+    html! {
+        <div class="overlay-screen">
+            <form onsubmit={submit}>
+                <h2>{"Recover your wallet"}</h2>
+                <h3>{"paste the shares you collected, one per line"}</h3>
+                <textarea ref={shares_ref} placeholder="chatuza-share ..."></textarea>
+                <input type="password" ref={password_ref} placeholder="new password"/>
+                <button type="submit">{"Recover"}</button>
+                <a class="switch" onclick={cancel}>{"Cancel"}</a>
+                if let Some(error) = &*error {
+                    <p class="error">{error}</p>
+                }
+            </form>
+        </div>
+    }
+}
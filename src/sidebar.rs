@@ -0,0 +1,692 @@
+use crate::backup::BackupScreen;
+use crate::bridge;
+use crate::presence::{self, LiveState};
+use crate::shares::ShareScreen;
+use crate::types::{
+    AddressBookEntry, MessageSearchResult, Page, SnippetPart, UserProfile, DEFAULT_AVATAR,
+};
+use gloo_timers::callback::Timeout;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+// the search waits for the user to stop typing this long
+const SEARCH_DEBOUNCE_MS: u32 = 300;
+// the sizes the history kept on this device can be capped at, the oldest messages go first
+const STORAGE_CAP_CHOICES: [(Option<u64>, &str); 4] = [
+    (None, "none"),
+    (Some(100 * 1024 * 1024), "100 MB"),
+    (Some(500 * 1024 * 1024), "500 MB"),
+    (Some(1024 * 1024 * 1024), "1 GB"),
+];
+
+#[derive(Serialize)]
+struct SearchArgs {
+    query: String,
+    page: u32,
+}
+
+#[derive(Serialize)]
+struct MessageSearchArgs {
+    query: String,
+    // the username of the conversation to search in, all of them when None
+    contact: Option<String>,
+}
+
+#[derive(Serialize)]
+struct WatchArgs {
+    usernames: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct AddContactArgs {
+    username: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+struct PrivacySettings {
+    send_read_receipts: bool,
+}
+
+#[derive(Serialize)]
+struct PrivacyArgs {
+    settings: PrivacySettings,
+}
+
+#[derive(Serialize)]
+struct StorageCapArgs {
+    cap: Option<u64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportArgs {
+    // the conversation with this user, all of them when None
+    contact: Option<String>,
+    format: &'static str,
+    // a new file next to the app files when None
+    path: Option<String>,
+    with_attachments: bool,
+}
+
+#[derive(Deserialize)]
+struct ExportReport {
+    path: String,
+    attachments_path: Option<String>,
+    messages: usize,
+    missing_attachments: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportArgs {
+    path: String,
+    dry_run: bool,
+}
+
+#[derive(Deserialize)]
+struct ImportReport {
+    messages_added: usize,
+    messages_already_here: usize,
+    messages_conflicting: Vec<u64>,
+    attachments_added: usize,
+    attachments_missing: Vec<String>,
+    contacts_updated: Vec<String>,
+    contacts_missing: Vec<String>,
+}
+
+#[derive(Properties, PartialEq)]
+pub struct SidebarProps {
+    pub user: UserProfile,
+    pub live: UseReducerHandle<LiveState>,
+    // username of the open conversation
+    pub selected: Option<String>,
+    pub on_select: Callback<UserProfile>,
+    // opens the conversation with the contact at the message
+    pub on_jump: Callback<(UserProfile, u64)>,
+    pub on_signed_out: Callback<()>,
+}
+
+#[function_component(Sidebar)]
+pub fn sidebar(props: &SidebarProps) -> Html {
+    let contacts = use_state(Vec::<UserProfile>::new);
+    // nicknames, avatars and addresses the user keeps for the contacts
+    let address_book = use_state(Vec::<AddressBookEntry>::new);
+    let query = use_state(String::new);
+    // None while no search is running, the pages loaded so far otherwise
+    let results = use_state(|| None::<(Vec<UserProfile>, Page<UserProfile>)>);
+    // the messages of the local history matching the same query
+    let message_results = use_state(Vec::<MessageSearchResult>::new);
+    // the message search is narrowed to the open conversation
+    let in_conversation = use_state(|| false);
+    let debounce = use_mut_ref(|| None::<Timeout>);
+    let read_receipts = use_state(|| true);
+    // why large transfers are blocked, None once the recovery phrase is confirmed
+    let backup_reminder = use_state(|| None::<String>);
+    let backing_up = use_state(|| false);
+    let sharing = use_state(|| false);
+    // bytes of history kept at most, None without a cap
+    let storage_cap = use_state(|| None::<u64>);
+    // what the last export or import did, or why it failed
+    let history_status = use_state(|| None::<String>);
+
+    // the presence of every contact is followed
+    {
+        let usernames: Vec<String> = contacts
+            .iter()
+            .map(|contact| contact.username.clone())
+            .collect();
+        use_effect_with_deps(
+            move |usernames| {
+                let args = WatchArgs {
+                    usernames: usernames.clone(),
+                };
+                if !args.usernames.is_empty() {
+                    spawn_local(async move {
+                        let _ = bridge::call::<_, ()>("watch_presence", &args).await;
+                    });
+                }
+                || {}
+            },
+            usernames,
+        );
+    }
+    {
+        let contacts = contacts.clone();
+        let address_book = address_book.clone();
+        let read_receipts = read_receipts.clone();
+        let storage_cap = storage_cap.clone();
+        let backup_reminder = backup_reminder.clone();
+        use_effect_with_deps(
+            move |_| {
+                spawn_local(async move {
+                    if let Ok(reminder) =
+                        bridge::call::<_, Option<String>>("backup_reminder", &()).await
+                    {
+                        backup_reminder.set(reminder);
+                    }
+                    if let Ok(settings) =
+                        bridge::call::<_, PrivacySettings>("get_privacy_settings", &()).await
+                    {
+                        read_receipts.set(settings.send_read_receipts);
+                    }
+                    if let Ok(cap) = bridge::call::<_, Option<u64>>("get_storage_cap", &()).await {
+                        storage_cap.set(cap);
+                    }
+                    if let Ok(list) =
+                        bridge::call::<_, Vec<UserProfile>>("list_contacts", &()).await
+                    {
+                        contacts.set(list);
+                    }
+                    // read after the list, which saves the profiles and their address changes
+                    if let Ok(book) =
+                        bridge::call::<_, Vec<AddressBookEntry>>("list_address_book", &()).await
+                    {
+                        address_book.set(book);
+                    }
+                });
+                || {}
+            },
+            (),
+        );
+    }
+
+    let search = {
+        let results = results.clone();
+        move |query: String, page: u32, loaded: Vec<UserProfile>| {
+            let results = results.clone();
+            spawn_local(async move {
+                let args = SearchArgs { query, page };
+                if let Ok(found) = bridge::call::<_, Page<UserProfile>>("search_users", &args).await
+                {
+                    let mut loaded = loaded;
+                    loaded.extend(found.items.iter().cloned());
+                    results.set(Some((loaded, found)));
+                }
+            });
+        }
+    };
+
+    let search_messages = {
+        let message_results = message_results.clone();
+        move |query: String, contact: Option<String>| {
+            let message_results = message_results.clone();
+            spawn_local(async move {
+                let args = MessageSearchArgs { query, contact };
+                if let Ok(found) =
+                    bridge::call::<_, Vec<MessageSearchResult>>("search_messages", &args).await
+                {
+                    message_results.set(found);
+                }
+            });
+        }
+    };
+    let search_contact = (*in_conversation).then(|| props.selected.clone()).flatten();
+
+    let on_search_input = {
+        let query = query.clone();
+        let results = results.clone();
+        let message_results = message_results.clone();
+        let debounce = debounce.clone();
+        let search = search.clone();
+        let search_messages = search_messages.clone();
+        let search_contact = search_contact.clone();
+        Callback::from(move |e: InputEvent| {
+            let value = e.target_unchecked_into::<HtmlInputElement>().value();
+            query.set(value.clone());
+            if value.trim().is_empty() {
+                debounce.borrow_mut().take();
+                results.set(None);
+                message_results.set(vec![]);
+                return;
+            }
+            let search = search.clone();
+            let search_messages = search_messages.clone();
+            let search_contact = search_contact.clone();
+            // replacing the pending timeout cancels it
+            *debounce.borrow_mut() = Some(Timeout::new(SEARCH_DEBOUNCE_MS, move || {
+                search_messages(value.clone(), search_contact);
+                search(value, 0, vec![])
+            }));
+        })
+    };
+
+    let toggle_in_conversation = {
+        let query = query.clone();
+        let in_conversation = in_conversation.clone();
+        let selected = props.selected.clone();
+        Callback::from(move |_: MouseEvent| {
+            let narrowed = !*in_conversation;
+            in_conversation.set(narrowed);
+            search_messages(
+                (*query).clone(),
+                narrowed.then(|| selected.clone()).flatten(),
+            );
+        })
+    };
+
+    // the contact may not be in the list anymore, its conversation still opens
+    let jump = {
+        let contacts = contacts.clone();
+        let on_jump = props.on_jump.clone();
+        move |username: String, message_id: u64| {
+            let contacts = contacts.clone();
+            let on_jump = on_jump.clone();
+            Callback::from(move |_: MouseEvent| {
+                let contact = contacts
+                    .iter()
+                    .find(|contact| contact.username == username)
+                    .cloned()
+                    .unwrap_or_else(|| UserProfile {
+                        username: username.clone(),
+                        display_name: username.clone(),
+                        wallet_pubkey: String::new(),
+                        avatar_url: None,
+                    });
+                on_jump.emit((contact, message_id));
+            })
+        }
+    };
+
+    let load_more = {
+        let query = query.clone();
+        let results = results.clone();
+        let search = search.clone();
+        Callback::from(move |_: MouseEvent| {
+            if let Some((loaded, last)) = &*results {
+                search((*query).clone(), last.page + 1, loaded.clone());
+            }
+        })
+    };
+
+    let add_contact = {
+        let contacts = contacts.clone();
+        move |username: String| {
+            let contacts = contacts.clone();
+            Callback::from(move |_: MouseEvent| {
+                let contacts = contacts.clone();
+                let args = AddContactArgs {
+                    username: username.clone(),
+                };
+                spawn_local(async move {
+                    if let Ok(added) = bridge::call::<_, UserProfile>("add_contact", &args).await {
+                        let mut list = (*contacts).clone();
+                        list.retain(|contact| contact.username != added.username);
+                        list.push(added);
+                        contacts.set(list);
+                    }
+                });
+            })
+        }
+    };
+
+    let sign_out = {
+        let on_signed_out = props.on_signed_out.clone();
+        Callback::from(move |_: MouseEvent| {
+            let on_signed_out = on_signed_out.clone();
+            spawn_local(async move {
+                // signed out locally even when the server can't be reached
+                let _ = bridge::call::<_, ()>("sign_out", &()).await;
+                on_signed_out.emit(());
+            });
+        })
+    };
+    let toggle_read_receipts = {
+        let read_receipts = read_receipts.clone();
+        Callback::from(move |_: MouseEvent| {
+            let read_receipts = read_receipts.clone();
+            let args = PrivacyArgs {
+                settings: PrivacySettings {
+                    send_read_receipts: !*read_receipts,
+                },
+            };
+            spawn_local(async move {
+                if bridge::call::<_, ()>("set_privacy_settings", &args)
+                    .await
+                    .is_ok()
+                {
+                    read_receipts.set(args.settings.send_read_receipts);
+                }
+            });
+        })
+    };
+    let open_backup = {
+        let backing_up = backing_up.clone();
+        Callback::from(move |_: MouseEvent| backing_up.set(true))
+    };
+    let on_backup_confirmed = {
+        let backing_up = backing_up.clone();
+        let backup_reminder = backup_reminder.clone();
+        Callback::from(move |_| {
+            backup_reminder.set(None);
+            backing_up.set(false);
+        })
+    };
+    let on_backup_cancel = {
+        let backing_up = backing_up.clone();
+        Callback::from(move |_| backing_up.set(false))
+    };
+    let open_sharing = {
+        let sharing = sharing.clone();
+        Callback::from(move |_: MouseEvent| sharing.set(true))
+    };
+    let on_sharing_closed = {
+        let sharing = sharing.clone();
+        Callback::from(move |_| sharing.set(false))
+    };
+    let set_storage_cap = {
+        let storage_cap = storage_cap.clone();
+        let history_status = history_status.clone();
+        move |cap: Option<u64>| {
+            let storage_cap = storage_cap.clone();
+            let history_status = history_status.clone();
+            Callback::from(move |_: MouseEvent| {
+                let storage_cap = storage_cap.clone();
+                let history_status = history_status.clone();
+                spawn_local(async move {
+                    match bridge::call::<_, ()>("set_storage_cap", &StorageCapArgs { cap }).await {
+                        Ok(_) => storage_cap.set(cap),
+                        Err(e) => history_status.set(Some(e)),
+                    }
+                });
+            })
+        }
+    };
+    // the open conversation, or all of them
+    let export = {
+        let history_status = history_status.clone();
+        let contact = props.selected.clone();
+        move |format: &'static str| {
+            let history_status = history_status.clone();
+            let contact = contact.clone();
+            Callback::from(move |_: MouseEvent| {
+                let history_status = history_status.clone();
+                let args = ExportArgs {
+                    contact: contact.clone(),
+                    format,
+                    path: None,
+                    with_attachments: true,
+                };
+                spawn_local(async move {
+                    let status =
+                        match bridge::call::<_, ExportReport>("export_history", &args).await {
+                            Ok(report) => export_summary(&report),
+                            Err(e) => e,
+                        };
+                    history_status.set(Some(status));
+                });
+            })
+        }
+    };
+    // asks for the export to import, shows what it would change and imports it once confirmed
+    let import = {
+        let history_status = history_status.clone();
+        Callback::from(move |_: MouseEvent| {
+            let window = match web_sys::window() {
+                Some(window) => window,
+                None => return,
+            };
+            let path = match window.prompt_with_message("Path of the JSON export to import") {
+                Ok(Some(path)) if !path.trim().is_empty() => path,
+                _ => return,
+            };
+            let history_status = history_status.clone();
+            spawn_local(async move {
+                let args = ImportArgs {
+                    path,
+                    dry_run: true,
+                };
+                let report = match bridge::call::<_, ImportReport>("import_history", &args).await {
+                    Ok(report) => report,
+                    Err(e) => return history_status.set(Some(e)),
+                };
+                let question = format!(
+                    "The import would bring {}. Import it?",
+                    import_summary(&report)
+                );
+                if !window.confirm_with_message(&question).unwrap_or(false) {
+                    return;
+                }
+                let args = ImportArgs {
+                    dry_run: false,
+                    ..args
+                };
+                let status = match bridge::call::<_, ImportReport>("import_history", &args).await {
+                    Ok(report) => format!("Imported {}", import_summary(&report)),
+                    Err(e) => e,
+                };
+                history_status.set(Some(status));
+            });
+        })
+    };
+    let avatar = props
+        .user
+        .avatar_url
+        .clone()
+        .unwrap_or_else(|| DEFAULT_AVATAR.to_string());
+
+    let list = match &*results {
+        Some((found, last)) => html! {
+            <>
+                <li class="section">
+                    {"Messages"}
+                    if props.selected.is_some() {
+                        <a onclick={toggle_in_conversation}>
+                            { if search_contact.is_some() { "in this chat" } else { "in all chats" } }
+                        </a>
+                    }
+                </li>
+                { for message_results.iter().map(|result| {
+                    let name = result.contact.as_ref().map(|username| {
+                        contacts
+                            .iter()
+                            .find(|contact| &contact.username == username)
+                            .map(|contact| contact.display_name.clone())
+                            .unwrap_or_else(|| username.clone())
+                    });
+                    html! {
+                        <li class="hit" onclick={result.contact.clone().map(|username| jump(username, result.message.id))}>
+                            <h2>{name.unwrap_or_default()}</h2>
+                            <p>{ snippet(&result.snippet) }</p>
+                        </li>
+                    }
+                }) }
+                if message_results.is_empty() {
+                    <li class="empty">{"No messages found"}</li>
+                }
+                <li class="section">{"People"}</li>
+                { for found.iter().map(|profile| {
+                    let is_contact = contacts.iter().any(|contact| contact.username == profile.username);
+                    html! {
+                        <li>
+                            { user_entry(profile, html! { {format!("@{}", profile.username)} }) }
+                            if !is_contact && profile.username != props.user.username {
+                                <a class="add" onclick={add_contact(profile.username.clone())}>{"Add"}</a>
+                            }
+                        </li>
+                    }
+                }) }
+                if found.is_empty() {
+                    <li class="empty">{"Nobody found"}</li>
+                }
+                if last.has_more() {
+                    <li class="more" onclick={load_more}>{"Load more"}</li>
+                }
+            </>
+        },
+        None => html! {
+            <>
+                { for contacts.iter().map(|contact| {
+                    let on_select = props.on_select.clone();
+                    let picked = contact.clone();
+                    let active = props.selected.as_ref() == Some(&contact.username);
+                    let entry = address_book.iter().find(|entry| entry.profile.username == contact.username);
+                    let status = presence::status_line(props.live.presence(&contact.username));
+                    let subtitle = match entry.and_then(|entry| entry.previous_wallet_pubkey.as_ref()) {
+                        Some(previous) => html! {
+                            <span class="warning" title={format!("was {}, now {}, check it with them before paying", previous, contact.wallet_pubkey)}>
+                                {"wallet address changed"}
+                            </span>
+                        },
+                        None => status,
+                    };
+                    html! {
+                        <li class={classes!(active.then_some("active"))} onclick={move |_| on_select.emit(picked.clone())}>
+                            { user_entry(&known_as(contact, entry), subtitle) }
+                        </li>
+                    }
+                }) }
+                if contacts.is_empty() {
+                    <li class="empty">{"No contacts yet, search people to add them"}</li>
+                }
+            </>
+        },
+    };
+
+    html! {
+        <aside>
+            <header>
+                <img src="https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/ico_search.png"/>
+                <input type="text" placeholder="search" value={(*query).clone()} oninput={on_search_input}/>
+            </header>
+            <div class="profile">
+                <img src={avatar} alt=""/>
+                <div>
+                    <h2>{&props.user.display_name}</h2>
+                    <h3>{format!("@{}", props.user.username)}</h3>
+                </div>
+                <a onclick={sign_out}>{"Sign out"}</a>
+                <a class="setting" onclick={toggle_read_receipts} title="let senders know when you read their messages">
+                    { if *read_receipts { "Read receipts: on" } else { "Read receipts: off" } }
+                </a>
+                if let Some(reminder) = &*backup_reminder {
+                    <a class="setting warning" onclick={open_backup} title={reminder.clone()}>
+                        {"Confirm your recovery phrase"}
+                    </a>
+                }
+                <a class="setting" onclick={open_sharing} title="split the recovery phrase between people you trust">
+                    {"Recovery shares"}
+                </a>
+                <a class="setting" title="the oldest messages and attachments are deleted past this size">
+                    {"History cap: "}
+                    { for STORAGE_CAP_CHOICES.iter().enumerate().map(|(index, (cap, label))| html! {
+                        <>
+                            if index > 0 { {" · "} }
+                            <span class={classes!((*storage_cap == *cap).then_some("current"))} onclick={set_storage_cap(*cap)}>{*label}</span>
+                        </>
+                    }) }
+                </a>
+                <a class="setting" title={if props.selected.is_some() { "export this chat" } else { "export all chats" }}>
+                    {"Export: "}
+                    <span onclick={export("json")}>{"JSON"}</span>{" · "}
+                    <span onclick={export("html")}>{"HTML"}</span>{" · "}
+                    <span onclick={export("text")}>{"Text"}</span>
+                </a>
+                <a class="setting" onclick={import} title="merge an exported history into this one">{"Import history"}</a>
+                if let Some(status) = &*history_status {
+                    <p class="export-status">{status}</p>
+                }
+            </div>
+            <ul>
+                { list }
+            </ul>
+            if *backing_up {
+                <BackupScreen on_confirmed={on_backup_confirmed} on_cancel={on_backup_cancel}/>
+            }
+            if *sharing {
+                <ShareScreen on_close={on_sharing_closed}/>
+            }
+        </aside>
+    }
+}
+
+fn user_entry(profile: &UserProfile, subtitle: Html) -> Html {
+    let avatar = profile
+        .avatar_url
+        .clone()
+        .unwrap_or_else(|| DEFAULT_AVATAR.to_string());
+    html! {
+        <>
+            <img src={avatar} alt=""/>
+            <div>
+                <h2>{&profile.display_name}</h2>
+                <h3>{subtitle}</h3>
+            </div>
+        </>
+    }
+}
+
+fn export_summary(report: &ExportReport) -> String {
+    let mut summary = format!("{} messages exported to {}", report.messages, report.path);
+    if let Some(attachments_path) = &report.attachments_path {
+        summary.push_str(&format!(", attachments in {}", attachments_path));
+    }
+    if !report.missing_attachments.is_empty() {
+        summary.push_str(&format!(
+            ", {} attachment files were missing",
+            report.missing_attachments.len()
+        ));
+    }
+    summary
+}
+
+fn import_summary(report: &ImportReport) -> String {
+    let mut summary = format!(
+        "{} new messages, {} already here",
+        report.messages_added, report.messages_already_here
+    );
+    if report.attachments_added > 0 {
+        summary.push_str(&format!(", {} attachments", report.attachments_added));
+    }
+    if !report.contacts_updated.is_empty() {
+        summary.push_str(&format!(
+            ", details of {}",
+            report.contacts_updated.join(", ")
+        ));
+    }
+    if !report.messages_conflicting.is_empty() {
+        summary.push_str(&format!(
+            ". {} messages differ from the ones here and are left out",
+            report.messages_conflicting.len()
+        ));
+    }
+    if !report.attachments_missing.is_empty() {
+        summary.push_str(&format!(
+            ". {} attachments aren't in the archive",
+            report.attachments_missing.len()
+        ));
+    }
+    if !report.contacts_missing.is_empty() {
+        summary.push_str(&format!(
+            ". Add {} to your contacts to bring back their details",
+            report.contacts_missing.join(", ")
+        ));
+    }
+    summary
+}
+
+// the profile with the nickname and avatar the user gave the contact
+fn known_as(contact: &UserProfile, entry: Option<&AddressBookEntry>) -> UserProfile {
+    let mut profile = contact.clone();
+    if let Some(entry) = entry {
+        if let Some(nickname) = &entry.nickname {
+            profile.display_name = nickname.clone();
+        }
+        if entry.custom_avatar_url.is_some() {
+            profile.avatar_url = entry.custom_avatar_url.clone();
+        }
+    }
+    profile
+}
+
+// the extract of a found message with the matching words marked
+fn snippet(parts: &[SnippetPart]) -> Html {
+    html! {
+        { for parts.iter().map(|part| if part.highlighted {
+            html! { <mark>{&part.text}</mark> }
+        } else {
+            html! { {&part.text} }
+        }) }
+    }
+}
@@ -0,0 +1,167 @@
+// mirrors of the backend types the webview receives from Tauri commands
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct UserProfile {
+    pub username: String,
+    pub display_name: String,
+    pub wallet_pubkey: String,
+    pub avatar_url: Option<String>,
+}
+
+// a contact as the user knows it
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct AddressBookEntry {
+    #[serde(flatten)]
+    pub profile: UserProfile,
+    pub nickname: Option<String>,
+    pub notes: Option<String>,
+    pub custom_avatar_url: Option<String>,
+    // the address of the profile before it changed, until the user verifies the new one
+    pub previous_wallet_pubkey: Option<String>,
+    pub addresses: Vec<ContactAddress>,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct ContactAddress {
+    pub id: u64,
+    pub username: String,
+    pub address: String,
+    pub label: String,
+    pub verified_at: Option<u64>,
+    pub added_at: u64,
+}
+
+pub const DEFAULT_AVATAR: &str =
+    "https://s3-us-west-2.amazonaws.com/s.cdpn.io/1940306/chat_avatar_01.jpg";
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: u32,
+    pub per_page: u32,
+    pub total: u64,
+}
+
+impl<T> Page<T> {
+    pub fn has_more(&self) -> bool {
+        ((self.page as u64) + 1) * (self.per_page as u64) < self.total
+    }
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct ChatMessage {
+    pub id: u64,
+    pub conversation_id: String,
+    pub sender: String,
+    pub body: String,
+    pub sent_at: u64,
+    pub client_id: Option<String>,
+    #[serde(default)]
+    pub status: MessageStatus,
+}
+
+// a slice of a conversation, oldest first
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct MessagePage {
+    pub messages: Vec<ChatMessage>,
+    // more messages past the slice, in the direction it was loaded
+    pub has_more: bool,
+}
+
+// a message of the local history matching a search
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct MessageSearchResult {
+    pub message: ChatMessage,
+    pub snippet: Vec<SnippetPart>,
+    // the other user of the conversation
+    pub contact: Option<String>,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct SnippetPart {
+    pub text: String,
+    pub highlighted: bool,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageStatus {
+    #[default]
+    Stored,
+    Delivered,
+    Read,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct Receipt {
+    pub conversation_id: String,
+    pub up_to: u64,
+    pub status: MessageStatus,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxState {
+    Pending,
+    Sent,
+    Failed,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct OutboxEntry {
+    pub client_id: String,
+    pub conversation_id: String,
+    pub body: String,
+    pub created_at: u64,
+    pub attempts: u32,
+    pub state: OutboxState,
+    pub last_error: Option<String>,
+}
+
+// state changes of the chat connection, messages come through their own event
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ConnectionEvent {
+    Connected,
+    Disconnected { reason: String, retry_in_secs: u64 },
+    ServerError { message: String },
+    LocalError { message: String },
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PresenceStatus {
+    Online,
+    Away,
+    Offline,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct PresenceUpdate {
+    pub username: String,
+    pub status: PresenceStatus,
+    pub last_seen: Option<u64>,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct Typing {
+    pub conversation_id: String,
+    pub username: String,
+}
+
+// the disappearing timer of a conversation, set by either side and applied by the server
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct RetentionUpdate {
+    pub conversation_id: String,
+    pub retention_secs: Option<u64>,
+    pub set_by: String,
+    pub set_at: u64,
+}
+
+// messages deleted from this device by their timer or the storage cap
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct ExpiredMessages {
+    pub conversation_id: String,
+    pub ids: Vec<u64>,
+}